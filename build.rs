@@ -3,7 +3,7 @@ extern crate protoc_rust;
 fn main() {
     protoc_rust::Codegen::new()
         .out_dir("src/source/protos")
-        .inputs(&["protos/netmessages.proto"])
+        .inputs(&["protos/netmessages.proto", "protos/cstrike15_usermessages.proto"])
         .include("protos")
         .run()
         .expect("protoc");
@@ -10,40 +10,50 @@ mod protoutil;
 use source::ConnectionlessChannel;
 use source::packets::*;
 use steam::SteamClient;
-use source::protos::{CMsg_CVars, CCLCMsg_SplitPlayerConnect, CMsg_CVars_CVar};
 use source::NetChannel;
+use source::signon::SignonTracker;
 
-use std::net::{UdpSocket, IpAddr};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
 use crate::source::netmessages::NetMessage;
 use crate::source::protos::NET_Messages;
-use log::{info, debug, trace};
+use log::{info, debug, trace, warn};
+
+// how long to wait for Steam to log on if we were started alongside it
+const STEAM_LOGON_TIMEOUT: Duration = Duration::from_secs(30);
+
+// the longest the whole connect handshake is allowed to take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how long to wait for a second S2C_Connection, which not every server sends
+const SECOND_CONNECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+// how long to wait on the server to progress signon before resending, and how many times
+const SIGNON_RESEND_TIMEOUT: Duration = Duration::from_millis(2000);
+const SIGNON_MAX_RETRIES: u32 = 5;
+
+// how long to wait for a datagram before checking on signon anyway, so a server that goes
+// quiet still gets the signon message resent
+const READ_TIMEOUT: Duration = Duration::from_millis(250);
 
 fn run() -> anyhow::Result<()>
 {
     pretty_env_logger::init();
 
     info!("Connecting to Steam...");
-    let _steam = SteamClient::connect()?;
+    let _steam = SteamClient::connect_with_logon_timeout(Some(STEAM_LOGON_TIMEOUT))?;
     //_steam.request_join_server(13759, )
     info!("Connected to Steam!");
 
-    // bind to some client socket
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-
-    // "connect" to udp server
-    socket.connect("192.168.201.128:6543")?;
-    let addr = socket.peer_addr()?;
+    // resolve and connect to the server over a connectionless channel
+    let mut stream = ConnectionlessChannel::connect("192.168.201.128:6543", None)?;
+    let addr = stream.peer_addr()?;
 
-    // promote to a connectionless netchannel
-    let mut stream = ConnectionlessChannel::new(socket)?;
+    // bound the time spent on the whole handshake
+    stream.set_deadline(Some(Instant::now() + CONNECT_TIMEOUT))?;
 
-    // request server info
-    let packet = A2sInfo::default();
-    //dbg!(&packet);
-    stream.send_packet(packet.into())?;
-
-    // receive server info response
-    let _res: S2aInfoSrc = stream.recv_packet_type()?;
+    // request server info, answering the server's challenge if it has one
+    let _res: S2aInfoSrc = stream.query_info()?;
     //dbg!(&_res);
 
     // request challenge
@@ -52,7 +62,7 @@ fn run() -> anyhow::Result<()>
     stream.send_packet(packet.into())?;
 
     // receive challenge response
-    let _res: S2cChallenge = stream.recv_packet_type()?;
+    let _res: S2cChallenge = stream.recv_packet_during("challenge")?;
     //dbg!(&_res);
 
     // verify the challenge
@@ -61,8 +71,9 @@ fn run() -> anyhow::Result<()>
     stream.send_packet(packet.into())?;
 
     // ensure we have successfully verified the challenge
-    let chal: S2cChallenge = stream.recv_packet_type()?;
+    let chal: S2cChallenge = stream.recv_packet_during("challenge")?;
     //dbg!(&_res);
+    chal.verify_cookie(_res.challenge_num)?;
 
     let ip_encoded: u32;
     if let IpAddr::V4(ip) = addr.ip()
@@ -83,6 +94,7 @@ fn run() -> anyhow::Result<()>
         ip_encoded,
         addr.port() as u32
     )?;
+    stream.check_deadline("reservation")?;
 
     // now we need to ask the steamworks api to generate our client an authentication ticket
     // to send to the server
@@ -90,81 +102,100 @@ fn run() -> anyhow::Result<()>
     // this ticket is basically an encrypted blob which is signed by the steam backend which proves
     // that we own the game we are trying to use and that we are who we say we are (so the server
     // can properly assign our steamid)
-    let auth_ticket = _steam.get_auth_ticket()?;
-    info!("Ticket length: {}", auth_ticket.len());
-    info!("SteamID: {}", _steam.get_steam_id().raw());
+    let auth = _steam.get_auth_info()?;
+    info!("Ticket length: {}", auth.auth_ticket.len());
+    info!("SteamID: {}", auth.steamid);
     info!("ReservationID: {}", reservation.reservationid);
 
-
-    let auth = SteamAuthInfo {
-        steamid: _steam.get_steam_id().raw(),
-        auth_ticket,
-    };
-
     // this protobuf packet is encoded directly into the C2S_CONNECT packet
     // it contains all of our userinfo convars, and some of them are verified for integrity
     // in the authentication process
-    let mut split_connect = CCLCMsg_SplitPlayerConnect::new();
-    let mut convars = CMsg_CVars::new();
-
-    let mut cvar = CMsg_CVars_CVar::new();
-    cvar.set_name("cl_session".to_string());
-    cvar.set_value(format!("${:#x}", reservation.reservationid));
-
-    convars.cvars.push(cvar);
-
-    split_connect.set_convars(convars);
-
-    let mut player_connects = Vec::with_capacity(1);
-    player_connects.push(split_connect);
-
-    let conn = C2sConnect{
-        host_version: chal.host_version,
-        auth_protocol: AuthProtocolType::PROTOCOL_STEAM,
-        challenge_num: chal.challenge_num,
-        player_name: String::new(), // not used cs:go, uses "name" from the protobuf above^
-        server_password: String::from("a59CdkwjR4"),
-        num_players: 1, // no split screen
-        split_player_connect: player_connects,
-        low_violence: false,
-        lobby_cookie: reservation.reservationid,
-        crossplay_platform: CrossplayPlatform::Pc,
-        encryption_key_index: 0, // no steam2 cert encryption
-        auth_info: auth,
-    };
+    let session = format!("${:#x}", reservation.reservationid);
+    let convars = build_convars(&[("cl_session", &session)]);
+
+    // cs:go takes the player name from the "name" convar, C2sConnect::set_player_name sets both
+    let mut conn = C2sConnect::new(&chal, auth);
+    conn.server_password = String::from("a59CdkwjR4");
+    conn.set_convars(convars);
+    conn.lobby_cookie = reservation.reservationid;
 
     // send off the connect packet
     stream.send_packet(conn.into())?;
 
     // assuming everything worked out, we should get S2CConnection back, which means we have established
     // a netchannel
-    // we actually receive two different S2C_Connection packets, neither of them actually matter.
-    let _connection_pkt: S2cConnection = stream.recv_packet_type()?;
-    let _connection_pkt: S2cConnection = stream.recv_packet_type()?;
+    // depending on the server we receive one or two S2C_Connection packets, neither of them actually matter.
+    let _connection_pkt: S2cConnection = stream.recv_packet_during("connect")?;
     debug!("Connect packet: {:?}", &_connection_pkt);
+
+    // a reservation the server doesn't agree with otherwise just looks like a silent rejection later on
+    if let Err(e) = _connection_pkt.verify_lobby_cookie(reservation.reservationid) {
+        warn!("{}", e);
+    }
+
+    // only wait a little while for the second one, the server may not send it at all
+    let _connection_pkt: Option<S2cConnection> = stream.recv_packet_timeout(SECOND_CONNECTION_TIMEOUT)?;
+    debug!("Second connect packet: {:?}", &_connection_pkt);
     info!("Successfully established a netchannel.");
 
+    // handshake is done, the netchannel manages its own reads from here
+    stream.set_deadline(None)?;
+
     let mut channel = NetChannel::upgrade(stream, chal.host_version)?;
+
+    // show anything the server prints to our console
+    channel.set_print_handler(|text| info!("[SERVER] {}", text.trim_end()));
+
+    // and let us know as the server moves us through signon
+    channel.set_signon_change_handler(|state| info!("Signon state is now {}", state));
+
     let mut signon = source::protos::CNETMsg_SignonState::new();
     signon.set_signon_state(2);
 
+    // keep track of signon so lost signon messages get resent
+    let mut signon_tracker = SignonTracker::new(SIGNON_RESEND_TIMEOUT, SIGNON_MAX_RETRIES);
+
     let msg = NetMessage::from_proto(Box::new(signon), NET_Messages::net_SignonState as i32);
+    signon_tracker.sent(&msg)?;
     let err = channel.write_netmessage(msg);
     debug!("Packet result: {:?}", &err);
 
     loop{
-        // read incoming data
-        let datagram = channel.read_data()?;
-        if let Some(messages) = datagram.get_messages()
-        {
-            for msg in messages.into_iter()
+        // read incoming data, timing out now and then so signon is checked on even if the
+        // server has stopped sending
+        if let Some(datagram) = channel.read_data_timeout(READ_TIMEOUT)? {
+            let was_complete = signon_tracker.is_complete();
+            signon_tracker.observe(&datagram);
+
+            // say hello once we're fully in the game
+            if !was_complete && signon_tracker.is_complete() {
+                channel.send_command("say hello")?;
+            }
+
+            if let Some(messages) = datagram.get_messages()
             {
-                trace!("* {}", msg.get_type_name());
+                for msg in messages.into_iter()
+                {
+                    trace!("* {}", msg.get_type_name());
+                }
+            }
+
+            // acknowledge any new entity baseline, otherwise send a blank message just to keep
+            // the other side updated
+            if !channel.ack_baseline()? {
+                channel.write_nop()?;
             }
         }
 
-        // blank message just to keep the other side updated
-        channel.write_nop()?;
+        // resend the last signon message if the server hasn't moved on, this gives up with
+        // an error if it never does
+        if let Some(msg) = signon_tracker.poll()? {
+            debug!("Resending signon message at state {}", signon_tracker.get_state());
+            channel.write_netmessage(msg)?;
+        }
+
+        // keep anything we're sending reliably moving, even while the server is quiet
+        channel.tick()?;
     }
     //::std::thread::sleep(std::time::Duration::from_millis(10000));
     //Ok(())
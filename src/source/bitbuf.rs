@@ -7,7 +7,7 @@ pub type BitBufReaderType<'a> = BitReader<std::io::Cursor<&'a [u8]>, LittleEndia
 
 // A bit buffer writer type which writes bits in little endian
 // Used for writing messages to a stream
-pub type BitBufWriterType<'a> = BitWriter<std::io::Cursor<&'a mut Vec<u8>>, LittleEndian>;
+pub type BitBufWriterType<'a> = CountingBitWriter<std::io::Cursor<&'a mut Vec<u8>>>;
 
 // read useful types from a bit buffer
 pub trait WireReader
@@ -15,9 +15,32 @@ pub trait WireReader
     fn read_long(&mut self) -> Result<u32>;
     fn read_longlong(&mut self) -> Result<u64>;
     fn read_word(&mut self) -> Result<u16>;
+    fn read_i16(&mut self) -> Result<i16>;
+    fn read_i32(&mut self) -> Result<i32>;
     fn read_char(&mut self) -> Result<u8>;
     fn read_string(&mut self) -> Result<String>;
     fn read_int32_var(&mut self) -> Result<u32>;
+    fn read_ubitvar(&mut self) -> Result<u32>;
+    fn read_bytes_into(&mut self, buf: &mut [u8]) -> Result<()>;
+    fn read_bytes_vec(&mut self, n: usize) -> Result<Vec<u8>>;
+}
+
+// number of extra bits following the first 6 bits of a ubitvar, chosen by the 2 bit selector
+const UBITVAR_EXTRA_BITS: [u32; 4] = [0, 4, 8, 28];
+
+// bytes read_bytes_vec fills at a time, so a bogus length read off the wire can only allocate
+// about as much as the stream really holds
+const READ_BYTES_CHUNK: usize = 4096;
+
+// pick the ubitvar selector for a value (4, 8, 12 or 32 bits wide)
+fn ubitvar_selector(num: u32) -> u32
+{
+    match num {
+        0..=0xF => 0,
+        0x10..=0xFF => 1,
+        0x100..=0xFFF => 2,
+        _ => 3,
+    }
 }
 
 // reads values from a buffer
@@ -42,6 +65,19 @@ impl<T> WireReader for BitReader<T, LittleEndian>
         Ok(self.read::<u16>(16)?)
     }
 
+    // read a little endian two's complement 16-bit int from the stream
+    fn read_i16(&mut self) -> Result<i16>
+    {
+        Ok(self.read_signed::<i16>(16)?)
+    }
+
+    // read a little endian two's complement 32-bit int from the stream
+    // fields which are signed on the wire but used as lengths must be checked for negatives
+    fn read_i32(&mut self) -> Result<i32>
+    {
+        Ok(self.read_signed::<i32>(32)?)
+    }
+
     // read a single byte from the stream
     fn read_char(&mut self) -> Result<u8>
     {
@@ -99,6 +135,45 @@ impl<T> WireReader for BitReader<T, LittleEndian>
 
         Ok(res)
     }
+
+    // fill the whole of `buf` with bytes from the stream
+    fn read_bytes_into(&mut self, buf: &mut [u8]) -> Result<()>
+    {
+        self.read_bytes(buf)?;
+
+        Ok(())
+    }
+
+    // read `n` bytes from the stream into a new vector. `n` often comes off the wire, so the
+    // vector grows a chunk at a time as bytes are read rather than being sized up front
+    fn read_bytes_vec(&mut self, n: usize) -> Result<Vec<u8>>
+    {
+        let mut buf = Vec::with_capacity(std::cmp::min(n, READ_BYTES_CHUNK));
+
+        while buf.len() < n {
+            let start = buf.len();
+            buf.resize(start + std::cmp::min(n - start, READ_BYTES_CHUNK), 0);
+            self.read_bytes_into(&mut buf[start..])?;
+        }
+
+        Ok(buf)
+    }
+
+    /// source engine "UBitVar" encoding, the low 4 bits of the value and a 2 bit selector
+    /// followed by 0, 4, 8 or 28 more bits of the value
+    fn read_ubitvar(&mut self) -> Result<u32>
+    {
+        let head = self.read::<u32>(6)?;
+
+        let extra_bits = UBITVAR_EXTRA_BITS[((head >> 4) & 3) as usize];
+        if extra_bits == 0 {
+            return Ok(head & 0xF);
+        }
+
+        let rest = self.read::<u32>(extra_bits)?;
+
+        Ok((head & 0xF) | (rest << 4))
+    }
 }
 
 // wrapper to write network data as source engine expects on the wire
@@ -111,6 +186,8 @@ pub trait WireWriter
     fn write_string(&mut self, s: &str) -> Result<()>;
     fn write_bit(&mut self, bit: bool) -> Result<()>;
     fn write_int32_var(&mut self, num: u32) -> Result<()>;
+    fn write_ubitvar(&mut self, num: u32) -> Result<()>;
+    fn byte_align(&mut self) -> Result<()>;
 }
 
 impl<T> WireWriter for BitWriter<T, LittleEndian>
@@ -187,5 +264,293 @@ impl<T> WireWriter for BitWriter<T, LittleEndian>
         self.write(8, data & 0x7F)?;
         Ok(())
     }
+
+    // source engine "UBitVar" encoding, see WireReader::read_ubitvar
+    #[inline]
+    fn write_ubitvar(&mut self, num: u32) -> Result<()>
+    {
+        let selector = ubitvar_selector(num);
+
+        self.write(6, (num & 0xF) | (selector << 4))?;
+
+        let extra_bits = UBITVAR_EXTRA_BITS[selector as usize];
+        if extra_bits > 0 {
+            self.write(extra_bits, num >> 4)?;
+        }
+
+        Ok(())
+    }
+
+    // pad with zero bits up to the next byte boundary
+    #[inline]
+    fn byte_align(&mut self) -> Result<()>
+    {
+        while !self.byte_aligned()
+        {
+            self.write_bit(false)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A little endian bit writer which keeps track of how many bits have been written through it
+// Used for packets with bit-level framing that need to know their position in the stream
+pub struct CountingBitWriter<T>
+    where T: std::io::Write
+{
+    // the underlying bit writer
+    writer: BitWriter<T, LittleEndian>,
+
+    // number of bits written so far
+    bits_written: u64,
+}
+
+impl<T> CountingBitWriter<T>
+    where T: std::io::Write
+{
+    // wrap a writer to write bits in little endian
+    pub fn new(writer: T) -> Self
+    {
+        CountingBitWriter {
+            writer: BitWriter::endian(writer, LittleEndian),
+            bits_written: 0,
+        }
+    }
+
+    // the total number of bits written so far
+    pub fn bits_written(&self) -> u64
+    {
+        self.bits_written
+    }
+
+    // write raw bytes to the stream
+    #[inline]
+    pub fn write_bytes(&mut self, buf: &[u8]) -> Result<()>
+    {
+        self.writer.write_bytes(buf)?;
+        self.bits_written += (buf.len() as u64) * 8;
+
+        Ok(())
+    }
+}
+
+impl<T> WireWriter for CountingBitWriter<T>
+    where T: std::io::Write
+{
+    #[inline]
+    fn write_long(&mut self, num: u32) -> Result<()>
+    {
+        self.writer.write_long(num)?;
+        self.bits_written += 32;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_longlong(&mut self, num: u64) -> Result<()>
+    {
+        self.writer.write_longlong(num)?;
+        self.bits_written += 64;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_word(&mut self, num: u16) -> Result<()>
+    {
+        self.writer.write_word(num)?;
+        self.bits_written += 16;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_char(&mut self, num: u8) -> Result<()>
+    {
+        self.writer.write_char(num)?;
+        self.bits_written += 8;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_string(&mut self, s: &str) -> Result<()>
+    {
+        self.writer.write_string(s)?;
+
+        // string and null terminator
+        self.bits_written += ((s.len() as u64) + 1) * 8;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_bit(&mut self, bit: bool) -> Result<()>
+    {
+        self.writer.write_bit(bit)?;
+        self.bits_written += 1;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_int32_var(&mut self, mut data: u32) -> Result<()>
+    {
+        // one byte for every 7 bits of data
+        while data > 0x7F
+        {
+            self.write_char(((data & 0x7F) | 0x80) as u8)?;
+            data >>= 7;
+        }
+        self.write_char((data & 0x7F) as u8)?;
+
+        Ok(())
+    }
+
+    // source engine "UBitVar" encoding, see WireReader::read_ubitvar
+    #[inline]
+    fn write_ubitvar(&mut self, num: u32) -> Result<()>
+    {
+        self.writer.write_ubitvar(num)?;
+        self.bits_written += 6 + (UBITVAR_EXTRA_BITS[ubitvar_selector(num) as usize] as u64);
+
+        Ok(())
+    }
+
+    // pad with zero bits up to the next byte boundary
+    #[inline]
+    fn byte_align(&mut self) -> Result<()>
+    {
+        while self.bits_written % 8 != 0
+        {
+            self.write_bit(false)?;
+        }
+
+        Ok(())
+    }
 }
 
+// a 32-bit value sent in network (big endian) byte order, unlike everything else on the wire.
+// the size field which frames an encrypted datagram is the only one, so it gets its own type
+// rather than a byte swap that's easy to forget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkOrderU32(pub u32);
+
+impl NetworkOrderU32
+{
+    // read a network order long from the stream
+    pub fn read<R: WireReader>(reader: &mut R) -> Result<NetworkOrderU32>
+    {
+        Ok(NetworkOrderU32(reader.read_long()?.swap_bytes()))
+    }
+
+    // write the value to the stream in network order
+    pub fn write<W: WireWriter>(self, writer: &mut W) -> Result<()>
+    {
+        writer.write_long(self.0.swap_bytes())
+    }
+
+    // the value in native byte order
+    pub fn get(self) -> u32
+    {
+        self.0
+    }
+}
+
+#[test]
+fn test_byte_align() {
+    let mut buf: Vec<u8> = Vec::new();
+
+    {
+        let mut writer = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+
+        writer.write_bit(true).unwrap();
+        assert_eq!(writer.bits_written(), 1);
+
+        // pads the remaining 7 bits of the byte
+        writer.byte_align().unwrap();
+        assert_eq!(writer.bits_written(), 8);
+
+        // already aligned, nothing more to write
+        writer.byte_align().unwrap();
+        assert_eq!(writer.bits_written(), 8);
+    }
+
+    assert_eq!(buf, [0x01]);
+}
+
+#[test]
+fn test_ubitvar() {
+    // values at the edges of each width tier, and the total bits they take up
+    let values: [(u32, u64); 8] = [
+        (0, 6),
+        (0xF, 6),
+        (0x10, 10),
+        (0xFF, 10),
+        (0x100, 14),
+        (0xFFF, 14),
+        (0x1000, 34),
+        (0xFFFFFFFF, 34),
+    ];
+
+    for &(value, bits) in values.iter() {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+            writer.write_ubitvar(value).unwrap();
+            assert_eq!(writer.bits_written(), bits, "bits for {:#x}", value);
+            writer.byte_align().unwrap();
+        }
+
+        let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+        assert_eq!(reader.read_ubitvar().unwrap(), value);
+    }
+
+    // 0x10 is the low nibble 0 with selector 1, then the upper nibble 1
+    let data: [u8; 2] = [0x50, 0x00];
+    let mut reader = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+    assert_eq!(reader.read_ubitvar().unwrap(), 0x10);
+}
+
+#[test]
+fn test_read_signed() {
+    let data: [u8; 6] = [0xFE, 0xFF, 0x00, 0x00, 0x00, 0x80];
+    let mut reader = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+
+    assert_eq!(reader.read_i16().unwrap(), -2);
+    assert_eq!(reader.read_i32().unwrap(), i32::MIN);
+}
+
+#[test]
+fn test_read_bytes_vec() {
+    let data: [u8; 5] = [1, 2, 3, 4, 5];
+    let mut reader = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+
+    assert_eq!(reader.read_bytes_vec(3).unwrap(), [1, 2, 3]);
+
+    // not enough left to fill the request
+    assert!(reader.read_bytes_vec(3).is_err());
+
+    // a length far past the end fails after the first chunk instead of allocating all of it
+    let mut reader = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+    assert!(reader.read_bytes_vec(1 << 40).is_err());
+
+    // lengths over a chunk are read in full
+    let long: Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&long[..]), LittleEndian);
+    assert_eq!(reader.read_bytes_vec(long.len()).unwrap(), long);
+}
+
+#[test]
+fn test_network_order() {
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+        NetworkOrderU32(0x01020304).write(&mut writer).unwrap();
+    }
+    assert_eq!(buf, [0x01, 0x02, 0x03, 0x04]);
+
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert_eq!(NetworkOrderU32::read(&mut reader).unwrap().get(), 0x01020304);
+}
@@ -0,0 +1,109 @@
+// buffers kept around for reuse by default, enough for nested decodes (e.g. encrypted data
+// inside a reliable payload) without allocating
+const DEFAULT_MAX_BUFFERS: usize = 4;
+
+// largest capacity a buffer may keep when it is returned to the pool by default, anything
+// bigger was only needed for one huge message and is freed instead
+pub const DEFAULT_MAX_RETAINED: usize = 64 * 1024;
+
+// the size new buffers are created with
+const INITIAL_CAPACITY: usize = 0x1000 * 2;
+
+// a small pool of byte buffers which are handed out for encoding/decoding netmessages and
+// recycled afterwards, so steady traffic doesn't allocate per message
+pub struct BufferPool
+{
+    // buffers ready to be handed out again, always empty
+    free: Vec<Vec<u8>>,
+
+    // most buffers kept in the pool
+    max_buffers: usize,
+
+    // largest capacity a buffer may keep when returned
+    max_retained: usize,
+}
+
+impl BufferPool
+{
+    pub fn new(max_retained: usize) -> Self
+    {
+        BufferPool {
+            free: Vec::new(),
+            max_buffers: DEFAULT_MAX_BUFFERS,
+            max_retained,
+        }
+    }
+
+    // change the largest capacity a returned buffer may keep, buffers already in the
+    // pool are trimmed to fit
+    pub fn set_max_retained(&mut self, max_retained: usize)
+    {
+        self.max_retained = max_retained;
+
+        for buf in self.free.iter_mut() {
+            if buf.capacity() > max_retained {
+                buf.shrink_to_fit();
+            }
+        }
+    }
+
+    // take an empty buffer out of the pool, allocating a new one if there are none left
+    pub fn take(&mut self) -> Vec<u8>
+    {
+        match self.free.pop() {
+            Some(buf) => buf,
+            None => Vec::with_capacity(std::cmp::min(INITIAL_CAPACITY, self.max_retained)),
+        }
+    }
+
+    // return a buffer to the pool. it is dropped if the pool is full, and has its memory
+    // given back if it grew past the retained capacity
+    pub fn give(&mut self, mut buf: Vec<u8>)
+    {
+        if self.free.len() >= self.max_buffers {
+            return;
+        }
+
+        buf.clear();
+        if buf.capacity() > self.max_retained {
+            buf.shrink_to_fit();
+        }
+
+        self.free.push(buf);
+    }
+
+    // the total capacity held by buffers sitting in the pool
+    pub fn retained_capacity(&self) -> usize
+    {
+        return self.free.iter().map(|buf| buf.capacity()).sum();
+    }
+}
+
+#[test]
+fn test_buffer_pool_recycles() {
+    let mut pool = BufferPool::new(1024);
+
+    let mut buf = pool.take();
+    buf.extend_from_slice(&[1, 2, 3]);
+    let ptr = buf.as_ptr();
+    pool.give(buf);
+
+    // the same allocation comes back out, emptied
+    let buf = pool.take();
+    assert!(buf.is_empty());
+    assert_eq!(buf.as_ptr(), ptr);
+    pool.give(buf);
+
+    // a buffer grown for a huge message doesn't stay that big
+    let mut big = pool.take();
+    big.resize(1 << 20, 0);
+    pool.give(big);
+    assert!(pool.retained_capacity() <= 1024);
+
+    // and the pool only holds on to so many buffers
+    let bufs: Vec<Vec<u8>> = (0..10).map(|_| pool.take()).collect();
+    for buf in bufs {
+        pool.give(buf);
+    }
+    assert_eq!(pool.free.len(), DEFAULT_MAX_BUFFERS);
+}
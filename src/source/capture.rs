@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use anyhow::Result;
+use crate::source::netmessages::NetMessage;
+
+// magic bytes at the start of every capture file, followed by the format version
+const CAPTURE_MAGIC: [u8; 4] = *b"SENM";
+const CAPTURE_VERSION: u32 = 1;
+
+// A single netmessage as it was received off the network
+//
+// Each record in a capture file is laid out little endian as:
+//   u64 timestamp (milliseconds since the unix epoch)
+//   i32 netmessage id
+//   u32 size of the message
+//   [u8; size] the encoded protobuf message
+#[derive(Debug, PartialEq)]
+pub struct CaptureRecord
+{
+    // when the message was received, in milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+
+    // the netmessage enum identifier for this message
+    pub id: i32,
+
+    // the encoded protobuf message
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord
+{
+    // decode the captured message, just as it would have been when it was received
+    pub fn bind(&self) -> Result<NetMessage>
+    {
+        NetMessage::bind(self.id, &self.data)
+    }
+}
+
+// Writes netmessages to a capture file as they are received
+pub struct CaptureWriter
+{
+    file: BufWriter<File>,
+}
+
+impl CaptureWriter
+{
+    // create (or truncate) a capture file and write its header
+    pub fn create(path: &Path) -> Result<Self>
+    {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(&CAPTURE_MAGIC)?;
+        file.write_u32::<LittleEndian>(CAPTURE_VERSION)?;
+
+        Ok(Self { file })
+    }
+
+    // append a received netmessage to the capture, stamped with the current time
+    pub fn write_message(&mut self, id: i32, data: &[u8]) -> Result<()>
+    {
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        self.file.write_u64::<LittleEndian>(timestamp_ms)?;
+        self.file.write_i32::<LittleEndian>(id)?;
+        self.file.write_u32::<LittleEndian>(data.len() as u32)?;
+        self.file.write_all(data)?;
+
+        Ok(())
+    }
+
+    // flush everything written so far out to the file
+    pub fn flush(&mut self) -> Result<()>
+    {
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+// Reads netmessages back out of a capture file
+pub struct CaptureReader
+{
+    file: BufReader<File>,
+}
+
+impl CaptureReader
+{
+    // open a capture file and check its header
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(anyhow::anyhow!("Not a netmessage capture file"));
+        }
+
+        let version = file.read_u32::<LittleEndian>()?;
+        if version != CAPTURE_VERSION {
+            return Err(anyhow::anyhow!("Unsupported capture version {}", version));
+        }
+
+        Ok(Self { file })
+    }
+
+    // read the next message from the capture, None at the end of the file
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>>
+    {
+        // a clean end of file can only happen between records
+        let timestamp_ms = match self.file.read_u64::<LittleEndian>() {
+            Ok(timestamp_ms) => timestamp_ms,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let id = self.file.read_i32::<LittleEndian>()?;
+        let size = self.file.read_u32::<LittleEndian>()? as usize;
+
+        let mut data = vec![0u8; size];
+        self.file.read_exact(&mut data)?;
+
+        Ok(Some(CaptureRecord { timestamp_ms, id, data }))
+    }
+}
+
+#[test]
+fn test_capture_roundtrip() {
+    let path = std::env::temp_dir().join(format!("se-client-capture-{}.bin", std::process::id()));
+
+    {
+        let mut writer = CaptureWriter::create(&path).unwrap();
+        writer.write_message(4, &[0x08, 0x2A]).unwrap();
+        writer.write_message(16, &[]).unwrap();
+        writer.flush().unwrap();
+    }
+
+    let mut reader = CaptureReader::open(&path).unwrap();
+
+    let record = reader.next_record().unwrap().unwrap();
+    assert_eq!((record.id, record.data.as_slice()), (4, &[0x08, 0x2A][..]));
+    assert!(record.bind().is_ok());
+
+    let record = reader.next_record().unwrap().unwrap();
+    assert_eq!((record.id, record.data.len()), (16, 0));
+
+    assert!(reader.next_record().unwrap().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -1,30 +1,58 @@
-use std::net::UdpSocket;
+use std::net::{UdpSocket, SocketAddr, ToSocketAddrs};
 use anyhow::{Result, Context};
 use super::packetbase::*;
 use super::bitbuf::*;
 use pretty_hex::PrettyHex;
 use crate::source::ice::IceEncryption;
-use bitstream_io::BigEndian;
-use std::cell::{RefCell, Ref, Cell};
+use std::cell::{RefCell, Ref};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 use crc32fast::Hasher;
 use std::io::Cursor;
-use crate::source::netmessages::NetMessage;
-use crate::source::subchannel::{SubChannel, TransferBuffer, SubchannelStreamType};
+use crate::source::netmessages::{NetMessage, NetMessageRegistry};
+use crate::source::subchannel::{SubChannel, TransferBuffer, TransferInfo, SubchannelStreamType, OutgoingTransfer, ReliableSender, MAX_STREAMS, MAX_SUBCHANNELS};
 use log::{trace, warn};
 use crate::source::lzss::Lzss;
-use smallvec::SmallVec;
+use crate::source::transport::PacketTransport;
+use crate::source::capture::CaptureWriter;
+use crate::source::bufpool::{BufferPool, DEFAULT_MAX_RETAINED};
+use crate::source::gameevents::{GameEvent, GameEventLog};
+use crate::source::voice::VoiceEncoder;
+use crate::source::packets::{A2aAck, A2sInfo, S2aInfoSrc, A2sPlayer, S2aPlayer, A2sRules, S2aRules, QUERY_CHALLENGE_REQUEST};
+use std::fmt;
+use std::time::{Duration, Instant};
+use std::path::Path;
+use crate::source::protos::{CNETMsg_Tick, CNETMsg_SetConVar, CNETMsg_Disconnect, CNETMsg_SignonState, CNETMsg_StringCmd, CSVCMsg_EncryptedData, CSVCMsg_PacketEntities, CSVCMsg_Print, CSVCMsg_SetPause, CCLCMsg_BaselineAck, CCLCMsg_VoiceData, CSVCMsg_VoiceInit, VoiceDataFormat_t, CLC_Messages, NET_Messages};
+use std::collections::{HashMap, VecDeque};
 
 // implements a buffered udp reader
-pub struct BufUdp
+pub struct BufUdp<S = UdpSocket>
 {
     // mutable vector that we will read messages from the udp socket to
     inner_vec: Vec<u8>,
 
     // socket to read from
-    socket: UdpSocket,
+    socket: S,
 
     // size of the message in the buffer
     message_len: usize,
+
+    // largest datagram that will be accepted
+    max_payload: usize,
+
+    // whether outgoing packets get compressed
+    compression: CompressionMode,
+}
+
+/// When outgoing packets should be LZSS compressed before being sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionMode
+{
+    /// Always send packets as they are
+    Never,
+
+    /// Compress packets larger than this many bytes, as long as it makes them smaller
+    Above(usize),
 }
 
 /// the maximum UDP payload that will ever be sent, or should ever be received
@@ -32,22 +60,240 @@ const NET_MAXPAYLOAD: usize = 262192;
 const PACKET_CHOKED: u8 = 1 << 4;
 const PACKET_RELIABLE: u8 = 1<<0;
 
+/// how many of the latest incoming sequence numbers the packet loss estimate covers
+const PACKET_LOSS_WINDOW: usize = 128;
+
 /// Specifies that a datagram packet is a split packet
 const NET_HEADER_FLAG_SPLITPACKET: u32 = 0xFFFFFFFE;
 const NET_HEADER_FLAG_COMPRESSEDPACKET: u32 = 0xFFFFFFFD;
 const CONNECTIONLESS_HEADER: u32 = 0xFFFFFFFF;
 
+/// Errors raised by a channel that callers may want to handle specifically
+#[derive(Debug)]
+pub enum ChannelError
+{
+    /// The remote end sent data that does not follow the netchannel protocol
+    Protocol(&'static str),
+
+    /// The remote end is no longer accepting packets on this connection
+    ConnectionClosed,
+
+    /// The connect deadline passed while the named handshake phase was in progress
+    HandshakeTimeout(&'static str),
+
+    /// The server disconnected us with net_Disconnect, `text` is the reason it gave. `datagram`
+    /// is the datagram the disconnect arrived in, along with any other messages sent before it
+    Disconnected { reason: KickReason, text: String, datagram: Box<NetDatagram> },
+
+    /// A datagram decrypted fine but could not be parsed, `bytes` is the decrypted datagram
+    /// so the failure can be reproduced
+    ParseFailed { bytes: Vec<u8>, source: anyhow::Error },
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self
+        {
+            ChannelError::Protocol(reason) => write!(f, "Protocol error: {}", reason),
+            ChannelError::ConnectionClosed => write!(f, "Connection closed by remote host"),
+            ChannelError::HandshakeTimeout(phase) => write!(f, "Handshake timed out during {}", phase),
+            ChannelError::Disconnected { reason, text, .. } => write!(f, "Disconnected by server ({:?}): {}", reason, text),
+            ChannelError::ParseFailed { bytes, source } => write!(f, "Failed to parse {} byte datagram: {}", bytes.len(), source),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self
+        {
+            ChannelError::ParseFailed { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+/// The voice codec and quality the server announced with svc_VoiceInit
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceSettings
+{
+    /// codec name, "steam" for steam voice or an engine codec such as "vaudio_celt"
+    pub codec: String,
+
+    /// codec quality setting
+    pub quality: i32,
+
+    /// codec version
+    pub version: i32,
+}
+
+/// Which parts of a netchannel are switched on, see `NetChannel::upgrade_with_config`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetChannelConfig
+{
+    /// receive reliable netmessages sent over the message stream, on by default. a client which
+    /// only downloads files can turn this off
+    pub message_stream: bool,
+
+    /// receive files sent over the file stream, off by default. the channel has nowhere to put
+    /// a file yet, so one that does arrive is dropped with a warning
+    pub file_stream: bool,
+
+    /// fail when a reliable transfer can't be decompressed, see `NetChannel::set_strict_decompression`
+    pub strict_decompression: bool,
+}
+
+impl Default for NetChannelConfig
+{
+    fn default() -> Self
+    {
+        NetChannelConfig {
+            message_stream: true,
+            file_stream: false,
+            strict_decompression: false,
+        }
+    }
+}
+
+/// Why the server disconnected us, worked out from the reason text of net_Disconnect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KickReason
+{
+    /// The account is VAC banned or failed an anti-cheat check
+    VacBanned,
+
+    /// The client and server are running different versions of the game
+    VersionMismatch,
+
+    /// The account is banned from this server
+    Banned,
+
+    /// The server could not verify our Steam login
+    NoSteamLogon,
+
+    /// Kicked by an admin or the server console
+    Kicked,
+
+    /// Any other reason, e.g. the server shutting down
+    Other,
+}
+
+/// Reason texts and localization tokens the engine disconnects clients with
+const KICK_REASONS: &[(&str, KickReason)] = &[
+    ("VAC banned from secure server", KickReason::VacBanned),
+    ("You are running an external tool that is incompatible with Secure servers", KickReason::VacBanned),
+    ("Server uses different class tables", KickReason::VersionMismatch),
+    ("You have been banned from this server", KickReason::Banned),
+    ("Added to banned list", KickReason::Banned),
+    ("No Steam logon", KickReason::NoSteamLogon),
+    ("STEAM validation rejected", KickReason::NoSteamLogon),
+    ("Kicked by Console", KickReason::Kicked),
+    ("You have been kicked from this server", KickReason::Kicked),
+    ("#Player_DisconnectReason_VotedOff", KickReason::Kicked),
+];
+
+impl KickReason
+{
+    /// classify the reason text sent by the server. the whole text has to be one of the
+    /// engine's reasons, optionally followed by `: <details>` (e.g. an admin's kick message)
+    pub fn from_text(text: &str) -> KickReason
+    {
+        let text = text.trim().trim_end_matches('.');
+
+        for (reason_text, reason) in KICK_REASONS.iter() {
+            if text.len() < reason_text.len() || !text.is_char_boundary(reason_text.len()) {
+                continue;
+            }
+
+            let (head, details) = text.split_at(reason_text.len());
+            if head.eq_ignore_ascii_case(reason_text) && (details.is_empty() || details.trim_start().starts_with(':')) {
+                return *reason;
+            }
+        }
+
+        KickReason::Other
+    }
+}
+
+/// true if the error was caused by reading past the end of a buffer
+fn is_unexpected_eof(error: &anyhow::Error) -> bool
+{
+    error.chain().any(|cause| {
+        match cause.downcast_ref::<std::io::Error>() {
+            Some(io_error) => io_error.kind() == std::io::ErrorKind::UnexpectedEof,
+            None => false,
+        }
+    })
+}
+
+/// true if the error was caused by a socket read timing out
+fn is_timed_out(error: &anyhow::Error) -> bool
+{
+    error.chain().any(|cause| {
+        match cause.downcast_ref::<std::io::Error>() {
+            Some(io_error) => matches!(io_error.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut),
+            None => false,
+        }
+    })
+}
+
+/// The kind of a datagram as received off the wire
+#[derive(Debug, PartialEq)]
+enum DatagramKind
+{
+    /// one piece of a larger datagram which was split to fit the MTU
+    Split,
+
+    /// an unencrypted connectionless packet
+    Connectionless,
+
+    /// an ICE encrypted netchannel datagram
+    Netchannel,
+}
+
+/// decide how a datagram should be handled from its first 4 bytes, read little endian
+///
+/// The engine only encrypts the netchannel datagram itself. Split packet and connectionless
+/// headers are written in the clear around (or instead of) it, so they have to be checked on the
+/// raw bytes before anything is decrypted. The compressed packet marker is part of the
+/// netchannel datagram and is only checked after decryption (see `parse_datagram`).
+///
+/// An encrypted datagram whose first 4 bytes happen to encrypt to one of these markers can't be
+/// told apart from a real one, the engine has the same ambiguity and routes it the same way.
+fn classify_datagram(datagram: &[u8]) -> Result<DatagramKind>
+{
+    if datagram.len() < 4 {
+        return Err(ChannelError::Protocol("datagram too short for a header").into());
+    }
 
-impl BufUdp
+    let header = u32::from_le_bytes([datagram[0], datagram[1], datagram[2], datagram[3]]);
+    match header {
+        NET_HEADER_FLAG_SPLITPACKET => Ok(DatagramKind::Split),
+        CONNECTIONLESS_HEADER => Ok(DatagramKind::Connectionless),
+        _ => Ok(DatagramKind::Netchannel),
+    }
+}
+
+impl<S> BufUdp<S>
+    where S: PacketTransport
 {
-    fn new(socket: UdpSocket) -> BufUdp
+    fn new(socket: S) -> Self
+    {
+        Self::with_max_payload(socket, NET_MAXPAYLOAD)
+    }
+
+    // create a buffered reader which rejects datagrams larger than max_payload
+    fn with_max_payload(socket: S, max_payload: usize) -> Self
     {
         BufUdp
         {
-            // preallocate space for the largest possible payload
-            inner_vec: vec![0; NET_MAXPAYLOAD],
+            // preallocate space for the largest possible payload, plus one byte so that
+            // a datagram which is too large can be told apart from one that fits exactly
+            inner_vec: vec![0; max_payload + 1],
             socket,
             message_len: 0,
+            max_payload,
+            compression: CompressionMode::Never,
         }
     }
 
@@ -56,23 +302,45 @@ impl BufUdp
     // the message from the internal buffer
     fn recv_message(&mut self) -> Result<&mut [u8]>
     {
-        // we always know that inner_vec has at least NET_MAXPAYLOAD
+        // we always know that inner_vec has at least max_payload + 1
         // because we allocated it and no one else can touch it
         // we have to do this because .recv() expects a vector not just
         // of enough *capacity* but also *length* (because of the conversion to &[u8])
         unsafe
         {
-            self.inner_vec.set_len(NET_MAXPAYLOAD)
+            self.inner_vec.set_len(self.max_payload + 1)
         }
 
         // receive the message from the socket
-        self.message_len = self.socket
-            .recv(self.inner_vec.as_mut())
-            .context("recv_message failed to read from socket")?;
+        // on a connected udp socket, a server that has gone away shows up as connection refused
+        self.message_len = match self.socket.recv(self.inner_vec.as_mut()) {
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                return Err(ChannelError::ConnectionClosed.into());
+            },
+            res => res.context("recv_message failed to read from socket")?,
+        };
+
+        // an empty datagram is never a valid packet, treat it as the end of the connection
+        if self.message_len == 0 {
+            return Err(ChannelError::ConnectionClosed.into());
+        }
+
+        // anything that filled the extra byte was truncated by the socket, and is too large
+        if self.message_len > self.max_payload {
+            self.message_len = 0;
+            return Err(ChannelError::Protocol("datagram exceeds the max payload size").into());
+        }
+
         // return the part of the vector that contains the message
         Ok(&mut self.inner_vec[0..self.message_len])
     }
 
+    // whether outgoing packets get compressed
+    pub fn get_compression(&self) -> CompressionMode
+    {
+        return self.compression;
+    }
+
     // get packet serialization scratch space as a mutable pointer
     pub fn get_scratch_mut(&mut self) -> &mut Vec<u8>
     {
@@ -106,24 +374,323 @@ impl BufUdp
     }
 }
 
+// how many times a query is re-sent with a new challenge before giving up
+const MAX_INFO_CHALLENGE_RETRIES: usize = 3;
+
+// size of the header in front of each piece of a split reply: the split header, the reply's
+// id, the number of pieces, this piece's number and the size pieces are split to
+const SPLIT_HEADER_SIZE: usize = 12;
+
+// set in a split reply's id when the reply was bzip2 compressed before splitting
+const SPLIT_COMPRESSED_FLAG: u32 = 0x80000000;
+
+// read the header of one piece of a split reply, returns the reply's id, how many pieces it
+// was split into, which piece this is and the piece's data
+fn read_split_header(msg: &[u8]) -> Result<(u32, usize, usize, &[u8])>
+{
+    if msg.len() < SPLIT_HEADER_SIZE {
+        return Err(ChannelError::Protocol("datagram too short for a split header").into());
+    }
+
+    let id = u32::from_le_bytes([msg[4], msg[5], msg[6], msg[7]]);
+    let total = msg[8] as usize;
+    let number = msg[9] as usize;
+
+    if total == 0 || number >= total {
+        return Err(ChannelError::Protocol("split reply piece number out of range").into());
+    }
+
+    Ok((id, total, number, &msg[SPLIT_HEADER_SIZE..]))
+}
+
+// what a server can answer a query with
+enum QueryReply<T>
+{
+    Reply(T),
+
+    // servers enforcing the anti-reflection challenge want the query sent again with this
+    Challenge(u32),
+}
+
 // send and receive connectionless source engine packets
-pub struct ConnectionlessChannel
+pub struct ConnectionlessChannel<S = UdpSocket>
 {
     // buffered udp socket
-    wrapper: BufUdp,
+    wrapper: BufUdp<S>,
+
+    // point in time by which the handshake must be complete, if any
+    deadline: Option<Instant>,
+
+    // whether pings received by recv_any are answered with an ack
+    answer_pings: bool,
+
+    // the last split reply that was put back together, query replies are read out of here
+    split_reply: Vec<u8>,
 }
 
 impl ConnectionlessChannel
+{
+    // resolve a "host:port" string, bind a local socket and connect it to the server
+    pub fn connect_to(host: &str) -> Result<Self>
+    {
+        Self::connect(host, None).context(format!("Failed to connect to {}", host))
+    }
+
+    // bind a local socket to `bind`, or any local address if None, and connect it to the server
+    // source engine only speaks ipv4, so ipv4 results are preferred over anything else
+    pub fn connect<A>(server: A, bind: Option<SocketAddr>) -> Result<Self>
+        where A: ToSocketAddrs
+    {
+        let addrs: Vec<SocketAddr> = server
+            .to_socket_addrs()
+            .context("Failed to resolve server address")?
+            .collect();
+
+        // pick the first ipv4 address that the server resolved to
+        let addr = addrs
+            .iter()
+            .find(|addr| addr.is_ipv4())
+            .ok_or(anyhow::anyhow!("Server did not resolve to an IPv4 address"))?;
+
+        // bind to some client socket
+        let socket = match bind {
+            Some(bind) => UdpSocket::bind(bind)?,
+            None => UdpSocket::bind("0.0.0.0:0")?,
+        };
+
+        // "connect" to udp server
+        socket.connect(addr)?;
+
+        ConnectionlessChannel::new(socket)
+    }
+
+    // wrap a socket the caller has already bound. it must be connected to the server, reads
+    // and writes rely on the connected socket only talking to that one address
+    pub fn from_socket(socket: UdpSocket) -> Result<Self>
+    {
+        socket.peer_addr().context("Socket must be connected to the server before it is used for a channel")?;
+
+        ConnectionlessChannel::new(socket)
+    }
+}
+
+impl<S> ConnectionlessChannel<S>
+    where S: PacketTransport
 {
     // wrap a udp socket
-    pub fn new(socket: UdpSocket) -> Result<Self>
+    pub fn new(socket: S) -> Result<Self>
+    {
+        Ok(Self
+        {
+            wrapper: BufUdp::new(socket),
+            deadline: None,
+            answer_pings: false,
+            split_reply: Vec::new(),
+        })
+    }
+
+    // wrap a udp socket, rejecting any datagram larger than max_payload bytes
+    pub fn with_max_payload(socket: S, max_payload: usize) -> Result<Self>
     {
         Ok(Self
         {
-            wrapper: BufUdp::new(socket)
+            wrapper: BufUdp::with_max_payload(socket, max_payload),
+            deadline: None,
+            answer_pings: false,
+            split_reply: Vec::new(),
+        })
+    }
+
+    // set when outgoing connectionless packets are LZSS compressed, large connect packets
+    // can otherwise be too big for a single datagram. carries over to the netchannel on upgrade
+    pub fn set_compression(&mut self, compression: CompressionMode)
+    {
+        self.wrapper.compression = compression;
+    }
+
+    // get the address of the server this channel is connected to
+    pub fn peer_addr(&self) -> Result<SocketAddr>
+    {
+        Ok(self.wrapper.socket.peer_addr()?)
+    }
+
+    // answer every A2A_PING received by recv_any with an A2A_ACK, for taking the server's side
+    // of the liveness check (e.g. in a relay)
+    pub fn set_answer_pings(&mut self, answer_pings: bool)
+    {
+        self.answer_pings = answer_pings;
+    }
+
+    // set an overall deadline for the handshake, enforced by recv_packet_during
+    // clearing the deadline also clears the socket read timeout
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) -> Result<()>
+    {
+        self.deadline = deadline;
+
+        if deadline.is_none() {
+            self.wrapper.socket.set_read_timeout(None)?;
+        }
+
+        Ok(())
+    }
+
+    // error with HandshakeTimeout if the deadline has already passed during the given phase
+    pub fn check_deadline(&self, phase: &'static str) -> Result<()>
+    {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(ChannelError::HandshakeTimeout(phase).into()),
+            _ => Ok(())
+        }
+    }
+
+    // read a specific connectionless packet as part of a handshake phase
+    // if a deadline is set, waits no longer than the time remaining until it
+    pub fn recv_packet_during<T>(&mut self, phase: &'static str) -> Result<T>
+        where T: ConnectionlessPacketReceive
+    {
+        self.recv_during(phase, |channel| channel.recv_packet_type())
+    }
+
+    // wait a short time for a packet which the server may or may not send, None if nothing
+    // arrived in time. also waits no longer than the deadline, if one is set
+    pub fn recv_packet_timeout<T>(&mut self, timeout: Duration) -> Result<Option<T>>
+        where T: ConnectionlessPacketReceive
+    {
+        let timeout = match self.deadline {
+            Some(deadline) => std::cmp::min(timeout, deadline.saturating_duration_since(Instant::now())),
+            None => timeout,
+        };
+
+        // a zero read timeout is not allowed, and there's no time left to wait anyway
+        if timeout.as_nanos() == 0 {
+            return Ok(None);
+        }
+
+        self.wrapper.socket.set_read_timeout(Some(timeout))?;
+        let res = self.recv_packet_type();
+
+        // without a deadline, reads go back to blocking
+        if self.deadline.is_none() {
+            self.wrapper.socket.set_read_timeout(None)?;
+        }
+
+        match res {
+            Err(e) if is_timed_out(&e) => Ok(None),
+            res => res.map(Some),
+        }
+    }
+
+    // query the server's info, answering the anti-reflection challenge if the server asks for
+    // one. waits no longer than the deadline, if one is set
+    pub fn query_info(&mut self) -> Result<S2aInfoSrc>
+    {
+        let mut query = A2sInfo::default();
+
+        for _attempt in 0..=MAX_INFO_CHALLENGE_RETRIES
+        {
+            self.send_packet(query.into())?;
+
+            match self.recv_query_reply::<S2aInfoSrc>("info")?
+            {
+                QueryReply::Reply(info) => return Ok(info),
+                QueryReply::Challenge(challenge) => query = A2sInfo::with_challenge(challenge),
+            }
+        }
+
+        Err(ChannelError::Protocol("server kept answering the info query with a challenge").into())
+    }
+
+    // query the players on the server, getting a challenge from the server first
+    pub fn query_players(&mut self) -> Result<S2aPlayer>
+    {
+        let (players, _challenge) = self.query_with_challenge("players", None, |challenge| A2sPlayer::with_challenge(challenge).into())?;
+
+        Ok(players)
+    }
+
+    // query the server's rules, getting a challenge from the server first
+    pub fn query_rules(&mut self) -> Result<S2aRules>
+    {
+        let (rules, _challenge) = self.query_with_challenge("rules", None, |challenge| A2sRules::with_challenge(challenge).into())?;
+
+        Ok(rules)
+    }
+
+    // query the server's info, players and rules. the challenge from the player query is
+    // reused for the rules query, servers which only accept a challenge once answer that
+    // with a new challenge and the rules query is sent again with it
+    pub fn query_full(&mut self) -> Result<(S2aInfoSrc, S2aPlayer, S2aRules)>
+    {
+        let info = self.query_info()?;
+        let (players, challenge) = self.query_with_challenge("players", None, |challenge| A2sPlayer::with_challenge(challenge).into())?;
+        let (rules, _challenge) = self.query_with_challenge("rules", Some(challenge), |challenge| A2sRules::with_challenge(challenge).into())?;
+
+        Ok((info, players, rules))
+    }
+
+    // send a player or rules query with `challenge`, or ask for one if None, and re-send it
+    // whenever the server answers with a new challenge instead. returns the reply along with
+    // the challenge the server accepted
+    fn query_with_challenge<T, F>(&mut self, phase: &'static str, challenge: Option<u32>, query: F) -> Result<(T, u32)>
+        where T: ConnectionlessPacketReceive, F: Fn(u32) -> ConnectionlessPacket
+    {
+        let mut challenge = challenge.unwrap_or(QUERY_CHALLENGE_REQUEST);
+
+        for _attempt in 0..=MAX_INFO_CHALLENGE_RETRIES
+        {
+            self.send_packet(query(challenge))?;
+
+            match self.recv_query_reply::<T>(phase)?
+            {
+                QueryReply::Reply(reply) => return Ok((reply, challenge)),
+                QueryReply::Challenge(new_challenge) => {
+                    trace!("Server answered the {} query with challenge {:#x}", phase, new_challenge);
+                    challenge = new_challenge;
+                },
+            }
+        }
+
+        Err(ChannelError::Protocol("server kept answering a query with a challenge").into())
+    }
+
+    // receive the answer to a query, which is either the expected reply or a challenge
+    fn recv_query_reply<T>(&mut self, phase: &'static str) -> Result<QueryReply<T>>
+        where T: ConnectionlessPacketReceive
+    {
+        self.recv_during(phase, |channel| {
+            let (packet_type, mut reader) = channel.recv_query_header()?;
+
+            match packet_type
+            {
+                ConnectionlessPacketType::S2C_CHALLENGE => Ok(QueryReply::Challenge(reader.read_long()?)),
+                packet_type if packet_type == T::get_type() => Ok(QueryReply::Reply(T::read_values(&mut reader)?)),
+                _ => Err(anyhow::anyhow!("Expected packet {:?}, got {:?}", T::get_type(), packet_type)),
+            }
         })
     }
 
+    // run a receive as part of a handshake phase, bounded by the deadline if one is set
+    fn recv_during<R, F>(&mut self, phase: &'static str, recv: F) -> Result<R>
+        where F: FnOnce(&mut Self) -> Result<R>
+    {
+        if let Some(deadline) = self.deadline
+        {
+            // only wait for whatever time is left
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.as_nanos() == 0 {
+                return Err(ChannelError::HandshakeTimeout(phase).into());
+            }
+            self.wrapper.socket.set_read_timeout(Some(remaining))?;
+        }
+
+        match recv(self) {
+            Err(e) if self.deadline.is_some() && is_timed_out(&e) => {
+                Err(ChannelError::HandshakeTimeout(phase).into())
+            },
+            res => res,
+        }
+    }
+
     // send a connectionless packet to the socket
     pub fn send_packet(&mut self, pkt: ConnectionlessPacket) -> Result<()>
     {
@@ -137,23 +704,105 @@ impl ConnectionlessChannel
         // read the message
         let msg = self.wrapper.recv_message()?;
 
-        // wrap in a bit buffer
-        let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(msg), LittleEndian);
+        read_connectionless_header(msg)
+    }
+
+    // receive the header of a query reply. replies too large for one datagram (rules, or the
+    // players on a full server) are split, their pieces are put back together first
+    fn recv_query_header(&mut self) -> Result<(ConnectionlessPacketType, BitBufReaderType)>
+    {
+        if classify_datagram(self.wrapper.recv_message()?)? != DatagramKind::Split {
+            return read_connectionless_header(self.wrapper.get_message());
+        }
+
+        self.split_reply = self.recv_split_reply()?;
+        read_connectionless_header(&self.split_reply)
+    }
+
+    // receive the rest of the split reply the last received datagram is a piece of, and
+    // return the whole reply. pieces may arrive in any order
+    fn recv_split_reply(&mut self) -> Result<Vec<u8>>
+    {
+        let (id, total, _, _) = read_split_header(self.wrapper.get_message())?;
+
+        if (id & SPLIT_COMPRESSED_FLAG) != 0 {
+            return Err(ChannelError::Protocol("compressed split replies are not supported").into());
+        }
+
+        let mut pieces: Vec<Option<Vec<u8>>> = vec![None; total];
+        let mut received = 0;
+
+        loop {
+            let (piece_id, piece_total, number, data) = read_split_header(self.wrapper.get_message())?;
+
+            if piece_id != id || piece_total != total {
+                warn!("Dropping piece of split reply {:#x} while waiting on {:#x}", piece_id, id);
+            } else if pieces[number].is_none() {
+                pieces[number] = Some(data.to_vec());
+                received += 1;
+            }
+
+            if received == total {
+                break;
+            }
+
+            // the next piece has to be a piece too, anything else means one went missing
+            if classify_datagram(self.wrapper.recv_message()?)? != DatagramKind::Split {
+                return Err(ChannelError::Protocol("split reply is missing pieces").into());
+            }
+        }
+
+        trace!("Put together split reply {:#x} from {} pieces", id, total);
+        Ok(pieces.into_iter().flatten().flatten().collect())
+    }
+
+    // read whatever connectionless packet arrives next, returning its type and the bytes of
+    // its values. pings are answered before returning if set_answer_pings is enabled
+    pub fn recv_any(&mut self) -> Result<(ConnectionlessPacketType, Vec<u8>)>
+    {
+        let (packet_type, reader) = self.recv_header()?;
+
+        let cursor = reader.into_reader();
+        let position = cursor.position() as usize;
+        let values = cursor.into_inner()[position..].to_vec();
 
-        // first ensure we have a proper connectionless header
-        let header = reader.read_long()?;
-        if header != CONNECTIONLESS_HEADER
+        if packet_type == ConnectionlessPacketType::A2A_PING && self.answer_pings
         {
-            return Err(anyhow::anyhow!("Invalid connectionless header"))
+            trace!("Answering ping from {:?}", self.wrapper.socket.peer_addr());
+            self.send_packet(A2aAck {}.into())?;
         }
 
-        // read the type number and convert it to a packet type enum
-        Ok((ConnectionlessPacketType::from(reader.read_char()?), reader))
+        Ok((packet_type, values))
     }
 
     // read a specific connectionless packet from the socket
     pub fn recv_packet_type<T>(&mut self) -> Result<T>
         where T: ConnectionlessPacketReceive
+    {
+        let (packet, _reader) = self.recv_packet_reader()?;
+
+        Ok(packet)
+    }
+
+    // read a specific connectionless packet from the socket, along with any bytes at the end
+    // of the datagram that the packet didn't consume (e.g. fields this crate doesn't parse yet)
+    // a partially read byte counts as consumed
+    pub fn recv_packet_type_with_trailing<T>(&mut self) -> Result<(T, Vec<u8>)>
+        where T: ConnectionlessPacketReceive
+    {
+        let (packet, reader) = self.recv_packet_reader()?;
+
+        // whatever the cursor hasn't reached yet was never read
+        let cursor = reader.into_reader();
+        let position = cursor.position() as usize;
+        let trailing = cursor.into_inner()[position..].to_vec();
+
+        Ok((packet, trailing))
+    }
+
+    // read a specific connectionless packet, returning the reader positioned just after it
+    fn recv_packet_reader<T>(&mut self) -> Result<(T, BitBufReaderType)>
+        where T: ConnectionlessPacketReceive
     {
         // read the type number and convert it to a packet type enum
         let (packet_type, mut target) = self.recv_header()?;
@@ -164,48 +813,209 @@ impl ConnectionlessChannel
         }
 
         // read the packet from the wire
-        Ok(T::read_values(&mut target)?)
+        let packet = T::read_values(&mut target)?;
+
+        Ok((packet, target))
     }
 }
 
 /// A NetChannel is a fully established connection with a server which can send source engine
 /// netmessage packets between it
-pub struct NetChannel
+///
+/// The interior `RefCell`/`Cell` state is only used to work around borrows within a single call,
+/// so a NetChannel is `Send` (but not `Sync`) and can be moved onto another thread to be driven
+/// from there. Any callbacks installed on the channel (and the transport it runs over) must be
+/// `Send` to keep this true.
+pub struct NetChannel<S = UdpSocket>
+    where S: PacketTransport
 {
     /// buffered udp socket
-    wrapper: RefCell<BufUdp>,
+    wrapper: RefCell<BufUdp<S>>,
 
     /// ICE network encryption key
-    crypt: IceEncryption,
+    crypt: Arc<IceEncryption>,
 
-    /// current input sequence number
-    in_sequence: u32,
+    /// sequence state shared with the other half of a split channel
+    sequence: Arc<SequenceState>,
 
     /// current out sequence number
     out_sequence: u32,
 
-    /// current acknowledged output sequence number
-    out_sequence_ack: u32,
-
     /// number of choked packets
     choked_num: u8,
 
     /// buffer to encrypt packets to
     encrypt_buffer: RefCell<Vec<u8>>,
 
-    /// buffer to encode protobuf packets into
-    encode_buffer: Vec<u8>,
+    /// recycled buffers that netmessages are encoded into and decoded from
+    buffer_pool: RefCell<BufferPool>,
 
-    /// all of the subchannels for this netchannel
-    subchannels: RefCell<[SubChannel; 2]>,
+    /// encoded netmessages waiting to be sent together by flush
+    send_queue: Vec<u8>,
 
-    /// current reliable state of all subchannels
-    reliable_state: Cell<u8>,
-}
+    /// receive buffers for each reliable stream (messages and files), reliable data from
+    /// any of the sender's subchannels is reassembled here
+    streams: RefCell<[SubChannel; MAX_STREAMS]>,
 
-/// Header read out of a basic netchannel packet
-#[derive(Debug)]
-pub struct NetChannelPacketHeader {
+    /// reliable payloads being sent to the server, they go out with the datagrams we send
+    reliable: RefCell<ReliableSender>,
+
+    /// latest server tick received from net_Tick, None until the first one arrives
+    server_tick: Option<u32>,
+
+    /// convars the server has told us about with net_SetConVar
+    convars: HashMap<String, String>,
+
+    /// entity baseline (tick, baseline number) the server wants us to acknowledge
+    pending_baseline_ack: Option<(u32, i32)>,
+
+    /// called with the text of every svc_Print received from the server
+    on_print: Option<Box<dyn FnMut(&str) + Send>>,
+
+    /// called with every connectionless packet (e.g. a reply to `send_connectionless`) that
+    /// arrives on the channel's socket
+    on_connectionless: Option<Box<dyn FnMut(ConnectionlessPacketType, &[u8]) + Send>>,
+
+    /// latest signon state the server sent with net_SignonState
+    signon_state: i32,
+
+    /// called with the new signon state whenever the server changes it
+    on_signon_change: Option<Box<dyn FnMut(i32) + Send>>,
+
+    /// whether the server last told us with svc_SetPause that the game is paused
+    paused: bool,
+
+    /// called with the new pause state whenever the server pauses or unpauses the game
+    on_pause_change: Option<Box<dyn FnMut(bool) + Send>>,
+
+    /// voice codec settings from svc_VoiceInit, None until the server sends them. shared between
+    /// the halves of a split channel so the writer can send voice
+    voice_settings: Arc<Mutex<Option<VoiceSettings>>>,
+
+    /// encodes PCM for `send_voice`, created for the current voice settings on first use
+    voice_encoder: Option<VoiceEncoder>,
+
+    /// total bytes of voice data sent so far, the position in our voice stream
+    voice_sequence_bytes: i32,
+
+    /// log of every game event received, if enabled
+    game_event_log: Option<GameEventLog>,
+
+    /// ICE keys for svc_EncryptedData payloads, by key type
+    encrypted_data_keys: HashMap<i32, IceEncryption>,
+
+    /// capture file that every received netmessage is written to, if capturing
+    capture: RefCell<Option<CaptureWriter>>,
+
+    /// host version the server reported when the channel was established
+    host_version: u32,
+
+    /// set once either side has sent net_Disconnect, nothing more is sent after that. shared
+    /// between the halves of a split channel, the writer mustn't say goodbye to a server that
+    /// has already disconnected the reader
+    disconnected: Arc<AtomicBool>,
+
+    /// whether dropping the channel tells the server we're leaving
+    disconnect_on_drop: bool,
+
+    /// incoming sequence numbers seen recently, for the packet loss estimate
+    packet_loss: RefCell<PacketLossWindow>,
+
+    /// decoders for netmessage ids the crate doesn't know about
+    message_registry: NetMessageRegistry,
+}
+
+/// Sequence state which is updated by the read side of a netchannel and
+/// acknowledged by the write side
+struct SequenceState {
+    /// current input sequence number
+    in_sequence: AtomicU32,
+
+    /// current acknowledged output sequence number
+    out_sequence_ack: AtomicU32,
+
+    /// current reliable state of all subchannels, one bit per subchannel index
+    reliable_state: AtomicU8,
+
+    /// the reliable state the server last sent, which says which of the batches of reliable
+    /// data we sent it has received
+    peer_reliable_state: AtomicU8,
+}
+
+/// Which of the latest incoming sequence numbers have arrived, for estimating packet loss
+///
+/// A gap in the sequence counts as lost until the missing datagram turns up late, so
+/// reordered datagrams don't count against the estimate, and duplicates are ignored.
+#[derive(Debug, Default)]
+struct PacketLossWindow {
+    /// the highest sequence number received
+    highest: u32,
+
+    /// whether each sequence number up to and including `highest` arrived, oldest first
+    received: VecDeque<bool>,
+}
+
+impl PacketLossWindow {
+    /// record a datagram with `sequence` arriving, which reported `choked` packets before it.
+    /// the sender skipped those sequence numbers on purpose, so they aren't lost
+    fn observe(&mut self, sequence: u32, choked: u8)
+    {
+        if sequence > self.highest {
+            // everything skipped over and not choked is missing for now. the first sequence
+            // seen starts the window, there is nothing before it to miss
+            if !self.received.is_empty() {
+                let gap = std::cmp::min((sequence - self.highest - 1) as usize, PACKET_LOSS_WINDOW);
+                let choked = std::cmp::min(choked as usize, gap);
+                self.received.extend(std::iter::repeat(false).take(gap - choked));
+                self.received.extend(std::iter::repeat(true).take(choked));
+            }
+            self.received.push_back(true);
+            self.highest = sequence;
+
+            while self.received.len() > PACKET_LOSS_WINDOW {
+                self.received.pop_front();
+            }
+        } else {
+            // arrived late, it was reordered rather than lost, and so were its choked packets
+            let behind = (self.highest - sequence) as usize;
+            for index in behind..=(behind + choked as usize) {
+                if index < self.received.len() {
+                    let index = self.received.len() - 1 - index;
+                    self.received[index] = true;
+                }
+            }
+        }
+    }
+
+    /// fraction of the window that never arrived, from 0.0 to 1.0
+    fn loss(&self) -> f32
+    {
+        if self.received.is_empty() {
+            return 0.0;
+        }
+
+        let lost = self.received.iter().filter(|received| !**received).count();
+        return lost as f32 / self.received.len() as f32;
+    }
+}
+
+/// The receiving half of a split NetChannel
+pub struct NetChannelReader<S = UdpSocket>
+    where S: PacketTransport
+{
+    channel: NetChannel<S>,
+}
+
+/// The sending half of a split NetChannel
+pub struct NetChannelWriter<S = UdpSocket>
+    where S: PacketTransport
+{
+    channel: NetChannel<S>,
+}
+
+/// Header read out of a basic netchannel packet
+#[derive(Debug)]
+pub struct NetChannelPacketHeader {
     sequence_in: u32,
     sequence_ack: u32,
     flags: u8,
@@ -222,6 +1032,182 @@ pub struct NetDatagram {
     /// If this packet contained any netmessages (other than NET_Nop)
     /// then they will be decoded and put here. Otherwise, None.
     messages: Option<Vec<NetMessage>>,
+
+    /// How decoding the netmessages in this packet went
+    decode_stats: DecodeStats,
+}
+
+impl fmt::Debug for NetDatagram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetDatagram")
+            .field("header", &self.header)
+            .field("messages", &self.messages.as_ref().map_or(0, |messages| messages.len()))
+            .field("decode_stats", &self.decode_stats)
+            .finish()
+    }
+}
+
+/// Counts of how the netmessages in a datagram decoded, for finding out which message types
+/// are not being handled
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DecodeStats {
+    /// Messages which decoded successfully
+    pub decoded: u32,
+
+    /// Messages with an id that `NetMessage::bind` does not know about
+    pub unknown: u32,
+
+    /// Messages with a known id which failed to parse
+    pub failed: u32,
+}
+
+/// Decodes the netmessages in a payload one at a time, see `NetChannel::read_messages_iter`
+///
+/// Messages which fail to decode are skipped and counted in `stats`, the messages inside
+/// a svc_EncryptedData are yielded right after it. Running off the end of the payload in the
+/// middle of a message yields an error and ends the iteration.
+pub struct NetMessageIter<'a, S = UdpSocket, T = std::io::Cursor<&'a [u8]>>
+    where S: PacketTransport, T: std::io::Read
+{
+    channel: &'a NetChannel<S>,
+    reader: BitReader<T, LittleEndian>,
+
+    /// buffer from the channel's pool that each message is read into, given back on drop
+    decode_buf: Vec<u8>,
+
+    /// messages from inside a svc_EncryptedData, yielded before reading any further
+    pending: VecDeque<NetMessage>,
+
+    stats: DecodeStats,
+    done: bool,
+}
+
+impl<'a, S, T> NetMessageIter<'a, S, T>
+    where S: PacketTransport, T: std::io::Read
+{
+    fn new(channel: &'a NetChannel<S>, reader: BitReader<T, LittleEndian>) -> Self
+    {
+        Self {
+            channel,
+            reader,
+            decode_buf: channel.buffer_pool.borrow_mut().take(),
+            pending: VecDeque::new(),
+            stats: DecodeStats::default(),
+            done: false,
+        }
+    }
+
+    /// how the messages read so far decoded
+    pub fn stats(&self) -> DecodeStats
+    {
+        return self.stats;
+    }
+
+    /// read the next message off the wire, None at the end of the payload
+    fn read_next(&mut self) -> anyhow::Result<Option<NetMessage>>
+    {
+        loop {
+            // if there is still data, there must be messages for us to process
+            // when we reach EOF, we stop netmessage parsing
+            let message_id = match self.reader.read_int32_var() {
+                Ok(message_id) => message_id,
+                Err(_) => return Ok(None),
+            };
+
+            if message_id == 0 {
+                // NOP packet, just ignore
+                continue;
+            }
+
+            // total size of the message
+            let message_size = self.reader.read_int32_var()? as usize;
+
+            trace!("MESSAGE [id={}, size={}]:", message_id, message_size);
+
+            // the size comes straight off the wire, don't let a forged one allocate without limit
+            if message_size > NET_MAXPAYLOAD {
+                return Err(ChannelError::Protocol("netmessage larger than the max payload").into());
+            }
+
+            // size the buffer for the message, it only reallocates for messages larger than any before
+            self.decode_buf.clear();
+            self.decode_buf.resize(message_size, 0);
+
+            // read the message's data
+            self.reader.read_bytes_into(self.decode_buf.as_mut_slice())?;
+
+            // record the message as it came off the wire if we're capturing
+            self.channel.capture_message(message_id as i32, self.decode_buf.as_slice());
+
+            // decode the protobuf message
+            let message = match NetMessage::bind_with(&self.channel.message_registry, message_id as i32, self.decode_buf.as_slice()) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Failed decoding netmessage [id={}]: {}", message_id, e);
+
+                    if NetMessage::is_known_id(message_id as i32) || self.channel.message_registry.contains(message_id as i32) {
+                        self.stats.failed += 1;
+                    } else {
+                        self.stats.unknown += 1;
+                    }
+                    continue;
+                }
+            };
+
+            self.stats.decoded += 1;
+
+            trace!("Successfully decoded \"{}\" (id={}, size={}) message", message.get_type_name(), message_id, message_size);
+
+            // encrypted data carries more netmessages inside of it, which come right after it
+            if let Some(encrypted) = message.downcast::<CSVCMsg_EncryptedData>() {
+                match self.channel.read_encrypted_data(encrypted, &mut self.stats) {
+                    Ok(inner_messages) => self.pending.extend(inner_messages),
+                    Err(e) => warn!("Failed decrypting svc_EncryptedData [key_type={}]: {}", encrypted.get_key_type(), e),
+                }
+            }
+
+            return Ok(Some(message));
+        }
+    }
+}
+
+impl<'a, S, T> Iterator for NetMessageIter<'a, S, T>
+    where S: PacketTransport, T: std::io::Read
+{
+    type Item = anyhow::Result<NetMessage>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if let Some(message) = self.pending.pop_front() {
+            return Some(Ok(message));
+        }
+
+        if self.done {
+            return None;
+        }
+
+        match self.read_next() {
+            Ok(Some(message)) => Some(Ok(message)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+impl<'a, S, T> Drop for NetMessageIter<'a, S, T>
+    where S: PacketTransport, T: std::io::Read
+{
+    fn drop(&mut self)
+    {
+        // done with the decode buffer, a huge one is shrunk back down by the pool
+        self.channel.buffer_pool.borrow_mut().give(std::mem::take(&mut self.decode_buf));
+    }
 }
 
 impl NetDatagram {
@@ -245,6 +1231,7 @@ impl NetDatagram {
                 choked,
             },
             messages: None,
+            decode_stats: DecodeStats::default(),
         }
     }
 
@@ -267,6 +1254,72 @@ impl NetDatagram {
         return self.messages.as_ref();
     }
 
+    /// how many packets the sender choked (held back) since the datagram before this one.
+    /// their sequence numbers were skipped on purpose, they weren't lost
+    pub fn choked(&self) -> u8
+    {
+        return self.header.choked;
+    }
+
+    /// how decoding the netmessages in this packet went, including any which were dropped
+    pub fn get_decode_stats(&self) -> DecodeStats
+    {
+        return self.decode_stats;
+    }
+
+    /// re-encode this datagram to the wire format for the given channel, so a captured datagram
+    /// can be modified and replayed. the header is written back as it was received with a fresh
+    /// checksum, and the messages are re-encoded after it before encrypting with the channel's key.
+    ///
+    /// reliable subchannel data is not kept once it has been reassembled, so any messages that
+    /// arrived reliably are re-encoded as regular unreliable messages
+    pub fn serialize<S>(&self, channel: &NetChannel<S>) -> Result<Vec<u8>>
+        where S: PacketTransport
+    {
+        let mut buf: Vec<u8> = Vec::with_capacity(4096);
+
+        // the reliable section is not written back out
+        let flags = self.header.flags & !PACKET_RELIABLE;
+
+        {
+            let mut writer = BitWriter::endian(Cursor::new(&mut buf), LittleEndian);
+
+            writer.write_long(self.header.sequence_in)?;
+            writer.write_long(self.header.sequence_ack)?;
+            writer.write_char(flags)?;
+
+            // checksum is filled in once the rest of the packet is written
+            writer.write_word(0)?;
+
+            writer.write_char(self.header.reliable_state)?;
+
+            if (flags & PACKET_CHOKED) != 0 {
+                writer.write_char(self.header.choked)?;
+            }
+        }
+
+        // append each of the messages
+        if let Some(messages) = self.get_messages()
+        {
+            let mut encode_buf: Vec<u8> = Vec::new();
+            for message in messages.iter()
+            {
+                encode_buf.clear();
+                message.encode_to_buffer(&mut encode_buf)?;
+                buf.extend_from_slice(&encode_buf);
+            }
+        }
+
+        // checksum everything after the checksum field (4 + 4 + 1 + 2)
+        let checksum = NetChannel::<S>::datagram_checksum(&buf[11..]);
+        buf[9..11].copy_from_slice(&checksum.to_le_bytes());
+
+        // encrypt the packet with the channel's ICE key
+        let encrypted = channel.encrypt_packet(&mut buf)?;
+
+        Ok(encrypted.clone())
+    }
+
     /// add a set of messages to this datagram
     fn add_messages(&mut self, messages: Vec<NetMessage>)
     {
@@ -283,9 +1336,12 @@ impl NetDatagram {
     }
 }
 
-impl NetChannel {
-    /// get the default channel encryption key
-    fn get_encryption_key(host_version: u32) -> [u8; 16]
+impl<S> NetChannel<S>
+    where S: PacketTransport
+{
+    /// get the default channel encryption key, "CSGO" followed by three shuffled copies of the
+    /// host version. a wrong host version still gives a key, just one that decrypts to garbage
+    pub(crate) fn get_encryption_key(host_version: u32) -> [u8; 16]
     {
         return [
             'C' as u8,
@@ -308,63 +1364,250 @@ impl NetChannel {
     }
 
     /// upgrade a connectionless channel into a netchannel after authentication is complete
-    pub fn upgrade(socket: ConnectionlessChannel, host_version: u32) -> Result<Self>
+    pub fn upgrade(socket: ConnectionlessChannel<S>, host_version: u32) -> Result<Self>
+    {
+        Self::upgrade_with_key_version(socket, host_version, None)
+    }
+
+    /// upgrade a connectionless channel with something other than the default config, e.g. to
+    /// turn off streams a specialized client doesn't need
+    pub fn upgrade_with_config(socket: ConnectionlessChannel<S>, host_version: u32, config: &NetChannelConfig) -> Result<Self>
+    {
+        let mut channel = Self::upgrade(socket, host_version)?;
+        channel.apply_config(config);
+
+        Ok(channel)
+    }
+
+    /// upgrade a connectionless channel, deriving the ICE key from `key_version` instead of the
+    /// host version the server reported in its challenge if one is given. useful for replaying
+    /// captured traffic or talking to a server which reports a spoofed version
+    pub fn upgrade_with_key_version(socket: ConnectionlessChannel<S>, host_version: u32, key_version: Option<u32>) -> Result<Self>
     {
-        let encryption_key = NetChannel::get_encryption_key(host_version);
+        let key_version = match key_version {
+            Some(key_version) => {
+                warn!("Deriving channel key from version {} instead of host version {}", key_version, host_version);
+                key_version
+            },
+            None => host_version,
+        };
+
+        let encryption_key = Self::get_encryption_key(key_version);
+
+        Self::upgrade_with_encryption_key(socket, host_version, &encryption_key)
+    }
 
+    /// upgrade a connectionless channel using a server's own network encryption key, handed
+    /// out of band by the server operator, instead of one derived from the host version. the
+    /// key's index has to be sent in `C2sConnect::encryption_key_index`
+    pub fn upgrade_with_encryption_key(socket: ConnectionlessChannel<S>, host_version: u32, encryption_key: &[u8; 16]) -> Result<Self>
+    {
         // apply the ice key to prepare for encryption/decryption
-        let crypt= IceEncryption::new(2, &encryption_key);
+        let crypt= IceEncryption::new(2, encryption_key);
+
+        let sequence = SequenceState {
+            in_sequence: AtomicU32::new(0),
+            out_sequence_ack: AtomicU32::new(0),
+            reliable_state: AtomicU8::new(0),
+            peer_reliable_state: AtomicU8::new(0),
+        };
+
+        let mut channel = Self::from_parts(socket.wrapper, Arc::new(crypt), Arc::new(sequence));
+        channel.host_version = host_version;
+        channel.apply_config(&NetChannelConfig::default());
 
-        let subchannels: [SubChannel; 2] = [
+        Ok(channel)
+    }
+
+    /// create a netchannel on a socket with the given key and sequence state
+    fn from_parts(wrapper: BufUdp<S>, crypt: Arc<IceEncryption>, sequence: Arc<SequenceState>) -> Self
+    {
+        let streams: [SubChannel; MAX_STREAMS] = [
             SubChannel::new(),
             SubChannel::new(),
         ];
 
-        Ok(Self
+        Self
         {
             crypt,
-            wrapper: RefCell::new(socket.wrapper),
-            in_sequence: 0,
-            out_sequence_ack: 0,
+            sequence,
+            wrapper: RefCell::new(wrapper),
             out_sequence: 1,
             choked_num: 0,
             encrypt_buffer: RefCell::new(Vec::with_capacity(4096)),
-            encode_buffer: Vec::with_capacity(4096),
-            subchannels: RefCell::new(subchannels),
-            reliable_state: Cell::new(0),
-        })
+            buffer_pool: RefCell::new(BufferPool::new(DEFAULT_MAX_RETAINED)),
+            send_queue: Vec::new(),
+            streams: RefCell::new(streams),
+            reliable: RefCell::new(ReliableSender::new()),
+            server_tick: None,
+            convars: HashMap::new(),
+            pending_baseline_ack: None,
+            on_print: None,
+            on_connectionless: None,
+            signon_state: 0,
+            on_signon_change: None,
+            paused: false,
+            on_pause_change: None,
+            voice_settings: Arc::new(Mutex::new(None)),
+            voice_encoder: None,
+            voice_sequence_bytes: 0,
+            game_event_log: None,
+            encrypted_data_keys: HashMap::new(),
+            capture: RefCell::new(None),
+            host_version: 0,
+            disconnected: Arc::new(AtomicBool::new(false)),
+            disconnect_on_drop: true,
+            packet_loss: RefCell::new(PacketLossWindow::default()),
+            message_registry: NetMessageRegistry::new(),
+        }
     }
 
-    /// read all of the incoming data from a packet
+    /// split the channel into halves which can be used independently (e.g. reading on one thread
+    /// while sending keepalives from another). The ICE key is shared between both halves and the
+    /// sequence numbers/reliable state received by the reader are acknowledged by the writer.
+    /// Queued messages and unfinished `send_reliable` payloads are sent by the writer.
+    pub fn split(mut self) -> Result<(NetChannelReader<S>, NetChannelWriter<S>)>
+    {
+        // the writer sends over its own handle to the same socket
+        let (socket, max_payload, compression) = {
+            let wrapper = self.wrapper.borrow();
+            (wrapper.socket.try_clone()?, wrapper.max_payload, wrapper.compression)
+        };
+
+        let mut writer_wrapper = BufUdp::with_max_payload(socket, max_payload);
+        writer_wrapper.compression = compression;
+
+        let mut writer = Self::from_parts(writer_wrapper, self.crypt.clone(), self.sequence.clone());
+        writer.out_sequence = self.out_sequence;
+        writer.host_version = self.host_version;
+        writer.disconnected = self.disconnected.clone();
+        writer.voice_settings = self.voice_settings.clone();
+
+        // anything queued or being sent reliably goes out with the writer, the reader can't send
+        writer.send_queue = std::mem::take(&mut self.send_queue);
+        writer.choked_num = self.choked_num;
+        writer.reliable = RefCell::new(std::mem::replace(self.reliable.get_mut(), ReliableSender::new()));
+
+        // only the writer says goodbye when the halves are dropped
+        writer.disconnect_on_drop = self.disconnect_on_drop;
+        let mut reader = self;
+        reader.disconnect_on_drop = false;
+
+        Ok((NetChannelReader { channel: reader }, NetChannelWriter { channel: writer }))
+    }
+
+    /// send an out-of-band connectionless packet (e.g. an info query) over the channel's socket.
+    /// connectionless and netchannel packets are told apart by their header, so both can share
+    /// the same connection. replies are read by `read_data` and handed to the callback set with
+    /// `set_connectionless_handler`
+    pub fn send_connectionless(&self, pkt: ConnectionlessPacket) -> Result<()>
+    {
+        pkt.serialize_to_channel(&mut self.wrapper.borrow_mut())
+    }
+
+    /// read all of the incoming data from a packet. late and duplicate datagrams are dropped
+    /// (they still count towards `packet_loss`) and the next one is read instead
     pub fn read_data(&mut self) -> Result<NetDatagram>
     {
-        {
-            let mut borrow = self.wrapper.borrow_mut();
-            // receive the datagram over the network
-            borrow.recv_message()?;
-        }
+        loop {
+            {
+                let mut borrow = self.wrapper.borrow_mut();
+                // receive the datagram over the network
+                borrow.recv_message()?;
+            }
 
-        {
-            let borrow = self.wrapper.borrow();
-            let datagram = borrow.get_message();
+            // figure out what kind of datagram this is before trying to decrypt it
+            let kind = classify_datagram(self.wrapper.borrow().get_message())?;
+            match kind {
+                // anyone can send us these, so they're dropped rather than taking the channel down
+                DatagramKind::Split => {
+                    warn!("Dropping split datagram, split packets are not supported");
+                    continue;
+                },
+                DatagramKind::Connectionless => {
+                    self.dispatch_connectionless();
+                    continue;
+                },
+                DatagramKind::Netchannel => (),
+            }
 
-            // wrap the datagram in a bitbuffer
-            let mut reader = BitReader::endian(std::io::Cursor::new(datagram), LittleEndian);
+            let datagram = {
+                let mut borrow = self.wrapper.borrow_mut();
+                self.decode_datagram(borrow.get_message_mut())?
+            };
 
-            // check the packet header for a split packet
-            // also hope that ICE doesn't encrypt the first 4 bytes to these values!?
-            // what the hell are they thinking??
-            let header = reader.read_long()?;
-            if header == NET_HEADER_FLAG_SPLITPACKET {
-                panic!("Split packets not supported yet!");
-            } else if header == CONNECTIONLESS_HEADER {
-                panic!("Unexpected connectionless packet!");
+            if let Some(datagram) = datagram {
+                return self.accept_datagram(datagram);
             }
         }
+    }
+
+    /// hand the connectionless packet just read by `read_data` to the connectionless handler.
+    /// it's dropped if there is no handler or it's too short to have a type
+    fn dispatch_connectionless(&mut self)
+    {
+        let wrapper = self.wrapper.borrow();
+
+        let (packet_type, reader) = match read_connectionless_header(wrapper.get_message()) {
+            Ok(header) => header,
+            Err(e) => {
+                warn!("Dropping malformed connectionless packet: {}", e);
+                return;
+            },
+        };
+
+        // the handler gets the bytes of the packet's values, like ConnectionlessChannel::recv_any
+        let cursor = reader.into_reader();
+        let position = cursor.position() as usize;
+        let values = &cursor.into_inner()[position..];
+
+        match self.on_connectionless.as_mut() {
+            Some(on_connectionless) => on_connectionless(packet_type, values),
+            None => warn!("Dropping connectionless packet {:?}, no handler is set", packet_type),
+        }
+    }
+
+    /// read the next datagram like `read_data`, but wait no longer than `timeout` for it.
+    /// None if nothing arrived in time, so a caller can get on with anything that has to
+    /// happen even when the server goes quiet
+    pub fn read_data_timeout(&mut self, timeout: Duration) -> Result<Option<NetDatagram>>
+    {
+        // a zero read timeout is not allowed, and there's no time to wait anyway
+        if timeout.as_nanos() == 0 {
+            return Ok(None);
+        }
+
+        self.wrapper.borrow().socket.set_read_timeout(Some(timeout))?;
+        let res = self.read_data();
+        self.wrapper.borrow().socket.set_read_timeout(None)?;
+
+        match res {
+            Err(e) if is_timed_out(&e) => Ok(None),
+            res => res.map(Some),
+        }
+    }
+
+    /// run a single captured datagram, exactly as it came off the wire, through the same decode
+    /// path as `read_data` without touching the socket. the channel's key and sequence state are
+    /// used and updated, so datagrams from a capture should be fed in the order they arrived.
+    /// None if the datagram was late or a duplicate and has been dropped
+    pub fn decode_captured(&mut self, encrypted_bytes: &[u8]) -> Result<Option<NetDatagram>>
+    {
+        match classify_datagram(encrypted_bytes)? {
+            DatagramKind::Netchannel => (),
+            _ => return Err(anyhow::anyhow!("Captured datagram is not a netchannel datagram")),
+        }
 
-        let mut borrow = self.wrapper.borrow_mut();
-        let datagram = borrow.get_message_mut();
+        let mut datagram = encrypted_bytes.to_vec();
+        match self.decode_datagram(&mut datagram)? {
+            Some(datagram) => self.accept_datagram(datagram).map(Some),
+            None => Ok(None),
+        }
+    }
 
+    /// decrypt a netchannel datagram in place and parse its contents, None if it was dropped
+    fn decode_datagram(&self, datagram: &mut [u8]) -> Result<Option<NetDatagram>>
+    {
         if (datagram.len() % 8) != 0 {
             return Err(anyhow::anyhow!("Unexpected packet alignment"));
         }
@@ -375,59 +1618,442 @@ impl NetChannel {
         // if we're here, we have successfully decrypted the contents of the packet
         trace!("[RECV DATAGRAM]: \n{:?}", packet_data.hex_dump());
 
-        // process header data, sequence numbers, subchannel data, etc.
-        let datagram = self.parse_datagram(&packet_data)?;
+        // process header data, sequence numbers, subchannel data, etc. on failure the decrypted
+        // datagram is handed back with the error
+        match self.parse_datagram(&packet_data) {
+            Ok(datagram) => Ok(datagram),
+            Err(source) => Err(ChannelError::ParseFailed { bytes: packet_data.to_vec(), source }.into()),
+        }
+    }
 
+    /// update the channel with a datagram that has been decoded
+    fn accept_datagram(&mut self, datagram: NetDatagram) -> Result<NetDatagram>
+    {
         // update current sequence number info for this packet
-        self.in_sequence = datagram.header.sequence_in;
-        self.out_sequence_ack = datagram.header.sequence_ack;
+        self.sequence.in_sequence.store(datagram.header.sequence_in, Ordering::SeqCst);
+        // the writer reads the ack before the reliable state, so store them the other way round
+        self.sequence.peer_reliable_state.store(datagram.header.reliable_state, Ordering::SeqCst);
+        self.sequence.out_sequence_ack.store(datagram.header.sequence_ack, Ordering::SeqCst);
+        self.packet_loss.get_mut().observe(datagram.header.sequence_in, datagram.header.choked);
 
-        trace!("Finished parsing datagram [seq={}, seq_ack={}]", self.in_sequence, self.out_sequence_ack);
-        Ok(datagram)
-    }
+        // update any channel state that depends on the received messages
+        let disconnect = self.process_messages(&datagram);
 
-    fn decrypt_packet<'a>(&self, datagram: &'a mut [u8]) -> Result<&'a [u8]>
-    {
-        // decrypt the buffer
-        self.crypt.decrypt_buffer_inplace(datagram);
+        trace!("Finished parsing datagram [seq={}, seq_ack={}]", datagram.header.sequence_in, datagram.header.sequence_ack);
 
-        // the first byte is the number of garbage bytes added to the packet
-        let garbage = datagram[0] as usize;
-        if garbage >= 0x80 || garbage+1 >= datagram.len() {
-            return Err(anyhow::anyhow!("Invalid garbage bytes in packet"));
+        // the rest of the datagram goes back with the disconnect so none of it is lost
+        if let Some(text) = disconnect {
+            return Err(ChannelError::Disconnected { reason: KickReason::from_text(&text), text, datagram: Box::new(datagram) }.into());
         }
 
-        // prune the garbage bytes off of our payload
-        let packet = &datagram[garbage+1..];
+        Ok(datagram)
+    }
 
-        // read the 4-byte network byte order size field of the packet
-        let mut reader = BitReader::endian(Cursor::new(packet), BigEndian);
-        let size_on_wire: i32 = reader.read_signed(32)?;
-        let size_on_wire = size_on_wire as usize;
+    /// update channel state from the netmessages received in a datagram
+    /// returns the reason text if the server has disconnected us
+    fn process_messages(&mut self, datagram: &NetDatagram) -> Option<String>
+    {
+        let messages = match datagram.get_messages() {
+            Some(messages) => messages,
+            None => return None,
+        };
+
+        for message in messages.iter() {
+            // a game event the log can't make sense of shouldn't take the channel down with it
+            if let Some(game_event_log) = self.game_event_log.as_mut() {
+                if let Err(e) = game_event_log.handle_message(message, self.server_tick.unwrap_or(0)) {
+                    warn!("Failed to log game event: {}", e);
+                }
+            }
 
-        // expect the packet to not lie about its size
-        if size_on_wire > (packet.len()-4) {
-            return Err(anyhow::anyhow!("Invalid wire size"));
+            if let Some(tick) = message.downcast::<CNETMsg_Tick>() {
+                // keep track of the server's tick, this is what baselines are acknowledged against
+                self.server_tick = Some(tick.get_tick());
+            } else if let Some(entities) = message.downcast::<CSVCMsg_PacketEntities>() {
+                // the server has a new baseline it wants us to delta against
+                if entities.get_update_baseline() {
+                    self.pending_baseline_ack = Some((self.server_tick.unwrap_or(0), entities.get_baseline()));
+                }
+            } else if let Some(set_convar) = message.downcast::<CNETMsg_SetConVar>() {
+                // remember the latest value of every convar the server sends
+                for cvar in set_convar.get_convars().get_cvars().iter() {
+                    if cvar.has_name() {
+                        self.convars.insert(cvar.get_name().to_string(), cvar.get_value().to_string());
+                    } else {
+                        trace!("Ignoring convar sent by dictionary index {}", cvar.get_dictionary_name());
+                    }
+                }
+            } else if let Some(print) = message.downcast::<CSVCMsg_Print>() {
+                // pass server console output along to whoever is listening
+                if let Some(on_print) = self.on_print.as_mut() {
+                    on_print(print.get_text());
+                }
+            } else if let Some(signon) = message.downcast::<CNETMsg_SignonState>() {
+                // let whoever is listening know when the server moves us along (e.g. to full)
+                if signon.get_signon_state() as i32 != self.signon_state {
+                    self.signon_state = signon.get_signon_state() as i32;
+                    trace!("Server changed signon state to {}", self.signon_state);
+
+                    if let Some(on_signon_change) = self.on_signon_change.as_mut() {
+                        on_signon_change(self.signon_state);
+                    }
+                }
+            } else if let Some(set_pause) = message.downcast::<CSVCMsg_SetPause>() {
+                // ticks stop advancing while paused, so timing by tick has to know about it
+                if set_pause.get_paused() != self.paused {
+                    self.paused = set_pause.get_paused();
+                    trace!("Server {} the game", if self.paused { "paused" } else { "unpaused" });
+
+                    if let Some(on_pause_change) = self.on_pause_change.as_mut() {
+                        on_pause_change(self.paused);
+                    }
+                }
+            } else if let Some(voice_init) = message.downcast::<CSVCMsg_VoiceInit>() {
+                // voice we send has to be encoded the way the server says
+                let settings = VoiceSettings {
+                    codec: voice_init.get_codec().to_string(),
+                    quality: voice_init.get_quality(),
+                    version: voice_init.get_version(),
+                };
+                trace!("Server voice settings: {:?}", settings);
+                *self.voice_settings.lock().unwrap() = Some(settings);
+            } else if let Some(disconnect) = message.downcast::<CNETMsg_Disconnect>() {
+                // nothing else the server sends matters once it has dropped us
+                let text = disconnect.get_text().to_string();
+                warn!("Disconnected by server: {}", text);
+                self.disconnected.store(true, Ordering::SeqCst);
+
+                return Some(text);
+            }
         }
 
-        // prune off the size_on_wire field
-        let packet_data = &packet[4..(size_on_wire+4)];
-
-        return Ok(packet_data);
+        None
     }
 
-    /// LE -> BE byteswap
-    fn bswap(le_in: u32) -> u32 {
-        let mut out: u32 = 0;
-        out |= ((le_in >> 24) as u8) as u32;
-        out |= (((le_in >> 16) as u8) as u32) << 8;
-        out |= (((le_in >> 8) as u8) as u32) << 16;
-        out |= ((le_in as u8) as u32) << 24;
-        return out;
+    /// get the last value the server set for a convar with net_SetConVar, if it has sent one
+    pub fn get_convar(&self, name: &str) -> Option<&str>
+    {
+        self.convars.get(name).map(|value| value.as_str())
+    }
+
+    /// the address of the server this channel is connected to
+    pub fn peer_addr(&self) -> Result<SocketAddr>
+    {
+        Ok(self.wrapper.borrow().socket.peer_addr()?)
+    }
+
+    /// the local address this channel's socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr>
+    {
+        Ok(self.wrapper.borrow().socket.local_addr()?)
+    }
+
+    /// the host version the server reported in its challenge, even if the key was derived
+    /// from a different version
+    pub fn get_host_version(&self) -> u32
+    {
+        return self.host_version;
+    }
+
+    /// describe every reliable transfer which is still being received (e.g. a file download
+    /// alongside a large message payload)
+    pub fn active_transfers(&self) -> Vec<TransferInfo>
+    {
+        self.streams.borrow()
+            .iter()
+            .enumerate()
+            .filter_map(|(stream_i, stream)| stream.transfer_info(SubchannelStreamType::from(stream_i as u8)))
+            .collect()
+    }
+
+    /// start writing every received netmessage (id, size, raw bytes and a timestamp) to a capture
+    /// file at `path`, which can be read back with `CaptureReader`. replaces any current capture
+    pub fn start_capture(&mut self, path: &Path) -> Result<()>
+    {
+        self.stop_capture()?;
+        *self.capture.borrow_mut() = Some(CaptureWriter::create(path)?);
+
+        Ok(())
+    }
+
+    /// stop capturing netmessages and close the capture file
+    pub fn stop_capture(&mut self) -> Result<()>
+    {
+        if let Some(mut writer) = self.capture.borrow_mut().take() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// set the ICE key used to decrypt svc_EncryptedData messages of the given key type.
+    /// without one, the channel's own key is tried
+    pub fn set_encrypted_data_key(&mut self, key_type: i32, key: &[u8; 16])
+    {
+        self.encrypted_data_keys.insert(key_type, IceEncryption::new(2, key));
+    }
+
+    /// set a callback which receives the text of all svc_Print messages sent by the server
+    /// (server console output such as map changes, admin announcements, rcon output)
+    pub fn set_print_handler<F>(&mut self, handler: F)
+        where F: FnMut(&str) + Send + 'static
+    {
+        self.on_print = Some(Box::new(handler));
+    }
+
+    /// set a callback which receives every connectionless packet read by `read_data`, with its
+    /// type and the bytes of its values. without one they are dropped with a warning
+    pub fn set_connectionless_handler<F>(&mut self, handler: F)
+        where F: FnMut(ConnectionlessPacketType, &[u8]) + Send + 'static
+    {
+        self.on_connectionless = Some(Box::new(handler));
+    }
+
+    /// the largest capacity a recycled encode/decode buffer keeps between messages. a buffer
+    /// grown past this for one huge message gives its memory back instead of keeping it
+    pub fn set_max_retained_buffer(&mut self, max_retained: usize)
+    {
+        self.buffer_pool.get_mut().set_max_retained(max_retained);
+    }
+
+    /// fail with an error when a reliable transfer can't be decompressed. by default the corrupt
+    /// transfer is dropped with a warning and the channel carries on. it is still acknowledged,
+    /// a resend would only carry the same corrupt data
+    pub fn set_strict_decompression(&mut self, strict: bool)
+    {
+        for subchan in self.streams.get_mut().iter_mut() {
+            subchan.set_strict(strict);
+        }
+    }
+
+    /// set everything `config` covers, on top of whatever was set before
+    pub fn apply_config(&mut self, config: &NetChannelConfig)
+    {
+        self.set_stream_enabled(SubchannelStreamType::Message, config.message_stream);
+        self.set_stream_enabled(SubchannelStreamType::File, config.file_stream);
+        self.set_strict_decompression(config.strict_decompression);
+    }
+
+    /// turn receiving a subchannel stream on or off. a disabled stream's data is still read past
+    /// so the rest of the datagram parses, but it is never buffered or processed. the file
+    /// stream is off unless turned on here or with `NetChannelConfig`
+    pub fn set_stream_enabled(&mut self, stream: SubchannelStreamType, enabled: bool)
+    {
+        if let Some(subchan) = self.streams.get_mut().get_mut(stream as usize) {
+            subchan.set_enabled(enabled);
+        }
+    }
+
+    /// the latest tick the server sent with net_Tick, None until it has sent one
+    pub fn current_tick(&self) -> Option<i32>
+    {
+        return self.server_tick.map(|tick| tick as i32);
+    }
+
+    /// decode received netmessages with `id` using `decoder`, for servers that send message ids
+    /// the crate doesn't know about. see `NetMessageRegistry::register`
+    pub fn register_message<F>(&mut self, id: i32, decoder: F)
+        where F: Fn(&[u8]) -> anyhow::Result<Box<dyn protobuf::Message>> + Send + 'static
+    {
+        self.message_registry.register(id, decoder);
+    }
+
+    /// estimated fraction of incoming datagrams lost, from 0.0 to 1.0, over the latest
+    /// PACKET_LOSS_WINDOW sequence numbers. datagrams that arrive out of order aren't counted
+    pub fn packet_loss(&self) -> f32
+    {
+        return self.packet_loss.borrow().loss();
+    }
+
+    /// start the sequence state over as if the channel was just created, keeping the socket
+    /// and everything configured on it. some servers re-key mid-session (a new
+    /// encryption_key_index), after which both sides count from the start again.
+    /// reliable transfers in progress are dropped both ways, the server resends its own under the
+    /// new sequence. messages queued for the next flush are dropped too
+    pub fn reset_sequences(&mut self)
+    {
+        self.sequence.in_sequence.store(0, Ordering::SeqCst);
+        self.sequence.out_sequence_ack.store(0, Ordering::SeqCst);
+        self.sequence.reliable_state.store(0, Ordering::SeqCst);
+        self.sequence.peer_reliable_state.store(0, Ordering::SeqCst);
+
+        self.out_sequence = 1;
+        self.choked_num = 0;
+        self.send_queue.clear();
+
+        for stream in self.streams.get_mut().iter_mut() {
+            stream.reset();
+        }
+        *self.reliable.get_mut() = ReliableSender::new();
+
+        *self.packet_loss.get_mut() = PacketLossWindow::default();
+    }
+
+    /// start logging every svc_GameEvent received along with the server tick it arrived on.
+    /// the server's svc_GameEventList is needed to decode them, so this should be done before signon
+    pub fn enable_game_event_log(&mut self)
+    {
+        if self.game_event_log.is_none() {
+            self.game_event_log = Some(GameEventLog::new());
+        }
+    }
+
+    /// the game events logged so far, None unless `enable_game_event_log` was called
+    pub fn game_event_log(&self) -> Option<&GameEventLog>
+    {
+        return self.game_event_log.as_ref();
+    }
+
+    /// take every game event logged so far, oldest first
+    pub fn take_game_events(&mut self) -> Vec<(u32, GameEvent)>
+    {
+        match self.game_event_log.as_mut() {
+            Some(game_event_log) => game_event_log.drain().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// the latest signon state the server has sent, 0 until it sends one
+    /// (SIGNONSTATE_FULL once the server considers us fully connected)
+    pub fn signon_state(&self) -> i32
+    {
+        return self.signon_state;
+    }
+
+    /// set a callback which receives the new signon state every time the server changes it
+    pub fn set_signon_change_handler<F>(&mut self, handler: F)
+        where F: FnMut(i32) + Send + 'static
+    {
+        self.on_signon_change = Some(Box::new(handler));
+    }
+
+    /// whether the server has paused the game with svc_SetPause. the server's tick doesn't
+    /// advance while paused, so a gap in ticks isn't necessarily packet loss
+    pub fn is_paused(&self) -> bool
+    {
+        return self.paused;
+    }
+
+    /// set a callback which receives the new pause state every time the server pauses or
+    /// unpauses the game
+    pub fn set_pause_change_handler<F>(&mut self, handler: F)
+        where F: FnMut(bool) + Send + 'static
+    {
+        self.on_pause_change = Some(Box::new(handler));
+    }
+
+    /// acknowledge the latest entity baseline sent by the server with a clc_BaselineAck,
+    /// without this the server never deltas against the new baseline
+    /// returns true if an acknowledgement was sent
+    pub fn ack_baseline(&mut self) -> Result<bool>
+    {
+        let (tick, baseline) = match self.pending_baseline_ack.take() {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+
+        let mut ack = CCLCMsg_BaselineAck::new();
+        ack.set_baseline_tick(tick as i32);
+        ack.set_baseline_nr(baseline);
+
+        trace!("Acknowledging baseline [tick={}, baseline={}]", tick, baseline);
+
+        self.write_netmessage(NetMessage::from_proto(Box::new(ack), CLC_Messages::clc_BaselineAck as i32))?;
+
+        Ok(true)
+    }
+
+    /// the voice codec settings the server announced, None until it has sent svc_VoiceInit
+    pub fn voice_settings(&self) -> Option<VoiceSettings>
+    {
+        return self.voice_settings.lock().unwrap().clone();
+    }
+
+    /// encode 16-bit mono PCM at VOICE_SAMPLE_RATE with the codec the server announced and send
+    /// it with clc_VoiceData. `pcm` must be a whole number of VOICE_FRAME_SAMPLES frames, and
+    /// codecs we can't encode are an error
+    pub fn send_voice(&mut self, pcm: &[i16]) -> Result<()>
+    {
+        let settings = match self.voice_settings() {
+            Some(settings) => settings,
+            None => return Err(ChannelError::Protocol("server has not sent svc_VoiceInit").into()),
+        };
+
+        // the server announces its settings again on a map change, start over if they changed
+        if self.voice_encoder.as_ref().map_or(true, |encoder| *encoder.settings() != settings) {
+            self.voice_encoder = Some(VoiceEncoder::new(&settings)?);
+        }
+
+        let data = self.voice_encoder.as_mut().expect("voice encoder was just created").encode(pcm)?;
+        self.send_voice_data(&data)
+    }
+
+    /// send a frame of voice to the server with clc_VoiceData. `data` must already be encoded
+    /// with the codec from `voice_settings`, this does no encoding of its own
+    pub fn send_voice_data(&mut self, data: &[u8]) -> Result<()>
+    {
+        let format = match self.voice_settings() {
+            Some(settings) if settings.codec == "steam" => VoiceDataFormat_t::VOICEDATA_FORMAT_STEAM,
+            Some(_) => VoiceDataFormat_t::VOICEDATA_FORMAT_ENGINE,
+            None => return Err(ChannelError::Protocol("server has not sent svc_VoiceInit").into()),
+        };
+
+        let mut voice = CCLCMsg_VoiceData::new();
+        voice.set_data(data.to_vec());
+        voice.set_format(format);
+        voice.set_sequence_bytes(self.voice_sequence_bytes);
+
+        self.write_netmessage(NetMessage::from_proto(Box::new(voice), CLC_Messages::clc_VoiceData as i32))?;
+        self.voice_sequence_bytes = self.voice_sequence_bytes.wrapping_add(data.len() as i32);
+
+        Ok(())
+    }
+
+    /// decrypt a datagram in place and return the payload inside of it
+    pub(crate) fn decrypt_packet<'a>(&self, datagram: &'a mut [u8]) -> Result<&'a [u8]>
+    {
+        Self::decrypt_payload(&self.crypt, datagram)
+    }
+
+    /// decrypt an ICE encrypted payload in place and return the data inside of it.
+    /// used for both datagrams and svc_EncryptedData, which share the same layout
+    fn decrypt_payload<'a>(crypt: &IceEncryption, datagram: &'a mut [u8]) -> Result<&'a [u8]>
+    {
+        // decrypt the buffer
+        crypt.decrypt_buffer_inplace(datagram);
+
+        // the first byte is the number of garbage bytes added to the packet
+        let garbage = datagram[0] as usize;
+        if garbage >= 0x80 || garbage+1 >= datagram.len() {
+            return Err(anyhow::anyhow!("Invalid garbage bytes in packet"));
+        }
+
+        // prune the garbage bytes off of our payload
+        let packet = &datagram[garbage+1..];
+
+        // read the 4-byte network byte order size field of the packet, it is signed on the wire
+        let mut reader = BitReader::endian(Cursor::new(packet), LittleEndian);
+        let size_on_wire = NetworkOrderU32::read(&mut reader)?.get() as i32;
+
+        // the size is signed on the wire, but a negative length is never valid
+        if size_on_wire < 0 {
+            return Err(ChannelError::Protocol("negative wire size").into());
+        }
+        let size_on_wire = size_on_wire as usize;
+
+        // expect the packet to not lie about its size
+        if size_on_wire > (packet.len()-4) {
+            return Err(anyhow::anyhow!("Invalid wire size"));
+        }
+
+        // prune off the size_on_wire field
+        let packet_data = &packet[4..(size_on_wire+4)];
+
+        return Ok(packet_data);
     }
 
     /// encrypt the datagram and return a reference to the encrypted result
-    fn encrypt_packet(&self, datagram: &mut [u8]) -> Result<Ref<Vec<u8>>>
+    pub(crate) fn encrypt_packet(&self, datagram: &mut [u8]) -> Result<Ref<Vec<u8>>>
     {
         {
             // get a reference to the temp buffer
@@ -461,7 +2087,7 @@ impl NetChannel {
             }
 
             // write the size on the wire
-            writer.write_long(NetChannel::bswap(datagram.len() as u32))?;
+            NetworkOrderU32(datagram.len() as u32).write(&mut writer)?;
 
             // and the actual payload
             writer.write_bytes(datagram)?;
@@ -486,14 +2112,7 @@ impl NetChannel {
             // 4 + 4 + 1 + 2
             let sum_area = &immut_scratch[11..];
 
-            // CRC32 on the buffer
-            let mut hasher = Hasher::new();
-            hasher.update(sum_area);
-            let checksum = hasher.finalize();
-
-            // XOR the high and low parts together to make the shortened sum
-            // I doubt this is actually a good way to checksum... but it's how the engine does it
-            shortened_checksum = (checksum as u16) ^ ((checksum >> 16) as u16);
+            shortened_checksum = Self::datagram_checksum(sum_area);
         }
 
         // update the packet now
@@ -502,6 +2121,19 @@ impl NetChannel {
         Ok(())
     }
 
+    /// calculate the shortened CRC32 checksum the engine uses for datagrams
+    fn datagram_checksum(sum_area: &[u8]) -> u16
+    {
+        // CRC32 on the buffer
+        let mut hasher = Hasher::new();
+        hasher.update(sum_area);
+        let checksum = hasher.finalize();
+
+        // XOR the high and low parts together to make the shortened sum
+        // I doubt this is actually a good way to checksum... but it's how the engine does it
+        return (checksum as u16) ^ ((checksum >> 16) as u16);
+    }
+
     /// update the checksum field of the current pending internal scratch buffer packet
     fn update_scratch_checksum(&self, checksum: u16) -> Result<()> {
         // create a cursor on the internal scratch buffer
@@ -520,37 +2152,150 @@ impl NetChannel {
     }
 
     /// send a netmessage to the server
-    pub fn write_netmessage(&mut self, mut message: NetMessage) -> anyhow::Result<()>
+    pub fn write_netmessage(&mut self, message: NetMessage) -> anyhow::Result<()>
     {
-        // clear to prepare for a new
-        self.encode_buffer.clear();
+        let mut encode_buffer = self.buffer_pool.borrow_mut().take();
 
         // ensure there is enough space in the encode buffer
-        let max_size = message.get_max_size();
-        if self.encode_buffer.capacity() < max_size {
-            self.encode_buffer.reserve(message.get_max_size() - max_size);
-        }
+        encode_buffer.reserve(message.get_max_size());
 
-        // encode the protobuf message to the local encoding buffer
-        message.encode_to_buffer(&mut self.encode_buffer)?;
-
-        // write to the network
-        self.write_datagram(&self.encode_buffer)?;
+        // encode the protobuf message to the encoding buffer
+        // and write it to the network, the buffer goes back to the pool either way
+        let result = message.encode_to_buffer(&mut encode_buffer)
+            .and_then(|_| self.write_datagram(&encode_buffer));
+        self.buffer_pool.borrow_mut().give(encode_buffer);
+        result?;
 
-        // continue processing next sequence
+        // continue processing next sequence, any choked packets have now been reported
         self.out_sequence += 1;
+        self.choked_num = 0;
 
         Ok(())
     }
 
+    /// run a console command on the server with net_StringCmd, e.g. "say hello" or "jointeam 2"
+    pub fn send_command(&mut self, cmd: &str) -> anyhow::Result<()>
+    {
+        let mut string_cmd = CNETMsg_StringCmd::new();
+        string_cmd.set_command(cmd.to_string());
+
+        self.write_netmessage(NetMessage::from_proto(Box::new(string_cmd), NET_Messages::net_StringCmd as i32))
+    }
+
+    /// tell the server we're leaving with net_Disconnect. the channel shouldn't be used to send
+    /// anything else afterwards
+    pub fn disconnect(&mut self, reason: &str) -> anyhow::Result<()>
+    {
+        let mut disconnect = CNETMsg_Disconnect::new();
+        disconnect.set_text(reason.to_string());
+
+        self.write_netmessage(NetMessage::from_proto(Box::new(disconnect), NET_Messages::net_Disconnect as i32))?;
+        self.disconnected.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// whether dropping the channel sends net_Disconnect to the server (the default). turn this
+    /// off to drop the channel silently, e.g. to hand the connection over to another process
+    pub fn set_disconnect_on_drop(&mut self, disconnect_on_drop: bool)
+    {
+        self.disconnect_on_drop = disconnect_on_drop;
+    }
+
     /// write a nop packet (no net messages encoded)
     pub fn write_nop(&mut self) -> anyhow::Result<()>
     {
         // write to the network
         self.write_datagram(&[])?;
 
-        // continue processing next sequence
+        // continue processing next sequence, any choked packets have now been reported
+        self.out_sequence += 1;
+        self.choked_num = 0;
+
+        Ok(())
+    }
+
+    /// queue a netmessage to be sent with the next flush, rather than in its own datagram
+    pub fn queue_message(&mut self, message: NetMessage) -> anyhow::Result<()>
+    {
+        let mut encode_buffer = self.buffer_pool.borrow_mut().take();
+        let result = message.encode_to_buffer(&mut encode_buffer);
+        if result.is_ok() {
+            self.send_queue.extend_from_slice(&encode_buffer);
+        }
+
+        self.buffer_pool.borrow_mut().give(encode_buffer);
+        result
+    }
+
+    /// send a netmessage reliably over the message stream, compressed as set by
+    /// `ConnectionlessChannel::set_compression`. it goes out with the datagrams sent after this,
+    /// split over as many as it needs, and each part is sent again if the server doesn't get it
+    pub fn send_reliable(&mut self, message: NetMessage) -> anyhow::Result<()>
+    {
+        let mut encode_buffer = self.buffer_pool.borrow_mut().take();
+        let compression = self.wrapper.get_mut().compression;
+
+        let result = message.encode_to_buffer(&mut encode_buffer)
+            .and_then(|_| OutgoingTransfer::new(&encode_buffer, compression));
+        self.buffer_pool.borrow_mut().give(encode_buffer);
+
+        self.reliable.get_mut().queue(result?);
+        Ok(())
+    }
+
+    /// whether every payload given to `send_reliable` has been received by the server
+    pub fn is_reliable_idle(&self) -> bool
+    {
+        return self.reliable.borrow().is_idle();
+    }
+
+    /// keep reliable data moving, call this regularly (e.g. from the read loop) while anything
+    /// sent with `send_reliable` is outstanding. sends the next batch once the last one is
+    /// acknowledged, and a lost batch again. a batch still unacknowledged after the retransmit
+    /// timeout gets a datagram sent after it, the server's ack of that says whether the batch
+    /// arrived. returns whether a datagram was sent
+    pub fn tick(&mut self) -> anyhow::Result<bool>
+    {
+        self.tick_at(Instant::now())
+    }
+
+    /// `tick` as of `now`
+    fn tick_at(&mut self, now: Instant) -> anyhow::Result<bool>
+    {
+        let reliable = self.reliable.get_mut();
+        reliable.update(
+            self.sequence.out_sequence_ack.load(Ordering::SeqCst),
+            self.sequence.peer_reliable_state.load(Ordering::SeqCst),
+            now,
+        );
+
+        if !reliable.has_pending() && !reliable.check_retransmit(now) {
+            return Ok(false);
+        }
+
+        self.write_nop()?;
+        Ok(true)
+    }
+
+    /// skip sending on this tick, like the engine does to stay under the rate limit. the
+    /// sequence number still moves on, and the next datagram sent tells the server how many
+    /// packets were choked
+    pub fn choke(&mut self)
+    {
+        self.out_sequence += 1;
+        self.choked_num = self.choked_num.saturating_add(1);
+    }
+
+    /// send every queued netmessage together in one datagram
+    pub fn flush(&mut self) -> anyhow::Result<()>
+    {
+        self.write_datagram(&self.send_queue)?;
+        self.send_queue.clear();
+
+        // continue processing next sequence, any choked packets have now been reported
         self.out_sequence += 1;
+        self.choked_num = 0;
 
         Ok(())
     }
@@ -573,7 +2318,16 @@ impl NetChannel {
             writer.write_long(self.out_sequence)?;
 
             // the input sequence number we are acknowledging
-            writer.write_long(self.in_sequence)?;
+            writer.write_long(self.sequence.in_sequence.load(Ordering::SeqCst))?;
+
+            // see what became of the reliable data we last sent before sending any more
+            let mut reliable = self.reliable.borrow_mut();
+            reliable.update(
+                self.sequence.out_sequence_ack.load(Ordering::SeqCst),
+                self.sequence.peer_reliable_state.load(Ordering::SeqCst),
+                Instant::now(),
+            );
+            let send_reliable = reliable.has_pending();
 
             // packet flags (choked, reliable data)
             let mut flags: u8 = 0;
@@ -583,16 +2337,19 @@ impl NetChannel {
                 flags |= PACKET_CHOKED;
             }
 
+            // is there reliable data after the header?
+            if send_reliable {
+                flags |= PACKET_RELIABLE;
+            }
+
             // write packet flags
             writer.write_char(flags)?;
 
             // write packet checksum as 0, we will checksum later then restore here
             writer.write_signed(16, 0)?;
 
-            // TODO: create send-side reliable fragments
-
             // write the reliable state (established in read_data)
-            writer.write_char(self.reliable_state.get())?;
+            writer.write_char(self.sequence.reliable_state.load(Ordering::SeqCst))?;
 
             // if we have choked packets, write them here
             if self.choked_num > 0 {
@@ -600,10 +2357,16 @@ impl NetChannel {
                 writer.write_char(self.choked_num)?;
             }
 
-            // TODO: Padding?
+            // the next batch of reliable data comes before the netmessages
+            if send_reliable {
+                reliable.write_next(&mut writer, self.out_sequence)?;
+            }
 
             // write the contents of the message
             writer.write_bytes(send_buffer)?;
+
+            // the reliable section isn't a whole number of bytes, pad out the last one
+            writer.byte_align()?;
         }
 
         // calculate and fix the checksum
@@ -623,69 +2386,30 @@ impl NetChannel {
     }
 
     /// reads a set of netmessages from a payload
-    fn read_messages<T>(&self, reader: &mut BitReader<T, LittleEndian>) -> anyhow::Result<Vec<NetMessage>>
+    fn read_messages<T>(&self, reader: BitReader<T, LittleEndian>, stats: &mut DecodeStats) -> anyhow::Result<Vec<NetMessage>>
         where T: std::io::Read
     {
-        let mut decode_buf: SmallVec<[u8; 0x1000*2]> = SmallVec::new();
-
-        let mut out_messages: Vec<NetMessage> = Vec::with_capacity(32);
-
         trace!("--- read_messages() begin ---");
-        loop {
-            // if there is still data, there must be messages for us to process
-            let message_number_e = reader.read_int32_var();
-            let message_id: u32;
-
-            // when we reach EOF, we stop netmessage parsing
-            if message_number_e.is_err() {
-                break;
-            } else {
-                // the message index number, maps to the netmessage enum
-                message_id = message_number_e?;
-            }
-
-            if message_id == 0 {
-                // NOP packet, just ignore
-                continue;
-            }
-
-            // total size of the message
-            let message_size = reader.read_int32_var()? as usize;
-
-            trace!("MESSAGE [id={}, size={}]:", message_id, message_size);
-
-            // allocate either stack or heap data depending on size
-            if message_size > decode_buf.capacity()
-            {
-                decode_buf.reserve(message_size - decode_buf.len());
-            }
-
-            // use unallocated space, don't clear contents
-            unsafe {
-                decode_buf.set_len(message_size);
-            }
-
-            // read the message's data
-            reader.read_bytes(decode_buf.as_mut_slice())?;
-
-            // decode the protobuf message
-            let message = NetMessage::bind(message_id as i32, decode_buf.as_slice());
-            if message.is_err() {
-                warn!("Failed decoding netmessage [id={}]: {}", message_id, message.err().unwrap());
-                continue;
-            }
-
-            let message = message.unwrap();
+        let mut messages = NetMessageIter::new(self, reader);
+        let out_messages = (&mut messages).collect::<anyhow::Result<Vec<NetMessage>>>();
 
-            trace!("Successfully decoded \"{}\" (id={}, size={}) message", message.get_type_name(), message_id, message_size);
-
-            // return this message
-            out_messages.push(message);
-        }
+        let decoded = messages.stats();
+        stats.decoded += decoded.decoded;
+        stats.unknown += decoded.unknown;
+        stats.failed += decoded.failed;
 
         // no more netmessages in this packet
-        trace!("--- read_messages() end [{} messages read] ---", out_messages.len());
-        return Ok(out_messages);
+        trace!("--- read_messages() end [{:?}] ---", stats);
+        return out_messages;
+    }
+
+    /// decode the netmessages in `payload` one at a time as they are asked for, so a caller
+    /// looking for a particular message can stop early without decoding the rest. the payload
+    /// is a run of netmessages as they appear on the wire, e.g. a completed transfer. messages
+    /// read this way don't update the channel's state, unlike the ones `read_data` returns
+    pub fn read_messages_iter<'a>(&'a self, payload: &'a [u8]) -> NetMessageIter<'a, S>
+    {
+        NetMessageIter::new(self, BitReader::endian(std::io::Cursor::new(payload), LittleEndian))
     }
 
     /// when a payload is received over a subchannel stream, process its data here
@@ -695,46 +2419,128 @@ impl NetChannel {
         let payload = transfer.unwrap_payload();
 
         // convert it to a bit reader
-        let mut reader = BitReader::endian(std::io::Cursor::new(payload), LittleEndian);
+        let reader = BitReader::endian(std::io::Cursor::new(payload), LittleEndian);
 
         // read the message/file inside
         match stream_index {
             // the message stream sends payloads that contain large, reliably sent groups of netmessages
-            SubchannelStreamType::Message => out_datagram.add_messages(self.read_messages(&mut reader)?),
-            SubchannelStreamType::File => panic!("File transfers not implemented yet!"),
+            SubchannelStreamType::Message => {
+                let messages = self.read_messages(reader, &mut out_datagram.decode_stats)?;
+                out_datagram.add_messages(messages);
+            },
+            // nothing can be done with a file yet, and the server sending one is no reason to stop
+            SubchannelStreamType::File => warn!("Dropping file received over the file stream, receiving files is not supported"),
             _ => ()
         }
 
         Ok(())
     }
 
-    /// parses datagram header and body values
-    /// parses netmessages from the packet and returns it in the NetDatagram packet
-    fn parse_datagram(&self, packet_data: &[u8]) -> anyhow::Result<NetDatagram>
+    /// write a received netmessage to the capture file, if there is one.
+    /// a failed write stops the capture rather than the channel
+    fn capture_message(&self, id: i32, data: &[u8])
     {
-        let mut reader = BitReader::endian(std::io::Cursor::new(packet_data), LittleEndian);
+        let mut capture = self.capture.borrow_mut();
 
-        // incoming sequence number
-        let mut sequence_in = reader.read_long()?;
-        let decompressed: Vec<u8>;
+        if let Some(writer) = capture.as_mut() {
+            if let Err(e) = writer.write_message(id, data) {
+                warn!("Stopping netmessage capture, failed to write: {}", e);
+                *capture = None;
+            }
+        }
+    }
 
-        if sequence_in == NET_HEADER_FLAG_COMPRESSEDPACKET {
-            trace!("Compressed datagram, {} uncompressed", packet_data.len());
+    /// decrypt the payload of an svc_EncryptedData message and read the netmessages inside of it
+    fn read_encrypted_data(&self, encrypted: &CSVCMsg_EncryptedData, stats: &mut DecodeStats) -> anyhow::Result<Vec<NetMessage>>
+    {
+        // use the key set for this key type, otherwise try the channel's own key
+        let crypt = match self.encrypted_data_keys.get(&encrypted.get_key_type()) {
+            Some(crypt) => crypt,
+            None => &self.crypt,
+        };
+
+        let mut data = encrypted.get_encrypted().to_vec();
+        if (data.len() % 8) != 0 {
+            return Err(anyhow::anyhow!("Unexpected encrypted data alignment"));
+        }
 
-            // decompress the LZSS payload
-            decompressed = Lzss::decode(&packet_data[4..])?;
+        let payload = Self::decrypt_payload(crypt, &mut data)?;
 
-            // retry this, but this time with the decompressed packet
-            reader = BitReader::endian(std::io::Cursor::new(decompressed.as_slice()), LittleEndian);
+        let reader = BitReader::endian(std::io::Cursor::new(payload), LittleEndian);
+        self.read_messages(reader, stats)
+    }
 
-            // re-read the first value of the packet now that it's decompressed
-            sequence_in = reader.read_long()?;
+    /// parses the reliable subchannel section of a datagram, any completed transfers have their
+    /// messages added to `out_datagram`
+    fn parse_reliable_data<T>(&self, reader: &mut BitReader<T, LittleEndian>, out_datagram: &mut NetDatagram) -> anyhow::Result<()>
+        where T: std::io::Read
+    {
+        // which of the sender's subchannels is sending data? this only decides which reliable
+        // state bit gets flipped, the data is received per stream below
+        let subchan_i = reader.read::<u8>(3)?;
+        trace!("subchannel[{}] is marked as updated", subchan_i);
+
+        if subchan_i as usize >= MAX_SUBCHANNELS {
+            return Err(ChannelError::Protocol("subchannel index out of range").into());
+        }
+
+        // for each stream in the subchannel,
+        for stream_i in 0..MAX_STREAMS {
+            // grab the receive buffer for this stream
+            let subchan = &mut (self.streams.borrow_mut())[stream_i];
+
+            // check to see if this stream is updated
+            let updated = reader.read_bit()?;
+            trace!("subchannel[{}][stream={}] updated={}", subchan_i, stream_i, updated);
+
+            if updated {
+                // read all incoming subchannel data
+                let buf = subchan.read_subchannel_data(reader)?;
+
+                // has a subchannel transfer completed?
+                if buf.is_some()
+                {
+                    // we received a full payload, processes it depending on what subchannel stream we're
+                    // receiving from
+                    self.process_subchannel_payload(buf.unwrap(), SubchannelStreamType::from(stream_i as u8), out_datagram)?;
+                }
+            }
+        }
+
+        // mark this subchannel as being read from by flipping the bit in reliable state
+        self.sequence.reliable_state.fetch_xor(1 << subchan_i, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// parses datagram header and body values
+    /// parses netmessages from the packet and returns it in the NetDatagram packet.
+    /// None if the datagram is late or a duplicate, which is dropped like the engine does
+    fn parse_datagram(&self, packet_data: &[u8]) -> anyhow::Result<Option<NetDatagram>>
+    {
+        let mut reader = BitReader::endian(std::io::Cursor::new(packet_data), LittleEndian);
+
+        // incoming sequence number
+        let mut sequence_in = reader.read_long()?;
+        let decompressed: Vec<u8>;
+
+        if sequence_in == NET_HEADER_FLAG_COMPRESSEDPACKET {
+            trace!("Compressed datagram, {} uncompressed", packet_data.len());
+
+            // decompress the LZSS payload
+            decompressed = Lzss::decode(&packet_data[4..])?;
+
+            // retry this, but this time with the decompressed packet
+            reader = BitReader::endian(std::io::Cursor::new(decompressed.as_slice()), LittleEndian);
+
+            // re-read the first value of the packet now that it's decompressed
+            sequence_in = reader.read_long()?;
 
             trace!("Decompressed {} bytes from datagram", decompressed.len());
         }
 
         if sequence_in == CONNECTIONLESS_HEADER {
-            panic!("Connectionless headers over netchannels not supported yet!");
+            return Err(ChannelError::Protocol("connectionless packet inside a compressed datagram").into());
         }
 
         // acknowledgement of the last sequence number
@@ -744,7 +2550,7 @@ impl NetChannel {
         let flags = reader.read_char()?;
 
         // checksum of the packet
-        let checksum: i16 = reader.read_signed(16)?;
+        let checksum = reader.read_i16()?;
 
         // TODO: Checksum the packet
 
@@ -760,9 +2566,22 @@ impl NetChannel {
         }
 
         // check for packet lag, network duplication
-        if sequence_in <= self.in_sequence {
-            warn!("Sequence number mismatch (in={}, current={})", sequence_in, self.in_sequence);
-            return Err(anyhow::anyhow!("Sequence number mismatch"))
+        let current_sequence = self.sequence.in_sequence.load(Ordering::SeqCst);
+        if sequence_in <= current_sequence {
+            // a late datagram still means it wasn't lost
+            self.packet_loss.borrow_mut().observe(sequence_in, choked);
+
+            warn!("Dropping stale datagram (in={}, current={})", sequence_in, current_sequence);
+            return Ok(None);
+        }
+
+        // choked packets used up sequence numbers without being sent, anything else that was
+        // skipped over is a packet that went missing
+        if current_sequence != 0 {
+            let dropped = (sequence_in - current_sequence - 1).saturating_sub(choked as u32);
+            if dropped > 0 {
+                trace!("Dropped {} packets before sequence {} ({} choked)", dropped, sequence_in, choked);
+            }
         }
 
         // create the datagram struct to return to caller
@@ -778,46 +2597,1894 @@ impl NetChannel {
         // TODO: Subchannel bits
 
         // is there subchannel info?
-        if (flags & PACKET_RELIABLE) != 0
+        let reliable = (flags & PACKET_RELIABLE) != 0;
+        if reliable
         {
-            // which subchannel is currently sending data?
-            let subchan_i = reader.read::<u8>(3)?;
-            trace!("subchannel[{}] is marked as updated", subchan_i);
-
-            // for each stream in the subchannel,
-            for stream_i in 0..2 {
-                // grab the subchannel object
-                let subchan = &mut (self.subchannels.borrow_mut())[stream_i as usize];
-
-                // check to see if this stream is updated
-                let updated = reader.read_bit()?;
-                trace!("subchannel[{}][stream={}] updated={}", subchan_i, stream_i, updated);
-
-                if updated {
-                    // read all incoming subchannel data
-                    let buf = subchan.read_subchannel_data(&mut reader)?;
-
-                    // has a subchannel transfer completed?
-                    if buf.is_some()
-                    {
-                        // we received a full payload, processes it depending on what subchannel stream we're
-                        // receiving from
-                        self.process_subchannel_payload(buf.unwrap(), SubchannelStreamType::from(stream_i), &mut out_datagram)?;
-                    }
+            // a reliable section that runs off the end of the datagram is a protocol error,
+            // not something the caller should have to pick out of an i/o error
+            self.parse_reliable_data(&mut reader, &mut out_datagram).map_err(|e| {
+                if is_unexpected_eof(&e) {
+                    ChannelError::Protocol("malformed subchannel header").into()
+                } else {
+                    e
                 }
-            }
-
-            // mark this subchannel as being read from by flipping the bit in reliable state
-            let new_state = self.reliable_state.get() ^ (1 << subchan_i);
-            self.reliable_state.set(new_state);
+            })?;
         }
 
         // is there still data left in the packet? if so, netmessages will be parsed here here
-        let messages = self.read_messages(&mut reader)?;
+        let mut tail_stats = DecodeStats::default();
+        let messages = self.read_messages(reader, &mut tail_stats);
+
+        out_datagram.decode_stats.decoded += tail_stats.decoded;
+        out_datagram.decode_stats.unknown += tail_stats.unknown;
+        out_datagram.decode_stats.failed += tail_stats.failed;
+
+        // a spurious reliable flag (or a misparsed reliable header) leaves the reader somewhere
+        // in the middle of the netmessages, which then run off the end of the datagram or fail
+        // to parse. the reliable section has been received and acknowledged by now, so only the
+        // netmessages after it are dropped. ids we don't know are not a sign of a misparse, the
+        // server sends plenty of those
+        let messages = match messages {
+            Ok(_) if reliable && tail_stats.decoded == 0 && tail_stats.failed > 0 => {
+                warn!("Dropping netmessages in datagram {} after the reliable section, none of them parsed ({:?})", sequence_in, tail_stats);
+                Vec::new()
+            },
+            Err(e) if reliable => {
+                warn!("Dropping netmessages in datagram {} after the reliable section: {}", sequence_in, e);
+                Vec::new()
+            },
+            messages => messages?,
+        };
 
         // add any parsed messages to the datagram object
         out_datagram.add_messages(messages);
 
-        Ok(out_datagram)
+        Ok(Some(out_datagram))
+    }
+}
+impl<S> NetChannelReader<S>
+    where S: PacketTransport
+{
+    /// read all of the incoming data from a packet
+    pub fn read_data(&mut self) -> Result<NetDatagram>
+    {
+        self.channel.read_data()
+    }
+
+    /// read the next datagram, waiting no longer than `timeout`, see `NetChannel::read_data_timeout`
+    pub fn read_data_timeout(&mut self, timeout: Duration) -> Result<Option<NetDatagram>>
+    {
+        self.channel.read_data_timeout(timeout)
+    }
+
+    /// set a callback which receives the text of all svc_Print messages sent by the server
+    pub fn set_print_handler<F>(&mut self, handler: F)
+        where F: FnMut(&str) + Send + 'static
+    {
+        self.channel.set_print_handler(handler)
+    }
+
+    /// set a callback which receives every connectionless packet read by `read_data`
+    pub fn set_connectionless_handler<F>(&mut self, handler: F)
+        where F: FnMut(ConnectionlessPacketType, &[u8]) + Send + 'static
+    {
+        self.channel.set_connectionless_handler(handler)
+    }
+
+    /// the latest signon state the server has sent
+    pub fn signon_state(&self) -> i32
+    {
+        self.channel.signon_state()
+    }
+
+    /// fail when a reliable transfer can't be decompressed, see `NetChannel::set_strict_decompression`
+    pub fn set_strict_decompression(&mut self, strict: bool)
+    {
+        self.channel.set_strict_decompression(strict)
+    }
+
+    /// set everything `config` covers, see `NetChannel::apply_config`
+    pub fn apply_config(&mut self, config: &NetChannelConfig)
+    {
+        self.channel.apply_config(config)
+    }
+
+    /// turn receiving a subchannel stream on or off, see `NetChannel::set_stream_enabled`
+    pub fn set_stream_enabled(&mut self, stream: SubchannelStreamType, enabled: bool)
+    {
+        self.channel.set_stream_enabled(stream, enabled)
+    }
+
+    /// decode the netmessages in a payload lazily, see `NetChannel::read_messages_iter`
+    pub fn read_messages_iter<'a>(&'a self, payload: &'a [u8]) -> NetMessageIter<'a, S>
+    {
+        self.channel.read_messages_iter(payload)
+    }
+
+    /// the latest tick the server sent with net_Tick, None until it has sent one
+    pub fn current_tick(&self) -> Option<i32>
+    {
+        self.channel.current_tick()
+    }
+
+    /// decode received netmessages with `id` using `decoder`, see `NetChannel::register_message`
+    pub fn register_message<F>(&mut self, id: i32, decoder: F)
+        where F: Fn(&[u8]) -> anyhow::Result<Box<dyn protobuf::Message>> + Send + 'static
+    {
+        self.channel.register_message(id, decoder)
+    }
+
+    /// estimated fraction of incoming datagrams lost, see `NetChannel::packet_loss`
+    pub fn packet_loss(&self) -> f32
+    {
+        self.channel.packet_loss()
+    }
+
+    /// start logging every svc_GameEvent received, see `NetChannel::enable_game_event_log`
+    pub fn enable_game_event_log(&mut self)
+    {
+        self.channel.enable_game_event_log()
+    }
+
+    /// the game events logged so far, None unless logging was enabled
+    pub fn game_event_log(&self) -> Option<&GameEventLog>
+    {
+        self.channel.game_event_log()
+    }
+
+    /// take every game event logged so far, oldest first
+    pub fn take_game_events(&mut self) -> Vec<(u32, GameEvent)>
+    {
+        self.channel.take_game_events()
+    }
+
+    /// set a callback which receives the new signon state every time the server changes it
+    pub fn set_signon_change_handler<F>(&mut self, handler: F)
+        where F: FnMut(i32) + Send + 'static
+    {
+        self.channel.set_signon_change_handler(handler)
+    }
+
+    /// whether the server has paused the game
+    pub fn is_paused(&self) -> bool
+    {
+        self.channel.is_paused()
+    }
+
+    /// set a callback which receives the new pause state every time the server changes it
+    pub fn set_pause_change_handler<F>(&mut self, handler: F)
+        where F: FnMut(bool) + Send + 'static
+    {
+        self.channel.set_pause_change_handler(handler)
+    }
+
+    /// get the last value the server set for a convar with net_SetConVar, if it has sent one
+    pub fn get_convar(&self, name: &str) -> Option<&str>
+    {
+        self.channel.get_convar(name)
+    }
+
+    /// describe every reliable transfer which is still being received
+    pub fn active_transfers(&self) -> Vec<TransferInfo>
+    {
+        self.channel.active_transfers()
+    }
+
+    /// set the ICE key used to decrypt svc_EncryptedData messages of the given key type
+    pub fn set_encrypted_data_key(&mut self, key_type: i32, key: &[u8; 16])
+    {
+        self.channel.set_encrypted_data_key(key_type, key)
+    }
+
+    /// start writing every received netmessage to a capture file
+    pub fn start_capture(&mut self, path: &Path) -> Result<()>
+    {
+        self.channel.start_capture(path)
+    }
+
+    /// stop capturing netmessages and close the capture file
+    pub fn stop_capture(&mut self) -> Result<()>
+    {
+        self.channel.stop_capture()
+    }
+
+    /// the address of the server this channel is connected to
+    pub fn peer_addr(&self) -> Result<SocketAddr>
+    {
+        self.channel.peer_addr()
+    }
+
+    /// the local address this channel's socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr>
+    {
+        self.channel.local_addr()
+    }
+
+    /// the host version the server reported in its challenge
+    pub fn get_host_version(&self) -> u32
+    {
+        self.channel.get_host_version()
+    }
+}
+
+impl<S> NetChannelWriter<S>
+    where S: PacketTransport
+{
+    /// send a netmessage to the server
+    pub fn write_netmessage(&mut self, message: NetMessage) -> Result<()>
+    {
+        self.channel.write_netmessage(message)
+    }
+
+    /// write a nop packet (no net messages encoded)
+    pub fn write_nop(&mut self) -> Result<()>
+    {
+        self.channel.write_nop()
+    }
+
+    /// run a console command on the server with net_StringCmd
+    pub fn send_command(&mut self, cmd: &str) -> Result<()>
+    {
+        self.channel.send_command(cmd)
+    }
+
+    /// tell the server we're leaving with net_Disconnect
+    pub fn disconnect(&mut self, reason: &str) -> Result<()>
+    {
+        self.channel.disconnect(reason)
+    }
+
+    /// whether dropping the writer sends net_Disconnect to the server, see `NetChannel::set_disconnect_on_drop`
+    pub fn set_disconnect_on_drop(&mut self, disconnect_on_drop: bool)
+    {
+        self.channel.set_disconnect_on_drop(disconnect_on_drop)
+    }
+
+    /// queue a netmessage to be sent with the next flush, rather than in its own datagram
+    pub fn queue_message(&mut self, message: NetMessage) -> Result<()>
+    {
+        self.channel.queue_message(message)
+    }
+
+    /// send a netmessage reliably over the message stream, see `NetChannel::send_reliable`
+    pub fn send_reliable(&mut self, message: NetMessage) -> Result<()>
+    {
+        self.channel.send_reliable(message)
+    }
+
+    /// whether the server has received everything sent reliably, see `NetChannel::is_reliable_idle`
+    pub fn is_reliable_idle(&self) -> bool
+    {
+        self.channel.is_reliable_idle()
+    }
+
+    /// keep reliable data moving, see `NetChannel::tick`
+    pub fn tick(&mut self) -> Result<bool>
+    {
+        self.channel.tick()
+    }
+
+    /// skip sending on this tick, see `NetChannel::choke`
+    pub fn choke(&mut self)
+    {
+        self.channel.choke()
+    }
+
+    /// send every queued netmessage together in one datagram
+    pub fn flush(&mut self) -> Result<()>
+    {
+        self.channel.flush()
+    }
+
+    /// the voice codec settings the server announced, as received by the reader
+    pub fn voice_settings(&self) -> Option<VoiceSettings>
+    {
+        self.channel.voice_settings()
+    }
+
+    /// encode PCM voice with the server's codec and send it, see `NetChannel::send_voice`
+    pub fn send_voice(&mut self, pcm: &[i16]) -> Result<()>
+    {
+        self.channel.send_voice(pcm)
+    }
+
+    /// send already encoded voice, see `NetChannel::send_voice_data`
+    pub fn send_voice_data(&mut self, data: &[u8]) -> Result<()>
+    {
+        self.channel.send_voice_data(data)
+    }
+
+    /// the address of the server this channel is connected to
+    pub fn peer_addr(&self) -> Result<SocketAddr>
+    {
+        self.channel.peer_addr()
+    }
+
+    /// the local address this channel's socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr>
+    {
+        self.channel.local_addr()
+    }
+
+    /// the host version the server reported in its challenge
+    pub fn get_host_version(&self) -> u32
+    {
+        self.channel.get_host_version()
+    }
+}
+
+/// Dropping a channel frees our slot on the server straight away instead of leaving it to time out
+impl<S> Drop for NetChannel<S>
+    where S: PacketTransport
+{
+    fn drop(&mut self)
+    {
+        if !self.disconnect_on_drop || self.disconnected.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // best effort, there's nothing to be done about a failed send while tearing down
+        if let Err(e) = self.disconnect("Disconnect by user.") {
+            trace!("Failed to send disconnect on drop: {}", e);
+        }
+    }
+}
+
+/// The channel's socket can be registered with an external poller (epoll, mio, ...) so that
+/// `read_data` is only called once the socket is readable
+#[cfg(unix)]
+impl<S> std::os::unix::io::AsRawFd for NetChannel<S>
+    where S: PacketTransport + std::os::unix::io::AsRawFd
+{
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd
+    {
+        self.wrapper.borrow().socket.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl<S> std::os::unix::io::AsRawFd for NetChannelReader<S>
+    where S: PacketTransport + std::os::unix::io::AsRawFd
+{
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd
+    {
+        self.channel.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<S> std::os::windows::io::AsRawSocket for NetChannel<S>
+    where S: PacketTransport + std::os::windows::io::AsRawSocket
+{
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket
+    {
+        self.wrapper.borrow().socket.as_raw_socket()
+    }
+}
+
+#[cfg(windows)]
+impl<S> std::os::windows::io::AsRawSocket for NetChannelReader<S>
+    where S: PacketTransport + std::os::windows::io::AsRawSocket
+{
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket
+    {
+        self.channel.as_raw_socket()
+    }
+}
+
+#[test]
+fn test_truncated_reliable_header() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // sequence, ack, flags (reliable), checksum, reliable state... and then nothing
+    let packet: [u8; 12] = [1, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0];
+
+    let err = channel.parse_datagram(&packet).err().expect("truncated datagram should not parse");
+
+    // should be reported as a protocol error rather than a raw i/o error
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
+
+#[test]
+fn test_reliable_state_subchannel_index() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // sequence, ack, flags (reliable), checksum, reliable state, then subchannel 7 with
+    // neither stream updated
+    let packet: [u8; 13] = [1, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0, 0b0000_0111];
+
+    channel.parse_datagram(&packet).unwrap();
+
+    // the reliable state bit is selected by the subchannel index from the wire
+    assert_eq!(channel.sequence.reliable_state.load(Ordering::SeqCst), 1 << 7);
+}
+
+#[test]
+fn test_netchannel_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<NetChannel>();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+    channel.set_print_handler(|_text| {});
+
+    // drive the channel from another thread
+    let handle = std::thread::spawn(move || {
+        channel.out_sequence
+    });
+
+    assert_eq!(handle.join().unwrap(), 1);
+}
+
+#[test]
+fn test_netchannel_split() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let (reader, writer) = channel.split().unwrap();
+
+    // sequence state received by the reader is visible to the writer
+    reader.channel.sequence.in_sequence.store(5, Ordering::SeqCst);
+    assert_eq!(writer.channel.sequence.in_sequence.load(Ordering::SeqCst), 5);
+
+    // both halves can live on their own threads
+    let handle = std::thread::spawn(move || writer.channel.out_sequence);
+    assert_eq!(handle.join().unwrap(), 1);
+}
+
+#[test]
+fn test_split_keeps_pending_sends() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    // a queued message and a reliable one not yet sent when the channel is split
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(9);
+    client.queue_message(NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32)).unwrap();
+
+    let mut string_cmd = CNETMsg_StringCmd::new();
+    string_cmd.set_command(String::from("status"));
+    client.send_reliable(NetMessage::from_proto(Box::new(string_cmd), NET_Messages::net_StringCmd as i32)).unwrap();
+
+    let (reader, mut writer) = client.split().unwrap();
+    assert!(reader.channel.send_queue.is_empty());
+    assert!(reader.channel.is_reliable_idle());
+    assert!(!writer.is_reliable_idle());
+
+    // both go out with the writer's next datagram
+    writer.flush().unwrap();
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+    server_transport.incoming.lock().unwrap().push_back(sent);
+
+    let datagram = server.read_data().unwrap();
+    let messages = datagram.get_messages().unwrap();
+    assert!(messages.iter().any(|message| message.downcast::<CNETMsg_Tick>().map_or(false, |tick| tick.get_tick() == 9)));
+    assert!(messages.iter().any(|message| message.downcast::<CNETMsg_StringCmd>().map_or(false, |cmd| cmd.get_command() == "status")));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_recv_connection_refused() {
+    // find a port that nothing is listening on
+    let closed_addr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(closed_addr).unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+    // the icmp port unreachable from this send is reported on the next recv
+    let mut wrapper = BufUdp::new(socket);
+    wrapper.send_raw(&[0xFF, 0xFF, 0xFF, 0xFF]).unwrap();
+
+    let err = wrapper.recv_message().err().expect("recv from a closed port should fail");
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::ConnectionClosed)));
+}
+
+#[test]
+fn test_handshake_deadline() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(server.local_addr().unwrap()).unwrap();
+    let mut channel = ConnectionlessChannel::new(socket).unwrap();
+
+    // server never responds, so the read should give up once the deadline passes
+    channel.set_deadline(Some(Instant::now() + std::time::Duration::from_millis(50))).unwrap();
+    let err = channel.recv_packet_during::<super::packets::S2cChallenge>("challenge")
+        .err().expect("recv with no response should time out");
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::HandshakeTimeout("challenge"))));
+
+    // once passed, later phases fail immediately
+    let err = channel.check_deadline("reservation").err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::HandshakeTimeout("reservation"))));
+}
+
+#[test]
+fn test_send_connectionless() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(server.local_addr().unwrap()).unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+    channel.set_disconnect_on_drop(false);
+
+    channel.send_connectionless(super::packets::A2sInfo::default().into()).unwrap();
+
+    // the packet arrives with the connectionless header, unencrypted
+    let mut buf = [0u8; 64];
+    let len = server.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..5], &[0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::A2S_INFO as u8]);
+    assert!(len > 5);
+}
+
+#[test]
+fn test_read_connectionless_reply() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let handler_received = received.clone();
+    client.set_connectionless_handler(move |packet_type, values| {
+        handler_received.lock().unwrap().push((packet_type, values.to_vec()));
+    });
+
+    // the server answers a ping in between two netchannel datagrams
+    server.write_nop().unwrap();
+    server.send_connectionless(A2aAck {}.into()).unwrap();
+    server.write_nop().unwrap();
+
+    for datagram in server_transport.sent.lock().unwrap().drain(..) {
+        client_transport.incoming.lock().unwrap().push_back(datagram);
     }
-}
\ No newline at end of file
+
+    // the reply goes to the handler, and reading carries on with the next datagram
+    assert_eq!(client.read_data().unwrap().header.sequence_in, 1);
+    assert!(received.lock().unwrap().is_empty());
+    assert_eq!(client.read_data().unwrap().header.sequence_in, 2);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0, ConnectionlessPacketType::A2A_ACK);
+}
+
+#[test]
+fn test_datagram_serialize_roundtrip() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(1234);
+
+    let mut datagram = NetDatagram::new(3, 7, 0, 0, 0, 0);
+    datagram.add_message(NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32));
+
+    let mut wire = datagram.serialize(&channel).unwrap();
+
+    // the serialized datagram decrypts and parses back to the same contents
+    let packet = channel.decrypt_packet(&mut wire).unwrap().to_vec();
+    let parsed = channel.parse_datagram(&packet).unwrap().unwrap();
+
+    assert_eq!(parsed.header.sequence_in, 7);
+    assert_eq!(parsed.header.sequence_ack, 3);
+
+    let messages = parsed.get_messages().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 1234);
+}
+
+#[test]
+fn test_set_convar_tracking() {
+    use crate::source::protos::{CMsg_CVars, CMsg_CVars_CVar, NET_Messages};
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let mut cvar = CMsg_CVars_CVar::new();
+    cvar.set_name("mp_roundtime".to_string());
+    cvar.set_value("5".to_string());
+
+    let mut convars = CMsg_CVars::new();
+    convars.cvars.push(cvar);
+
+    let mut set_convar = CNETMsg_SetConVar::new();
+    set_convar.set_convars(convars);
+
+    let mut datagram = NetDatagram::new(0, 1, 0, 0, 0, 0);
+    datagram.add_message(NetMessage::from_proto(Box::new(set_convar), NET_Messages::net_SetConVar as i32));
+    assert!(channel.process_messages(&datagram).is_none());
+
+    assert_eq!(channel.get_convar("mp_roundtime"), Some("5"));
+    assert_eq!(channel.get_convar("mp_freezetime"), None);
+}
+
+#[test]
+fn test_classify_datagram() {
+    // markers are little endian on the raw, unencrypted bytes
+    assert_eq!(classify_datagram(&[0xFE, 0xFF, 0xFF, 0xFF, 0x00]).unwrap(), DatagramKind::Split);
+    assert_eq!(classify_datagram(&[0xFF, 0xFF, 0xFF, 0xFF, 0x49]).unwrap(), DatagramKind::Connectionless);
+
+    // the compressed marker is inside the encryption, so it's just netchannel data here
+    assert_eq!(classify_datagram(&[0xFD, 0xFF, 0xFF, 0xFF, 0x00]).unwrap(), DatagramKind::Netchannel);
+    assert_eq!(classify_datagram(&[0xFF, 0xFF, 0xFF, 0xFE, 0x00]).unwrap(), DatagramKind::Netchannel);
+
+    assert!(classify_datagram(&[0xFF, 0xFF]).is_err());
+}
+
+#[test]
+fn test_recv_oversized_datagram() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    server.connect(socket.local_addr().unwrap()).unwrap();
+
+    let mut wrapper = BufUdp::with_max_payload(socket, 16);
+
+    // exactly the max size is fine
+    server.send(&[0xAB; 16]).unwrap();
+    assert_eq!(wrapper.recv_message().unwrap().len(), 16);
+
+    // one byte over is rejected
+    server.send(&[0xAB; 17]).unwrap();
+    let err = wrapper.recv_message().err().expect("oversized datagram should be rejected");
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
+
+#[test]
+fn test_encrypted_data_messages() {
+    use crate::source::protos::{NET_Messages, SVC_Messages};
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let key = [0x11; 16];
+    channel.set_encrypted_data_key(2, &key);
+
+    // a net_Tick encrypted the same way as a datagram
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(99);
+
+    let mut inner: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32).encode_to_buffer(&mut inner).unwrap();
+
+    let mut encrypted = CSVCMsg_EncryptedData::new();
+    encrypted.set_key_type(2);
+    {
+        let key_channel = NetChannel::from_parts(
+            BufUdp::new(UdpSocket::bind("127.0.0.1:0").unwrap()),
+            Arc::new(IceEncryption::new(2, &key)),
+            channel.sequence.clone(),
+        );
+        encrypted.set_encrypted(key_channel.encrypt_packet(&mut inner).unwrap().clone());
+    }
+
+    let mut outer: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(encrypted), SVC_Messages::svc_EncryptedData as i32).encode_to_buffer(&mut outer).unwrap();
+
+    let reader = BitReader::endian(std::io::Cursor::new(outer.as_slice()), LittleEndian);
+    let messages = channel.read_messages(reader, &mut DecodeStats::default()).unwrap();
+
+    // the encrypted message is followed by the messages that were inside of it
+    assert_eq!(messages.len(), 2);
+    assert!(messages[0].downcast::<CSVCMsg_EncryptedData>().is_some());
+    assert_eq!(messages[1].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 99);
+}
+
+/// in-memory transport which hands out queued datagrams and records everything sent
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct MockTransport
+{
+    incoming: Arc<std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>>,
+    sent: Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl PacketTransport for MockTransport
+{
+    fn send(&self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        self.sent.lock().unwrap().push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        let datagram = self.incoming.lock().unwrap().pop_front()
+            .ok_or(std::io::Error::from(std::io::ErrorKind::WouldBlock))?;
+
+        buf[..datagram.len()].copy_from_slice(&datagram);
+        Ok(datagram.len())
+    }
+
+    fn peer_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        Ok(SocketAddr::from(([127, 0, 0, 1], 27015)))
+    }
+
+    fn set_read_timeout(&self, _timeout: Option<std::time::Duration>) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> std::io::Result<Self>
+    {
+        Ok(self.clone())
+    }
+}
+
+#[test]
+fn test_mock_transport_roundtrip() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(42);
+    client.write_netmessage(NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32)).unwrap();
+
+    // hand the encrypted datagram the client sent over to the server
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+    server_transport.incoming.lock().unwrap().push_back(sent);
+    assert_eq!(server.current_tick(), None);
+
+    let datagram = server.read_data().unwrap();
+    assert_eq!(datagram.header.sequence_in, 1);
+    assert_eq!(server.current_tick(), Some(42));
+}
+
+#[test]
+fn test_send_reliable() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+
+    let mut connectionless = ConnectionlessChannel::new(client_transport.clone()).unwrap();
+    connectionless.set_compression(CompressionMode::Above(256));
+
+    let mut client = NetChannel::upgrade(connectionless, 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    // a command long enough to take a few datagrams even once compressed
+    let command: String = (0..100000).map(|i| (b'a' + (i * 7 % 26) as u8) as char).collect();
+    let mut string_cmd = CNETMsg_StringCmd::new();
+    string_cmd.set_command(command.clone());
+    client.send_reliable(NetMessage::from_proto(Box::new(string_cmd), NET_Messages::net_StringCmd as i32)).unwrap();
+    assert!(!client.is_reliable_idle());
+
+    let mut received = Vec::new();
+    let mut lost_one = false;
+    for _ in 0..64 {
+        if client.is_reliable_idle() {
+            break;
+        }
+
+        client.write_nop().unwrap();
+        let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+
+        // the second datagram, and the second batch of reliable data in it, never arrives
+        if client.out_sequence == 3 && !lost_one {
+            lost_one = true;
+        } else {
+            server_transport.incoming.lock().unwrap().push_back(sent);
+            let datagram = server.read_data().unwrap();
+            if let Some(messages) = datagram.get_messages() {
+                received.extend(messages.iter().filter_map(|message| message.downcast::<CNETMsg_StringCmd>()).map(|cmd| cmd.get_command().to_string()));
+            }
+        }
+
+        // the server's reply tells the client what it has received
+        server.write_nop().unwrap();
+        let reply = server_transport.sent.lock().unwrap().pop().unwrap();
+        client_transport.incoming.lock().unwrap().push_back(reply);
+        client.read_data().unwrap();
+    }
+
+    assert!(lost_one);
+    assert!(client.is_reliable_idle());
+    assert_eq!(received, vec![command]);
+}
+
+#[test]
+fn test_tick_retransmit() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    // nothing to send, nothing sent
+    let start = Instant::now();
+    assert!(!client.tick_at(start).unwrap());
+
+    let mut string_cmd = CNETMsg_StringCmd::new();
+    string_cmd.set_command(String::from("status"));
+    client.send_reliable(NetMessage::from_proto(Box::new(string_cmd), NET_Messages::net_StringCmd as i32)).unwrap();
+
+    // the tick sends it, but it's lost on the way
+    assert!(client.tick_at(start).unwrap());
+    client_transport.sent.lock().unwrap().clear();
+
+    // nothing more is sent until the retransmit timeout has passed
+    assert!(!client.tick_at(start).unwrap());
+    let late = start + Duration::from_secs(2);
+    assert!(client.tick_at(late).unwrap());
+    assert!(!client.tick_at(late).unwrap());
+
+    // the server gets the datagram sent after the timeout, without the reliable data
+    let relay = |from: &MockTransport, to: &MockTransport, channel: &mut NetChannel<MockTransport>| {
+        let sent = from.sent.lock().unwrap().pop().unwrap();
+        to.incoming.lock().unwrap().push_back(sent);
+        channel.read_data().unwrap()
+    };
+    assert!(relay(&client_transport, &server_transport, &mut server).get_messages().map_or(true, |messages| messages.is_empty()));
+
+    // its ack says the batch never arrived, so the next tick sends it again
+    server.write_nop().unwrap();
+    relay(&server_transport, &client_transport, &mut client);
+    assert!(client.tick_at(late).unwrap());
+
+    let datagram = relay(&client_transport, &server_transport, &mut server);
+    let messages = datagram.get_messages().unwrap();
+    assert_eq!(messages[0].downcast::<CNETMsg_StringCmd>().unwrap().get_command(), "status");
+
+    // which is acknowledged this time
+    server.write_nop().unwrap();
+    relay(&server_transport, &client_transport, &mut client);
+    assert!(!client.tick_at(late).unwrap());
+    assert!(client.is_reliable_idle());
+}
+
+#[test]
+fn test_recv_packet_trailing_bytes() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    // S2C_CONNECTION with a few unknown bytes after the connection string
+    let mut datagram = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2C_CONNECTION as u8];
+    datagram.extend_from_slice(b"0000000000\0");
+    datagram.extend_from_slice(&[1, 2, 3]);
+    transport.incoming.lock().unwrap().push_back(datagram);
+
+    let (_packet, trailing) = channel.recv_packet_type_with_trailing::<super::packets::S2cConnection>().unwrap();
+    assert_eq!(trailing, [1, 2, 3]);
+}
+
+#[test]
+fn test_netmessage_capture() {
+    use crate::source::capture::CaptureReader;
+
+    let path = std::env::temp_dir().join(format!("se-client-netchannel-capture-{}.bin", std::process::id()));
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+    channel.start_capture(&path).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(7);
+
+    let mut encoded: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32).encode_to_buffer(&mut encoded).unwrap();
+
+    let reader = BitReader::endian(std::io::Cursor::new(encoded.as_slice()), LittleEndian);
+    channel.read_messages(reader, &mut DecodeStats::default()).unwrap();
+    channel.stop_capture().unwrap();
+
+    // the captured message decodes back to what was received
+    let mut capture = CaptureReader::open(&path).unwrap();
+    let message = capture.next_record().unwrap().unwrap().bind().unwrap();
+    assert_eq!(message.downcast::<CNETMsg_Tick>().unwrap().get_tick(), 7);
+    assert!(capture.next_record().unwrap().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_decode_buffer_returned_on_error() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // a net_Tick which claims to be longer than the payload it's in
+    let payload = [NET_Messages::net_Tick as u8, 0x40, 0x01];
+    let reader = BitReader::endian(std::io::Cursor::new(&payload[..]), LittleEndian);
+    assert!(channel.read_messages(reader, &mut DecodeStats::default()).is_err());
+
+    // the decode buffer still made it back to the pool
+    assert!(channel.buffer_pool.borrow().retained_capacity() > 0);
+}
+
+#[test]
+fn test_send_compressed_connectionless() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+    channel.set_compression(CompressionMode::Above(16));
+
+    // a long, repetitive player name compresses well
+    let challenge = super::packets::challenge_for_cookie(1);
+    let mut connect = super::packets::C2sConnect::new(&challenge, super::packets::SteamAuthInfo::new(0, vec![0u8; 64]));
+    connect.player_name = "a".repeat(200);
+    channel.send_packet(connect.into()).unwrap();
+
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    assert_eq!(&sent[..4], &NET_HEADER_FLAG_COMPRESSEDPACKET.to_le_bytes());
+
+    // decompresses back to the plain connectionless packet, which is larger
+    let packet = Lzss::decode(&sent[4..]).unwrap();
+    assert!(sent.len() < packet.len());
+    assert_eq!(&packet[..5], &[0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::C2S_CONNECT as u8]);
+}
+
+#[test]
+fn test_connect_socket() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    // binds where it is asked to and talks to the server
+    let channel = ConnectionlessChannel::connect(server_addr, Some("127.0.0.1:0".parse().unwrap())).unwrap();
+    assert_eq!(channel.peer_addr().unwrap(), server_addr);
+
+    // an unconnected socket is refused up front
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    assert!(ConnectionlessChannel::from_socket(socket.try_clone().unwrap()).is_err());
+
+    socket.connect(server_addr).unwrap();
+    assert!(ConnectionlessChannel::from_socket(socket).is_ok());
+}
+
+#[test]
+fn test_send_oversized_connectionless() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    let challenge = super::packets::challenge_for_cookie(1);
+
+    // a ticket that pushes the packet past a single datagram is refused rather than sent
+    let connect = super::packets::C2sConnect::new(&challenge, super::packets::SteamAuthInfo::new(0, vec![0u8; 2000]));
+    assert!(connect.serialized_size().unwrap() > NET_MAX_ROUTABLE_PAYLOAD);
+    assert!(channel.send_packet(connect.into()).is_err());
+    assert!(transport.sent.lock().unwrap().is_empty());
+
+    // unless compression gets it small enough
+    channel.set_compression(CompressionMode::Above(NET_MAX_ROUTABLE_PAYLOAD));
+    let connect = super::packets::C2sConnect::new(&challenge, super::packets::SteamAuthInfo::new(0, vec![0u8; 2000]));
+    channel.send_packet(connect.into()).unwrap();
+    assert!(transport.sent.lock().unwrap().pop().unwrap().len() <= NET_MAX_ROUTABLE_PAYLOAD);
+}
+
+#[test]
+fn test_encryption_key() {
+    // every byte of the host version lands somewhere different in the key
+    assert_eq!(NetChannel::<UdpSocket>::get_encryption_key(0x12345678), [
+        b'C', b'S', b'G', b'O',
+        0x78, 0x56, 0x34, 0x12,
+        0x9E, 0x15, 0x8D, 0x04,
+        0x67, 0x45, 0x23, 0x01,
+    ]);
+
+    // a real host version
+    assert_eq!(NetChannel::<UdpSocket>::get_encryption_key(13765), [
+        b'C', b'S', b'G', b'O',
+        0xC5, 0x35, 0x00, 0x00,
+        0x71, 0x0D, 0x00, 0x00,
+        0x5C, 0x03, 0x00, 0x00,
+    ]);
+}
+
+#[test]
+fn test_read_messages_iter() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let mut payload: Vec<u8> = Vec::new();
+    for tick_num in 1..=3 {
+        let mut tick = CNETMsg_Tick::new();
+        tick.set_tick(tick_num);
+        let mut encoded: Vec<u8> = Vec::new();
+        NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32).encode_to_buffer(&mut encoded).unwrap();
+        payload.extend_from_slice(&encoded);
+    }
+
+    // stopping at the first message leaves the rest undecoded
+    let mut messages = channel.read_messages_iter(&payload);
+    let first = messages.next().unwrap().unwrap();
+    assert_eq!(first.downcast::<CNETMsg_Tick>().unwrap().get_tick(), 1);
+    assert_eq!(messages.stats().decoded, 1);
+
+    let rest: Vec<NetMessage> = messages.collect::<Result<_>>().unwrap();
+    assert_eq!(rest.len(), 2);
+
+    // a payload cut off in the middle of a message ends with an error
+    let mut messages = channel.read_messages_iter(&payload[..payload.len() - 1]);
+    assert!(messages.next().unwrap().is_ok());
+    assert!(messages.next().unwrap().is_ok());
+    assert!(messages.next().unwrap().is_err());
+    assert!(messages.next().is_none());
+
+    // a huge declared size in front of a couple of bytes is refused before anything is allocated
+    let forged: [u8; 8] = [NET_Messages::net_Tick as u8, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x08, 0x01];
+    let mut messages = channel.read_messages_iter(&forged);
+    let err = messages.next().unwrap().err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
+
+#[test]
+fn test_register_message() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(5);
+    let mut payload: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(tick), 200).encode_to_buffer(&mut payload).unwrap();
+
+    // unknown until registered
+    let mut stats = DecodeStats::default();
+    assert!(channel.read_messages(BitReader::endian(std::io::Cursor::new(payload.as_slice()), LittleEndian), &mut stats).unwrap().is_empty());
+    assert_eq!(stats.unknown, 1);
+
+    channel.register_message(200, |buffer| Ok(Box::new(crate::protoutil::deserialize::<CNETMsg_Tick>(buffer)?)));
+    let messages: Vec<NetMessage> = channel.read_messages_iter(&payload).collect::<Result<_>>().unwrap();
+    assert_eq!(messages[0].get_id(), 200);
+    assert_eq!(messages[0].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 5);
+}
+
+#[test]
+fn test_decode_stats() {
+    use crate::source::protos::NET_Messages;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(1);
+
+    let mut data: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32).encode_to_buffer(&mut data).unwrap();
+
+    // an id nothing knows about, with a single byte of data
+    data.extend_from_slice(&[0x7F, 0x01, 0x00]);
+
+    // a net_Tick with a truncated varint field, which fails to parse
+    data.extend_from_slice(&[NET_Messages::net_Tick as u8, 0x02, 0x08, 0x80]);
+
+    let mut stats = DecodeStats::default();
+    let reader = BitReader::endian(std::io::Cursor::new(data.as_slice()), LittleEndian);
+    let messages = channel.read_messages(reader, &mut stats).unwrap();
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(stats, DecodeStats { decoded: 1, unknown: 1, failed: 1 });
+}
+
+#[cfg(test)]
+fn info_challenge_reply(challenge: u32) -> Vec<u8>
+{
+    let mut reply = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2C_CHALLENGE as u8];
+    reply.extend_from_slice(&challenge.to_le_bytes());
+    reply
+}
+
+#[test]
+fn test_query_info_challenge() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    // the server asks for a challenge first, then answers with its info
+    let mut info = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2A_INFO_SRC as u8, 17];
+    info.extend_from_slice(b"host\0map\0csgo\0Counter-Strike\0");
+    info.extend_from_slice(&[0xDA, 0x02, 1, 10, 0, b'd', b'l', 0, 1]);
+    info.extend_from_slice(b"1.0\0");
+
+    transport.incoming.lock().unwrap().push_back(info_challenge_reply(0x11223344));
+    transport.incoming.lock().unwrap().push_back(info);
+
+    assert!(channel.query_info().is_ok());
+
+    // the second query carries the challenge after the query string
+    let sent = transport.sent.lock().unwrap();
+    assert_eq!(sent.len(), 2);
+    assert!(sent[1].ends_with(b"Source Engine Query\0\x44\x33\x22\x11"));
+}
+
+#[test]
+fn test_query_info_challenge_retries() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    // a server that never stops asking for a challenge
+    for challenge in 0..(MAX_INFO_CHALLENGE_RETRIES as u32 + 1) {
+        transport.incoming.lock().unwrap().push_back(info_challenge_reply(challenge));
+    }
+
+    let err = channel.query_info().err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+    assert_eq!(transport.sent.lock().unwrap().len(), MAX_INFO_CHALLENGE_RETRIES + 1);
+}
+
+#[test]
+fn test_query_full_rechallenge() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    let mut info = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2A_INFO_SRC as u8, 17];
+    info.extend_from_slice(b"host\0map\0csgo\0Counter-Strike\0");
+    info.extend_from_slice(&[0xDA, 0x02, 1, 10, 0, b'd', b'l', 0, 1]);
+    info.extend_from_slice(b"1.0\0");
+
+    let mut players = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2A_PLAYER as u8, 1, 0];
+    players.extend_from_slice(b"player\0");
+    players.extend_from_slice(&7i32.to_le_bytes());
+    players.extend_from_slice(&12.5f32.to_le_bytes());
+
+    let mut rules = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2A_RULES as u8, 1, 0];
+    rules.extend_from_slice(b"mp_maxrounds\030\0");
+
+    // the server gives out a challenge for the player query, then won't take it again for the
+    // rules query and hands out a new one
+    for reply in vec![info, info_challenge_reply(0x1111), players, info_challenge_reply(0x2222), rules] {
+        transport.incoming.lock().unwrap().push_back(reply);
+    }
+
+    let (_info, players, rules) = channel.query_full().unwrap();
+    assert_eq!(players.players[0].name, "player");
+    assert_eq!(players.players[0].score, 7);
+    assert_eq!(players.players[0].duration, 12.5);
+    assert_eq!(rules.get("mp_maxrounds"), Some("30"));
+
+    // the player challenge was reused first, then the rules query was re-challenged
+    let sent = transport.sent.lock().unwrap();
+    let challenges: Vec<&[u8]> = sent[1..].iter().map(|packet| &packet[4..]).collect();
+    assert_eq!(challenges, vec![
+        &b"U\xFF\xFF\xFF\xFF"[..],
+        &b"U\x11\x11\x00\x00"[..],
+        &b"V\x11\x11\x00\x00"[..],
+        &b"V\x22\x22\x00\x00"[..],
+    ]);
+}
+
+// split a connectionless reply into pieces of up to `piece_size` bytes, the way a server does
+#[cfg(test)]
+fn split_reply(reply: &[u8], id: u32, piece_size: usize) -> Vec<Vec<u8>>
+{
+    let total = (reply.len() + piece_size - 1) / piece_size;
+
+    reply.chunks(piece_size).enumerate().map(|(number, data)| {
+        let mut piece = vec![0xFE, 0xFF, 0xFF, 0xFF];
+        piece.extend_from_slice(&id.to_le_bytes());
+        piece.extend_from_slice(&[total as u8, number as u8]);
+        piece.extend_from_slice(&(piece_size as u16).to_le_bytes());
+        piece.extend_from_slice(data);
+        piece
+    }).collect()
+}
+
+#[test]
+fn test_query_split_reply() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    let mut rules = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2A_RULES as u8, 100, 0];
+    for rule in 0..100 {
+        rules.extend_from_slice(format!("sv_rule_{}\0{}\0", rule, rule).as_bytes());
+    }
+
+    // the pieces don't have to arrive in order, and pieces of some other reply are skipped
+    let pieces = split_reply(&rules, 7, 500);
+    assert_eq!(pieces.len(), 3);
+    let stray = split_reply(&rules, 8, 500).remove(0);
+    for reply in vec![info_challenge_reply(0x1111), pieces[1].clone(), stray, pieces[2].clone(), pieces[0].clone()] {
+        transport.incoming.lock().unwrap().push_back(reply);
+    }
+
+    let (rules, _challenge) = channel.query_with_challenge::<S2aRules, _>("rules", None, |challenge| A2sRules::with_challenge(challenge).into()).unwrap();
+    assert_eq!(rules.get("sv_rule_0"), Some("0"));
+    assert_eq!(rules.get("sv_rule_99"), Some("99"));
+
+    // compressed replies are refused rather than misread
+    for piece in split_reply(&[0xFF; 600], 9 | SPLIT_COMPRESSED_FLAG, 500) {
+        transport.incoming.lock().unwrap().push_back(piece);
+    }
+    let err = channel.query_with_challenge::<S2aRules, _>("rules", Some(0x1111), |challenge| A2sRules::with_challenge(challenge).into()).err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol("compressed split replies are not supported"))));
+}
+
+#[test]
+fn test_choked_flush() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    // two messages held back over two choked ticks
+    for tick_num in 1..=2 {
+        let mut tick = CNETMsg_Tick::new();
+        tick.set_tick(tick_num);
+        client.queue_message(NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32)).unwrap();
+        client.choke();
+    }
+    assert!(client_transport.sent.lock().unwrap().is_empty());
+
+    client.flush().unwrap();
+
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+    server_transport.incoming.lock().unwrap().push_back(sent);
+
+    // both messages arrive in one datagram, which reports the choked packets
+    let datagram = server.read_data().unwrap();
+    assert_eq!(datagram.header.sequence_in, 3);
+    assert_eq!(datagram.header.flags & PACKET_CHOKED, PACKET_CHOKED);
+    assert_eq!(datagram.choked(), 2);
+    assert_eq!(datagram.get_messages().unwrap().len(), 2);
+    assert_eq!(server.current_tick(), Some(2));
+
+    // the choke count is only reported once
+    client.write_nop().unwrap();
+    assert_eq!(client.choked_num, 0);
+}
+
+#[test]
+fn test_datagram_checksum_offsets() {
+    let transport = MockTransport::default();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(transport).unwrap(), 13758).unwrap();
+
+    // the check value for CRC32 is 0xCBF43926, folded together into 16 bits
+    assert_eq!(NetChannel::<MockTransport>::datagram_checksum(b"123456789"), 0x3926 ^ 0xCBF4);
+
+    channel.write_datagram(&[0x10, 0x20, 0x30, 0x40, 0x50]).unwrap();
+
+    let wrapper = channel.wrapper.borrow();
+    let scratch = wrapper.get_scratch();
+
+    // sequence (4) + ack (4) + flags (1) + checksum (2) + reliable state (1) + payload
+    assert_eq!(scratch.len(), 4 + 4 + 1 + 2 + 1 + 5);
+    assert_eq!(&scratch[0..4], &1u32.to_le_bytes());
+    assert_eq!(scratch[8], 0);
+    assert_eq!(&scratch[12..], &[0x10, 0x20, 0x30, 0x40, 0x50]);
+
+    // the checksum at offset 9 covers everything after it, starting at the reliable state
+    let crc = crc32fast::hash(&scratch[11..]);
+    let folded = ((crc & 0xFFFF) ^ (crc >> 16)) as u16;
+    assert_eq!(&scratch[9..11], &folded.to_le_bytes());
+}
+
+#[test]
+fn test_negative_wire_size() {
+    let crypt = IceEncryption::new(2, &NetChannel::<UdpSocket>::get_encryption_key(13758));
+
+    // no garbage bytes, then a size with the high bit set
+    let mut datagram: [u8; 8] = [0x00, 0x80, 0x00, 0x00, 0x10, 0, 0, 0];
+    crypt.encrypt_buffer_inplace(&mut datagram);
+
+    let err = NetChannel::<UdpSocket>::decrypt_payload(&crypt, &mut datagram).err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
+
+#[test]
+fn test_recv_packet_timeout() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    let mut datagram = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::S2C_CONNECTION as u8];
+    datagram.extend_from_slice(b"0000000000\0");
+    transport.incoming.lock().unwrap().push_back(datagram);
+
+    let timeout = Duration::from_millis(10);
+    assert!(channel.recv_packet_timeout::<super::packets::S2cConnection>(timeout).unwrap().is_some());
+
+    // nothing else was sent, which is not an error
+    assert!(channel.recv_packet_timeout::<super::packets::S2cConnection>(timeout).unwrap().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_netchannel_raw_fd() {
+    use std::os::unix::io::AsRawFd;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let fd = socket.as_raw_fd();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // the channel hands out the fd of the socket it was created on
+    assert_eq!(channel.as_raw_fd(), fd);
+}
+
+#[test]
+fn test_encrypt_decrypt_roundtrip() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // simple lcg so the payloads are the same every run
+    let mut seed: u32 = 0x1234_5678;
+    let mut next_byte = || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 16) as u8
+    };
+
+    // cover every length mod 8, including the ones which need a full block of padding
+    for len in 1..=64usize {
+        let payload: Vec<u8> = (0..len).map(|_| next_byte()).collect();
+
+        let mut encrypted = channel.encrypt_packet(&mut payload.clone()).unwrap().clone();
+        assert_eq!(encrypted.len() % 8, 0, "payload of {} bytes not padded to a block", len);
+
+        let decrypted = channel.decrypt_packet(&mut encrypted).unwrap();
+        assert_eq!(decrypted, &payload[..], "payload of {} bytes did not roundtrip", len);
+    }
+}
+
+#[test]
+fn test_kick_reasons() {
+    assert_eq!(KickReason::from_text("VAC banned from secure server\n"), KickReason::VacBanned);
+    assert_eq!(KickReason::from_text("Server uses different class tables"), KickReason::VersionMismatch);
+    assert_eq!(KickReason::from_text("You have been banned from this server."), KickReason::Banned);
+    assert_eq!(KickReason::from_text("No Steam logon"), KickReason::NoSteamLogon);
+    assert_eq!(KickReason::from_text("Kicked by Console"), KickReason::Kicked);
+    assert_eq!(KickReason::from_text("Kicked by Console : stop camping"), KickReason::Kicked);
+    assert_eq!(KickReason::from_text("#Player_DisconnectReason_VotedOff"), KickReason::Kicked);
+    assert_eq!(KickReason::from_text("Server shutting down"), KickReason::Other);
+
+    // a reason only has to mention one of these words to be misread by a substring match
+    assert_eq!(KickReason::from_text("Map vacation_house failed to load"), KickReason::Other);
+    assert_eq!(KickReason::from_text("Server is restarting for a new version"), KickReason::Other);
+    assert_eq!(KickReason::from_text("Kicked by Consolewatcher"), KickReason::Other);
+}
+
+#[test]
+fn test_disconnect_message() {
+    use crate::source::protos::NET_Messages;
+
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    // the client's own datagrams loop straight back to it, a tick sent alongside the disconnect
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(7);
+    client.queue_message(NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32)).unwrap();
+
+    let mut disconnect = CNETMsg_Disconnect::new();
+    disconnect.set_text(String::from("No Steam logon"));
+    client.queue_message(NetMessage::from_proto(Box::new(disconnect), NET_Messages::net_Disconnect as i32)).unwrap();
+    client.flush().unwrap();
+
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+
+    let err = client.read_data().err().unwrap();
+    match err.downcast::<ChannelError>().unwrap() {
+        ChannelError::Disconnected { reason, datagram, .. } => {
+            assert_eq!(reason, KickReason::NoSteamLogon);
+
+            // the tick comes back with the disconnect
+            let messages = datagram.get_messages().unwrap();
+            assert_eq!(messages.len(), 2);
+            assert_eq!(messages[0].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 7);
+        },
+        e => panic!("expected a disconnect, got {}", e),
+    }
+}
+
+#[test]
+fn test_upgrade_with_key_version() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+
+    // the server reported version 1, but the traffic is keyed with 13758
+    let mut client = NetChannel::upgrade_with_key_version(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 1, Some(13758)).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(7);
+    client.write_netmessage(NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32)).unwrap();
+
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+    server_transport.incoming.lock().unwrap().push_back(sent);
+
+    let datagram = server.read_data().unwrap();
+    assert_eq!(datagram.get_messages().unwrap()[0].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 7);
+}
+
+#[test]
+fn test_upgrade_with_encryption_key() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let key = *b"server key 12345";
+
+    let mut client = NetChannel::upgrade_with_encryption_key(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758, &key).unwrap();
+    let mut server = NetChannel::upgrade_with_encryption_key(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758, &key).unwrap();
+    let mut default_server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+    for channel in [&mut client, &mut server, &mut default_server].iter_mut() {
+        channel.set_disconnect_on_drop(false);
+    }
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(7);
+    client.write_netmessage(NetMessage::from_proto(Box::new(tick), crate::source::protos::NET_Messages::net_Tick as i32)).unwrap();
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+
+    // only a channel with the same key can read it
+    server_transport.incoming.lock().unwrap().push_back(sent.clone());
+    assert_eq!(server.read_data().unwrap().get_messages().unwrap()[0].downcast::<CNETMsg_Tick>().unwrap().get_tick(), 7);
+
+    server_transport.incoming.lock().unwrap().push_back(sent);
+    assert!(default_server.read_data().is_err());
+}
+
+#[test]
+fn test_netchannel_metadata() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let local = socket.local_addr().unwrap();
+    socket.connect("127.0.0.1:27015").unwrap();
+
+    let mut channel = NetChannel::upgrade_with_key_version(ConnectionlessChannel::new(socket).unwrap(), 13758, Some(1)).unwrap();
+    channel.set_disconnect_on_drop(false);
+    assert_eq!(channel.peer_addr().unwrap(), SocketAddr::from(([127, 0, 0, 1], 27015)));
+    assert_eq!(channel.local_addr().unwrap(), local);
+    assert_eq!(channel.get_host_version(), 13758);
+
+    // both halves of a split channel know where they're connected
+    let (reader, writer) = channel.split().unwrap();
+    assert_eq!(reader.peer_addr().unwrap(), writer.peer_addr().unwrap());
+    assert_eq!(writer.get_host_version(), 13758);
+}
+
+#[test]
+fn test_signon_state_tracking() {
+    use crate::source::protos::NET_Messages;
+    use crate::source::signon::SIGNONSTATE_FULL;
+
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let changes_cl = changes.clone();
+    client.set_signon_change_handler(move |state| changes_cl.lock().unwrap().push(state));
+
+    // the client's own datagrams loop straight back to it, the repeated state is not a change
+    for state in [2, 2, SIGNONSTATE_FULL].iter() {
+        let mut signon = CNETMsg_SignonState::new();
+        signon.set_signon_state(*state as u32);
+        client.write_netmessage(NetMessage::from_proto(Box::new(signon), NET_Messages::net_SignonState as i32)).unwrap();
+
+        let sent = transport.sent.lock().unwrap().pop().unwrap();
+        transport.incoming.lock().unwrap().push_back(sent);
+        client.read_data().unwrap();
+    }
+
+    assert_eq!(client.signon_state(), SIGNONSTATE_FULL);
+    assert_eq!(*changes.lock().unwrap(), vec![2, SIGNONSTATE_FULL]);
+}
+
+#[test]
+fn test_read_data_timeout() {
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    // nothing to read isn't an error
+    assert!(client.read_data_timeout(Duration::from_millis(10)).unwrap().is_none());
+
+    client.write_nop().unwrap();
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+    assert!(client.read_data_timeout(Duration::from_millis(10)).unwrap().is_some());
+}
+
+#[test]
+fn test_pause_tracking() {
+    use crate::source::protos::SVC_Messages;
+
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let changes_cl = changes.clone();
+    client.set_pause_change_handler(move |paused| changes_cl.lock().unwrap().push(paused));
+    assert!(!client.is_paused());
+
+    // loop svc_SetPause back to the client, repeating a state isn't a change
+    for paused in [true, true, false].iter() {
+        let mut set_pause = CSVCMsg_SetPause::new();
+        set_pause.set_paused(*paused);
+        client.write_netmessage(NetMessage::from_proto(Box::new(set_pause), SVC_Messages::svc_SetPause as i32)).unwrap();
+
+        let sent = transport.sent.lock().unwrap().pop().unwrap();
+        transport.incoming.lock().unwrap().push_back(sent);
+        client.read_data().unwrap();
+
+        assert_eq!(client.is_paused(), *paused);
+    }
+
+    assert_eq!(*changes.lock().unwrap(), vec![true, false]);
+}
+
+#[test]
+fn test_answer_pings() {
+    let transport = MockTransport::default();
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+
+    let ping = vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::A2A_PING as u8];
+
+    // pings are only answered when asked to
+    transport.incoming.lock().unwrap().push_back(ping.clone());
+    assert_eq!(channel.recv_any().unwrap(), (ConnectionlessPacketType::A2A_PING, Vec::new()));
+    assert!(transport.sent.lock().unwrap().is_empty());
+
+    channel.set_answer_pings(true);
+    transport.incoming.lock().unwrap().push_back(ping);
+    assert_eq!(channel.recv_any().unwrap().0, ConnectionlessPacketType::A2A_PING);
+    assert_eq!(transport.sent.lock().unwrap().pop().unwrap(), vec![0xFF, 0xFF, 0xFF, 0xFF, ConnectionlessPacketType::A2A_ACK as u8]);
+}
+
+#[test]
+fn test_file_stream_config() {
+    let file_datagram = || {
+        let mut packet: Vec<u8> = Vec::new();
+        {
+            let mut writer = BitWriter::endian(std::io::Cursor::new(&mut packet), LittleEndian);
+            writer.write_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0]).unwrap();
+
+            // subchannel 0, only the file stream updated with an uncompressed single block
+            writer.write(3, 0u8).unwrap();
+            writer.write_bit(false).unwrap();
+            writer.write_bit(true).unwrap();
+            writer.write_bit(false).unwrap();
+            writer.write_bit(false).unwrap();
+            writer.write(18, 4u32).unwrap();
+            writer.write_bytes(b"file").unwrap();
+            writer.byte_align().unwrap();
+        }
+        packet
+    };
+
+    // the file stream is off unless asked for
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(MockTransport::default()).unwrap(), 13758).unwrap();
+    assert!(!channel.streams.borrow()[SubchannelStreamType::File as usize].is_enabled());
+    assert!(channel.parse_datagram(&file_datagram()).unwrap().is_some());
+
+    // and a file which does arrive on it is dropped rather than taking the channel down
+    let config = NetChannelConfig { file_stream: true, strict_decompression: true, ..NetChannelConfig::default() };
+    let channel = NetChannel::upgrade_with_config(ConnectionlessChannel::new(MockTransport::default()).unwrap(), 13758, &config).unwrap();
+    assert!(channel.streams.borrow().iter().all(|stream| stream.is_enabled() && stream.is_strict()));
+    assert!(channel.parse_datagram(&file_datagram()).unwrap().is_some());
+    assert_eq!(channel.sequence.reliable_state.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_spurious_reliable_flag() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let channel = NetChannel::upgrade(ConnectionlessChannel::new(socket).unwrap(), 13758).unwrap();
+
+    // reliable flag with subchannel 7 and no streams updated, followed by bytes that then read
+    // as a netmessage with an id nothing knows about. that alone isn't a misparse
+    let packet: [u8; 15] = [1, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0, 0b0000_0111, 0x0F, 0];
+
+    let datagram = channel.parse_datagram(&packet).unwrap().unwrap();
+    assert_eq!(datagram.get_decode_stats().unknown, 1);
+    assert_eq!(channel.sequence.reliable_state.load(Ordering::SeqCst), 1 << 7);
+
+    // a net_Tick right after the reliable section which runs off the end of the datagram. the
+    // netmessages are dropped, but the reliable section was still received and acknowledged
+    let packet: [u8; 15] = [2, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0, 0b1000_0111, 0x00, 0b0000_1000];
+
+    let datagram = channel.parse_datagram(&packet).unwrap().unwrap();
+    assert!(datagram.get_messages().map_or(true, |messages| messages.is_empty()));
+    assert_eq!(channel.sequence.reliable_state.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_disconnect_on_drop() {
+    let transport = MockTransport::default();
+    let client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    drop(client);
+
+    // dropping sent a net_Disconnect, which loops back to a fresh channel
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    server.set_disconnect_on_drop(false);
+    let err = server.read_data().err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Disconnected { .. })));
+
+    // an explicit disconnect is only sent once
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    client.disconnect("leaving").unwrap();
+    drop(client);
+    assert_eq!(transport.sent.lock().unwrap().len(), 1);
+
+    // and nothing is sent when it's turned off
+    transport.sent.lock().unwrap().clear();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    client.set_disconnect_on_drop(false);
+    drop(client);
+    drop(server);
+    assert!(transport.sent.lock().unwrap().is_empty());
+
+    // the writer of a split channel doesn't say goodbye once the server has disconnected the reader
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    server.disconnect("kicked").unwrap();
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+    drop(server);
+
+    let client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    let (mut reader, writer) = client.split().unwrap();
+    assert!(reader.read_data().is_err());
+    drop(writer);
+    assert!(transport.sent.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_send_command() {
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    client.set_disconnect_on_drop(false);
+    client.send_command("say hello").unwrap();
+
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    server.set_disconnect_on_drop(false);
+
+    let datagram = server.read_data().unwrap();
+    let messages = datagram.get_messages().unwrap();
+    assert_eq!(messages[0].downcast::<CNETMsg_StringCmd>().unwrap().get_command(), "say hello");
+}
+
+#[test]
+fn test_parse_failed_bytes() {
+    let transport = MockTransport::default();
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    // a reliable section cut off by the end of the datagram, which decrypts fine but then
+    // fails to parse
+    let mut packet: [u8; 13] = [1, 0, 0, 0, 0, 0, 0, 0, PACKET_RELIABLE, 0, 0, 0, 0b0000_1111];
+    let encrypted = channel.encrypt_packet(&mut packet).unwrap().clone();
+    transport.incoming.lock().unwrap().push_back(encrypted);
+
+    let err = channel.read_data().err().unwrap();
+    match err.downcast_ref::<ChannelError>() {
+        Some(ChannelError::ParseFailed { bytes, source }) => {
+            assert_eq!(bytes.as_slice(), &packet[..]);
+            assert!(matches!(source.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+        },
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn test_send_voice_data() {
+    use crate::source::protos::SVC_Messages;
+
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+
+    // no way to know how to encode voice before the server says
+    assert!(client.send_voice_data(&[1, 2, 3]).is_err());
+
+    let mut voice_init = CSVCMsg_VoiceInit::new();
+    voice_init.set_codec(String::from("steam"));
+    voice_init.set_quality(5);
+    client.write_netmessage(NetMessage::from_proto(Box::new(voice_init), SVC_Messages::svc_VoiceInit as i32)).unwrap();
+
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+    client.read_data().unwrap();
+    assert_eq!(client.voice_settings().map(|settings| settings.codec), Some(String::from("steam")));
+
+    client.send_voice_data(&[1, 2, 3]).unwrap();
+    client.send_voice_data(&[4, 5]).unwrap();
+    assert_eq!(transport.sent.lock().unwrap().len(), 2);
+    assert_eq!(client.voice_sequence_bytes, 5);
+
+    // steam voice can only be passed through already encoded
+    assert!(client.send_voice(&[0; crate::source::voice::VOICE_FRAME_SAMPLES]).is_err());
+    assert_eq!(transport.sent.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_send_voice() {
+    use crate::source::protos::SVC_Messages;
+    use crate::source::voice::{OPUS_CODEC, VOICE_FRAME_SAMPLES};
+
+    let transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(transport.clone()).unwrap(), 13758).unwrap();
+    let pcm = [0i16; VOICE_FRAME_SAMPLES];
+
+    // nothing to encode with before the server says
+    assert!(client.send_voice(&pcm).is_err());
+
+    let mut voice_init = CSVCMsg_VoiceInit::new();
+    voice_init.set_codec(String::from(OPUS_CODEC));
+    voice_init.set_quality(5);
+    client.write_netmessage(NetMessage::from_proto(Box::new(voice_init), SVC_Messages::svc_VoiceInit as i32)).unwrap();
+
+    let sent = transport.sent.lock().unwrap().pop().unwrap();
+    transport.incoming.lock().unwrap().push_back(sent);
+
+    // the writer half sees the settings the reader half received
+    let (mut reader, mut writer) = client.split().unwrap();
+    reader.read_data().unwrap();
+    assert_eq!(writer.voice_settings().map(|settings| settings.codec), Some(String::from(OPUS_CODEC)));
+
+    if cfg!(feature = "opus") {
+        writer.send_voice(&pcm).unwrap();
+        assert_eq!(transport.sent.lock().unwrap().len(), 1);
+    } else {
+        assert!(writer.send_voice(&pcm).is_err());
+        assert!(transport.sent.lock().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_reset_sequences() {
+    let client_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(MockTransport::default()).unwrap(), 13758).unwrap();
+
+    for _ in 0..3 {
+        client.write_nop().unwrap();
+    }
+    for sent in client_transport.sent.lock().unwrap().drain(..) {
+        server.decode_captured(&sent).unwrap();
+    }
+    assert_eq!(client.out_sequence, 4);
+    assert_eq!(server.sequence.in_sequence.load(Ordering::SeqCst), 3);
+
+    // a message queued in the old session doesn't go out in the new one
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(3);
+    client.queue_message(NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32)).unwrap();
+
+    // after both sides reset, sequence 1 is new again rather than stale
+    client.reset_sequences();
+    server.reset_sequences();
+    assert_eq!(client.out_sequence, 1);
+    assert_eq!(server.sequence.in_sequence.load(Ordering::SeqCst), 0);
+
+    client.flush().unwrap();
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+    let datagram = server.decode_captured(&sent).unwrap().unwrap();
+    assert!(datagram.get_messages().is_none());
+    assert_eq!(server.sequence.in_sequence.load(Ordering::SeqCst), 1);
+    assert_eq!(server.packet_loss(), 0.0);
+
+    // what the channel was set up with is kept
+    assert_eq!(client.get_host_version(), 13758);
+
+    let mut channel = NetChannel::upgrade(ConnectionlessChannel::new(MockTransport::default()).unwrap(), 13758).unwrap();
+    channel.set_stream_enabled(SubchannelStreamType::File, false);
+    channel.set_strict_decompression(true);
+    channel.reset_sequences();
+
+    let streams = channel.streams.borrow();
+    assert!(streams[SubchannelStreamType::Message as usize].is_enabled());
+    assert!(!streams[SubchannelStreamType::File as usize].is_enabled());
+    assert!(streams.iter().all(|stream| stream.is_strict()));
+}
+
+#[test]
+fn test_packet_loss() {
+    let client_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(MockTransport::default()).unwrap(), 13758).unwrap();
+
+    for _ in 0..4 {
+        client.write_nop().unwrap();
+    }
+    let sent: Vec<Vec<u8>> = client_transport.sent.lock().unwrap().drain(..).collect();
+    assert_eq!(server.packet_loss(), 0.0);
+
+    // 3 goes missing
+    server.decode_captured(&sent[0]).unwrap();
+    server.decode_captured(&sent[1]).unwrap();
+    server.decode_captured(&sent[3]).unwrap();
+    assert_eq!(server.packet_loss(), 0.25);
+
+    // then turns up late, which is a reorder and not loss. it's dropped rather than an error
+    assert!(server.decode_captured(&sent[2]).unwrap().is_none());
+    assert_eq!(server.packet_loss(), 0.0);
+
+    // duplicates don't change anything either
+    assert!(server.decode_captured(&sent[3]).unwrap().is_none());
+    assert_eq!(server.packet_loss(), 0.0);
+    assert_eq!(server.sequence.in_sequence.load(Ordering::SeqCst), 4);
+
+    // old losses fall out of the window
+    let mut window = PacketLossWindow::default();
+    window.observe(1, 0);
+    window.observe(3, 0);
+    assert_eq!(window.loss(), 1.0 / 3.0);
+    for sequence in 4..(4 + PACKET_LOSS_WINDOW as u32) {
+        window.observe(sequence, 0);
+    }
+    assert_eq!(window.loss(), 0.0);
+
+    // choked packets skip sequence numbers without being lost
+    let mut window = PacketLossWindow::default();
+    window.observe(1, 0);
+    window.observe(4, 2);
+    assert_eq!(window.loss(), 0.0);
+    window.observe(8, 2);
+    assert_eq!(window.loss(), 1.0 / 8.0);
+}
+
+#[test]
+fn test_read_data_skips_stale() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    for _ in 0..3 {
+        client.write_nop().unwrap();
+    }
+    let sent: Vec<Vec<u8>> = client_transport.sent.lock().unwrap().drain(..).collect();
+
+    // 1 arrives again in between 2 and 3
+    for index in [0, 1, 0, 2].iter() {
+        server_transport.incoming.lock().unwrap().push_back(sent[*index].clone());
+    }
+
+    // and is read past without an error
+    let sequences: Vec<u32> = (0..3).map(|_| server.read_data().unwrap().header.sequence_in).collect();
+    assert_eq!(sequences, vec![1, 2, 3]);
+    assert!(server_transport.incoming.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_read_data_drops_unexpected_datagrams() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    client.write_nop().unwrap();
+    let sent = client_transport.sent.lock().unwrap().pop().unwrap();
+
+    // a split piece and a connectionless packet ahead of a real datagram
+    {
+        let mut incoming = server_transport.incoming.lock().unwrap();
+        incoming.push_back(vec![0xFE, 0xFF, 0xFF, 0xFF, 0x00]);
+        incoming.push_back(vec![0xFF, 0xFF, 0xFF, 0xFF, b'I', 0x00]);
+        incoming.push_back(sent);
+    }
+
+    // both are dropped and the datagram after them is read
+    assert_eq!(server.read_data().unwrap().header.sequence_in, 1);
+    assert!(server_transport.incoming.lock().unwrap().is_empty());
+
+    // a connectionless packet hidden inside a compressed datagram is a protocol error
+    let mut packet = NET_HEADER_FLAG_COMPRESSEDPACKET.to_le_bytes().to_vec();
+    packet.extend_from_slice(&Lzss::encode(&[0xFF, 0xFF, 0xFF, 0xFF, b'I', 0, 0, 0, 0, 0, 0, 0]));
+    let err = server.parse_datagram(&packet).err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
+
+#[test]
+fn test_decode_captured() {
+    let client_transport = MockTransport::default();
+    let server_transport = MockTransport::default();
+    let mut client = NetChannel::upgrade(ConnectionlessChannel::new(client_transport.clone()).unwrap(), 13758).unwrap();
+    let mut server = NetChannel::upgrade(ConnectionlessChannel::new(server_transport.clone()).unwrap(), 13758).unwrap();
+
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(77);
+    client.write_netmessage(NetMessage::from_proto(Box::new(tick), NET_Messages::net_Tick as i32)).unwrap();
+
+    // the datagram as it would appear in a capture, fed straight in
+    let captured = client_transport.sent.lock().unwrap().pop().unwrap();
+    let datagram = server.decode_captured(&captured).unwrap().unwrap();
+    assert_eq!(datagram.header.sequence_in, 1);
+    assert_eq!(server.current_tick(), Some(77));
+
+    // nothing went over the socket, and other kinds of datagram are refused
+    assert!(server_transport.sent.lock().unwrap().is_empty());
+    assert!(server.decode_captured(&[0xFF, 0xFF, 0xFF, 0xFF, b'A']).is_err());
+}
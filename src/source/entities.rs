@@ -0,0 +1,108 @@
+use anyhow::Result;
+use crate::source::ChannelError;
+use crate::source::netmessages::NetMessage;
+use crate::source::protos::CSVCMsg_PacketEntities;
+
+/// most entities a server can network at once
+pub const MAX_EDICTS: i32 = 2048;
+
+/// The header of a svc_PacketEntities message, without the entity props it carries
+///
+/// This is enough to follow entity counts and the chain of deltas the server sends, the
+/// prop bitstream itself is left undecoded in the message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityPacketHeader
+{
+    /// the highest entity index in the update
+    pub max_entries: i32,
+
+    /// number of entities the update changes
+    pub updated_entries: i32,
+
+    /// the tick this update is a delta from, None for a full update
+    pub delta_from: Option<i32>,
+
+    /// whether the server wants this update stored as the new baseline
+    pub update_baseline: bool,
+
+    /// which of the two baselines this update uses
+    pub baseline: i32,
+
+    /// length of the undecoded entity bitstream in bits
+    pub data_bits: usize,
+}
+
+impl EntityPacketHeader
+{
+    /// read the header out of a svc_PacketEntities message, rejecting counts the engine would not send
+    pub fn parse(msg: &CSVCMsg_PacketEntities) -> Result<EntityPacketHeader>
+    {
+        if msg.get_max_entries() < 0 || msg.get_max_entries() > MAX_EDICTS {
+            return Err(ChannelError::Protocol("packet entities max_entries out of range").into());
+        }
+
+        if msg.get_updated_entries() < 0 || msg.get_updated_entries() > msg.get_max_entries() {
+            return Err(ChannelError::Protocol("packet entities updated_entries out of range").into());
+        }
+
+        // there are only ever two baselines
+        if msg.get_baseline() != 0 && msg.get_baseline() != 1 {
+            return Err(ChannelError::Protocol("packet entities baseline out of range").into());
+        }
+
+        let delta_from = if msg.get_is_delta() {
+            if msg.get_delta_from() < 0 {
+                return Err(ChannelError::Protocol("packet entities delta from a negative tick").into());
+            }
+            Some(msg.get_delta_from())
+        } else {
+            None
+        };
+
+        Ok(EntityPacketHeader {
+            max_entries: msg.get_max_entries(),
+            updated_entries: msg.get_updated_entries(),
+            delta_from,
+            update_baseline: msg.get_update_baseline(),
+            baseline: msg.get_baseline(),
+            data_bits: msg.get_entity_data().len() * 8,
+        })
+    }
+
+    /// read the header if this is a svc_PacketEntities message, otherwise None
+    pub fn from_message(message: &NetMessage) -> Result<Option<EntityPacketHeader>>
+    {
+        match message.downcast::<CSVCMsg_PacketEntities>() {
+            Some(entities) => Ok(Some(Self::parse(entities)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// whether this update replaces every entity rather than deltaing from an earlier tick
+    pub fn is_full_update(&self) -> bool
+    {
+        return self.delta_from.is_none();
+    }
+}
+
+#[test]
+fn test_entity_packet_header() {
+    let mut msg = CSVCMsg_PacketEntities::new();
+    msg.set_max_entries(64);
+    msg.set_updated_entries(3);
+    msg.set_entity_data(vec![0u8; 10]);
+
+    let header = EntityPacketHeader::parse(&msg).unwrap();
+    assert!(header.is_full_update());
+    assert_eq!(header.data_bits, 80);
+
+    msg.set_is_delta(true);
+    msg.set_delta_from(1200);
+    let header = EntityPacketHeader::parse(&msg).unwrap();
+    assert_eq!(header.delta_from, Some(1200));
+
+    // can't update more entities than are in the packet
+    msg.set_updated_entries(65);
+    let err = EntityPacketHeader::parse(&msg).err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+}
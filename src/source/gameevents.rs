@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use anyhow::Result;
+use crate::source::netmessages::NetMessage;
+use crate::source::protos::{CSVCMsg_GameEvent, CSVCMsg_GameEventList};
+
+/// A single value carried by a game event, typed by the event's descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEventValue
+{
+    String(String),
+    Float(f32),
+    Long(i32),
+    Short(i32),
+    Byte(i32),
+    Bool(bool),
+    Uint64(u64),
+    WString(Vec<u8>),
+}
+
+/// A svc_GameEvent with its key names resolved from the server's game event list
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameEvent
+{
+    /// the event id the server sent
+    pub id: i32,
+
+    /// the event name, e.g. "player_death"
+    pub name: String,
+
+    /// every key the event carried, in descriptor order
+    pub keys: Vec<(String, GameEventValue)>,
+}
+
+impl GameEvent
+{
+    /// look up a key's value by name
+    pub fn get(&self, key: &str) -> Option<&GameEventValue>
+    {
+        self.keys.iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// How the server described one event in svc_GameEventList
+#[derive(Debug, Clone)]
+struct GameEventDescriptor
+{
+    name: String,
+
+    /// key names and their types, events only send the values in this order
+    keys: Vec<(String, i32)>,
+}
+
+/// The game events a server can send, built from svc_GameEventList
+#[derive(Debug, Default)]
+pub struct GameEventRegistry
+{
+    descriptors: HashMap<i32, GameEventDescriptor>,
+}
+
+impl GameEventRegistry
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// replace the known events with the ones in a svc_GameEventList
+    pub fn update(&mut self, list: &CSVCMsg_GameEventList)
+    {
+        self.descriptors.clear();
+
+        for descriptor in list.get_descriptors() {
+            let keys = descriptor.get_keys().iter()
+                .map(|key| (key.get_name().to_string(), key.get_field_type()))
+                .collect();
+
+            self.descriptors.insert(descriptor.get_eventid(), GameEventDescriptor {
+                name: descriptor.get_name().to_string(),
+                keys,
+            });
+        }
+    }
+
+    /// number of events the server has described
+    pub fn len(&self) -> usize
+    {
+        return self.descriptors.len();
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        return self.descriptors.is_empty();
+    }
+
+    /// the name of an event id, if the server has described it
+    pub fn event_name(&self, id: i32) -> Option<&str>
+    {
+        self.descriptors.get(&id).map(|descriptor| descriptor.name.as_str())
+    }
+
+    /// pair the values of a svc_GameEvent up with the key names and types from its descriptor
+    pub fn resolve(&self, event: &CSVCMsg_GameEvent) -> Result<GameEvent>
+    {
+        let descriptor = self.descriptors.get(&event.get_eventid())
+            .ok_or(anyhow::anyhow!("Game event {} was never described by the server", event.get_eventid()))?;
+
+        if event.get_keys().len() != descriptor.keys.len() {
+            return Err(anyhow::anyhow!("Game event {} has {} keys, expected {}", descriptor.name, event.get_keys().len(), descriptor.keys.len()));
+        }
+
+        let mut keys = Vec::with_capacity(descriptor.keys.len());
+        for ((name, field_type), key) in descriptor.keys.iter().zip(event.get_keys().iter()) {
+            // the descriptor's type decides which of the value fields is used
+            let value = match *field_type {
+                1 => GameEventValue::String(key.get_val_string().to_string()),
+                2 => GameEventValue::Float(key.get_val_float()),
+                3 => GameEventValue::Long(key.get_val_long()),
+                4 => GameEventValue::Short(key.get_val_short()),
+                5 => GameEventValue::Byte(key.get_val_byte()),
+                6 => GameEventValue::Bool(key.get_val_bool()),
+                7 => GameEventValue::Uint64(key.get_val_uint64()),
+                8 => GameEventValue::WString(key.get_val_wstring().to_vec()),
+                other => return Err(anyhow::anyhow!("Game event {} key {} has unknown type {}", descriptor.name, name, other)),
+            };
+
+            keys.push((name.clone(), value));
+        }
+
+        Ok(GameEvent {
+            id: event.get_eventid(),
+            name: descriptor.name.clone(),
+            keys,
+        })
+    }
+}
+
+/// A running log of every game event seen in a session, with the server tick it arrived on
+#[derive(Debug, Default)]
+pub struct GameEventLog
+{
+    registry: GameEventRegistry,
+    events: Vec<(u32, GameEvent)>,
+}
+
+impl GameEventLog
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// the events the server has described so far
+    pub fn registry(&self) -> &GameEventRegistry
+    {
+        return &self.registry;
+    }
+
+    /// update the registry from a svc_GameEventList, or log a svc_GameEvent against `tick`.
+    /// any other message is ignored
+    pub fn handle_message(&mut self, message: &NetMessage, tick: u32) -> Result<()>
+    {
+        if let Some(list) = message.downcast::<CSVCMsg_GameEventList>() {
+            self.registry.update(list);
+        } else if let Some(event) = message.downcast::<CSVCMsg_GameEvent>() {
+            let event = self.registry.resolve(event)?;
+            self.events.push((tick, event));
+        }
+
+        Ok(())
+    }
+
+    /// every event logged so far, oldest first
+    pub fn events(&self) -> &[(u32, GameEvent)]
+    {
+        return &self.events;
+    }
+
+    /// take the events logged so far, leaving the log empty
+    pub fn drain(&mut self) -> std::vec::Drain<'_, (u32, GameEvent)>
+    {
+        self.events.drain(..)
+    }
+}
+
+#[test]
+fn test_game_event_log() {
+    use crate::source::protos::{CSVCMsg_GameEventList_descriptor_t, CSVCMsg_GameEventList_key_t, CSVCMsg_GameEvent_key_t, SVC_Messages};
+
+    let mut list = CSVCMsg_GameEventList::new();
+    let mut descriptor = CSVCMsg_GameEventList_descriptor_t::new();
+    descriptor.set_eventid(23);
+    descriptor.set_name(String::from("player_death"));
+    for (name, field_type) in [("userid", 4), ("weapon", 1)].iter() {
+        let mut key = CSVCMsg_GameEventList_key_t::new();
+        key.set_name(name.to_string());
+        key.set_field_type(*field_type);
+        descriptor.keys.push(key);
+    }
+    list.descriptors.push(descriptor);
+
+    let mut event = CSVCMsg_GameEvent::new();
+    event.set_eventid(23);
+    let mut userid = CSVCMsg_GameEvent_key_t::new();
+    userid.set_val_short(4);
+    let mut weapon = CSVCMsg_GameEvent_key_t::new();
+    weapon.set_val_string(String::from("ak47"));
+    event.keys.push(userid);
+    event.keys.push(weapon);
+
+    let mut log = GameEventLog::new();
+
+    // can't resolve events before the list has arrived
+    let event_msg = NetMessage::from_proto(Box::new(event), SVC_Messages::svc_GameEvent as i32);
+    assert!(log.handle_message(&event_msg, 10).is_err());
+
+    log.handle_message(&NetMessage::from_proto(Box::new(list), SVC_Messages::svc_GameEventList as i32), 10).unwrap();
+    log.handle_message(&event_msg, 12).unwrap();
+
+    let (tick, event) = &log.events()[0];
+    assert_eq!(*tick, 12);
+    assert_eq!(event.name, "player_death");
+    assert_eq!(event.get("userid"), Some(&GameEventValue::Short(4)));
+    assert_eq!(event.get("weapon"), Some(&GameEventValue::String(String::from("ak47"))));
+
+    assert_eq!(log.drain().count(), 1);
+    assert!(log.events().is_empty());
+}
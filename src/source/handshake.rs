@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use anyhow::Result;
+use crate::source::transport::PacketTransport;
+use crate::source::packets::SteamAuthInfo;
+
+// magic bytes at the start of every handshake recording, followed by the format version
+const HANDSHAKE_MAGIC: [u8; 4] = *b"SEHS";
+const HANDSHAKE_VERSION: u32 = 1;
+
+/// Which way a recorded datagram went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacketDirection
+{
+    Sent,
+    Received,
+}
+
+/// A single datagram sent or received during a handshake
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandshakePacket
+{
+    pub direction: PacketDirection,
+    pub data: Vec<u8>,
+}
+
+/// Everything needed to replay a connectionless handshake without Steam or the server
+///
+/// A recording file is laid out little endian as:
+///   [u8; 4] magic, u32 version
+///   u32 length of the server address, followed by the address as a string
+///   u64 steamid, u32 ticket length, followed by the auth ticket
+///   u64 reservation id
+///   then for each datagram: u8 direction (0 sent, 1 received), u32 length, the datagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandshakeRecording
+{
+    /// address of the server the handshake was with
+    pub peer: SocketAddr,
+
+    /// identity the client authenticated with
+    pub steamid: u64,
+    pub auth_ticket: Vec<u8>,
+
+    /// reservation the game coordinator granted for the connection
+    pub reservationid: u64,
+
+    /// every datagram in the order it was sent or received
+    pub packets: Vec<HandshakePacket>,
+}
+
+impl HandshakeRecording
+{
+    /// start an empty recording of a handshake with `peer`
+    pub fn new(peer: SocketAddr) -> Self
+    {
+        HandshakeRecording {
+            peer,
+            steamid: 0,
+            auth_ticket: Vec::new(),
+            reservationid: 0,
+            packets: Vec::new(),
+        }
+    }
+
+    /// the auth info the recorded client sent in its connect packet
+    pub fn auth_info(&self) -> SteamAuthInfo
+    {
+        SteamAuthInfo::new(self.steamid, self.auth_ticket.clone())
+    }
+
+    /// write the recording out to a file
+    pub fn save(&self, path: &Path) -> Result<()>
+    {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(&HANDSHAKE_MAGIC)?;
+        file.write_u32::<LittleEndian>(HANDSHAKE_VERSION)?;
+
+        let peer = self.peer.to_string();
+        file.write_u32::<LittleEndian>(peer.len() as u32)?;
+        file.write_all(peer.as_bytes())?;
+
+        file.write_u64::<LittleEndian>(self.steamid)?;
+        file.write_u32::<LittleEndian>(self.auth_ticket.len() as u32)?;
+        file.write_all(&self.auth_ticket)?;
+        file.write_u64::<LittleEndian>(self.reservationid)?;
+
+        for packet in self.packets.iter()
+        {
+            file.write_u8(match packet.direction {
+                PacketDirection::Sent => 0,
+                PacketDirection::Received => 1,
+            })?;
+            file.write_u32::<LittleEndian>(packet.data.len() as u32)?;
+            file.write_all(&packet.data)?;
+        }
+
+        file.flush()?;
+
+        Ok(())
+    }
+
+    /// read a recording back in from a file
+    pub fn load(path: &Path) -> Result<Self>
+    {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if magic != HANDSHAKE_MAGIC {
+            return Err(anyhow::anyhow!("Not a handshake recording"));
+        }
+
+        let version = file.read_u32::<LittleEndian>()?;
+        if version != HANDSHAKE_VERSION {
+            return Err(anyhow::anyhow!("Unsupported handshake recording version {}", version));
+        }
+
+        let peer = String::from_utf8(Self::read_block(&mut file)?)?;
+        let mut recording = HandshakeRecording::new(peer.parse()?);
+
+        recording.steamid = file.read_u64::<LittleEndian>()?;
+        recording.auth_ticket = Self::read_block(&mut file)?;
+        recording.reservationid = file.read_u64::<LittleEndian>()?;
+
+        loop
+        {
+            // a clean end of file can only happen between datagrams
+            let direction = match file.read_u8() {
+                Ok(0) => PacketDirection::Sent,
+                Ok(1) => PacketDirection::Received,
+                Ok(x) => return Err(anyhow::anyhow!("Invalid packet direction {}", x)),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            let data = Self::read_block(&mut file)?;
+            recording.packets.push(HandshakePacket { direction, data });
+        }
+
+        Ok(recording)
+    }
+
+    // read a u32 length followed by that many bytes
+    fn read_block<R: Read>(file: &mut R) -> Result<Vec<u8>>
+    {
+        let len = file.read_u32::<LittleEndian>()? as usize;
+
+        let mut data = vec![0u8; len];
+        file.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// A transport which records every datagram that passes through it to a `HandshakeRecording`
+///
+/// Wrap the socket a `ConnectionlessChannel` is created on with this, then save the recording
+/// once the handshake is done (or has failed).
+pub struct RecordingTransport<S = std::net::UdpSocket>
+{
+    inner: S,
+    recording: Arc<Mutex<HandshakeRecording>>,
+}
+
+impl<S> RecordingTransport<S>
+    where S: PacketTransport
+{
+    /// record everything sent and received over `inner`
+    pub fn new(inner: S) -> Result<Self>
+    {
+        let recording = HandshakeRecording::new(inner.peer_addr()?);
+
+        Ok(RecordingTransport {
+            inner,
+            recording: Arc::new(Mutex::new(recording)),
+        })
+    }
+
+    /// a shared handle to the recording, which keeps filling in as long as the transport is used
+    pub fn recording(&self) -> Arc<Mutex<HandshakeRecording>>
+    {
+        self.recording.clone()
+    }
+
+    fn record(&self, direction: PacketDirection, data: &[u8])
+    {
+        self.recording.lock().unwrap().packets.push(HandshakePacket { direction, data: data.to_vec() });
+    }
+}
+
+impl<S> PacketTransport for RecordingTransport<S>
+    where S: PacketTransport
+{
+    fn send(&self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        let len = self.inner.send(buf)?;
+        self.record(PacketDirection::Sent, buf);
+
+        Ok(len)
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        let len = self.inner.recv(buf)?;
+        self.record(PacketDirection::Received, &buf[..len]);
+
+        Ok(len)
+    }
+
+    fn peer_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        self.inner.peer_addr()
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn try_clone(&self) -> std::io::Result<Self>
+    {
+        Ok(RecordingTransport {
+            inner: self.inner.try_clone()?,
+            recording: self.recording.clone(),
+        })
+    }
+}
+
+/// A transport which plays back the server's side of a recorded handshake
+///
+/// Each `recv` returns the next datagram the server sent in the recording, regardless of what
+/// the client sends. Once they run out, `recv` times out. Everything the client sends is kept so
+/// it can be compared against what was recorded.
+#[derive(Clone)]
+pub struct ReplayTransport
+{
+    peer: SocketAddr,
+    responses: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    sent: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl ReplayTransport
+{
+    /// play back the datagrams received in a recording
+    pub fn new(recording: &HandshakeRecording) -> Self
+    {
+        let responses = recording.packets.iter()
+            .filter(|packet| packet.direction == PacketDirection::Received)
+            .map(|packet| packet.data.clone())
+            .collect();
+
+        ReplayTransport {
+            peer: recording.peer,
+            responses: Arc::new(Mutex::new(responses)),
+            sent: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// every datagram sent over this transport so far
+    pub fn sent(&self) -> Vec<Vec<u8>>
+    {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl PacketTransport for ReplayTransport
+{
+    fn send(&self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        self.sent.lock().unwrap().push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        let datagram = self.responses.lock().unwrap().pop_front()
+            .ok_or(std::io::Error::from(std::io::ErrorKind::TimedOut))?;
+
+        // the same as a socket would, anything that doesn't fit is cut off
+        let len = std::cmp::min(datagram.len(), buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+
+        Ok(len)
+    }
+
+    fn peer_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        Ok(self.peer)
+    }
+
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> std::io::Result<Self>
+    {
+        Ok(self.clone())
+    }
+}
+
+#[test]
+fn test_handshake_record_replay() {
+    use crate::source::ConnectionlessChannel;
+    use crate::source::packets::{A2sGetChallenge, S2cChallenge};
+
+    let path = std::env::temp_dir().join(format!("se-client-handshake-{}.bin", std::process::id()));
+
+    let mut recording = HandshakeRecording::new(SocketAddr::from(([10, 0, 0, 1], 27015)));
+    recording.steamid = 76561197960287930;
+    recording.auth_ticket = vec![1, 2, 3];
+    recording.reservationid = 0x1234;
+    recording.packets.push(HandshakePacket { direction: PacketDirection::Sent, data: vec![0xFF; 5] });
+
+    // a challenge reply with the context "connect0x00000000" and host version 13758
+    let mut challenge = vec![0xFF, 0xFF, 0xFF, 0xFF, b'A'];
+    challenge.extend_from_slice(&[1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    challenge.extend_from_slice(b"connect0x00000000\0");
+    challenge.extend_from_slice(&[0xBE, 0x35, 0, 0]);
+    challenge.extend_from_slice(b"\0");
+    challenge.extend_from_slice(&[0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0]);
+    recording.packets.push(HandshakePacket { direction: PacketDirection::Received, data: challenge });
+
+    recording.save(&path).unwrap();
+    let loaded = HandshakeRecording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, recording);
+
+    // replaying gives the channel the recorded challenge, whatever the client sends
+    let transport = ReplayTransport::new(&loaded);
+    let mut channel = ConnectionlessChannel::new(transport.clone()).unwrap();
+    channel.send_packet(A2sGetChallenge::default().into()).unwrap();
+
+    let challenge: S2cChallenge = channel.recv_packet_type().unwrap();
+    assert_eq!(challenge.host_version, 13758);
+    assert_eq!(transport.sent().len(), 1);
+
+    // and there is nothing left to receive afterwards
+    assert!(channel.recv_packet_type::<S2cChallenge>().is_err());
+}
@@ -11,6 +11,7 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SO
 // C implementation written by Written by Matthew Kwan - July 1996
 
 use std::borrow::{BorrowMut, Borrow};
+use std::convert::TryInto;
 
 #[derive(Default)]
 struct IceSubKey {
@@ -92,80 +93,48 @@ impl IceEncryption {
         return obj
     }
 
-
-    /// Encrypt 8-bytes of plaintext
+    /// Replace the encryption key, keeping the s-boxes which were already built. This is much
+    /// cheaper than creating a new object when rotating keys.
     ///
     /// # Arguments
     ///
-    /// * `ptext` - A reference to 8-bytes of plaintext to encrypt
-    /// * `ctext` - A reference to a mutable array of at least 8-bytes for ciphertext output
-    pub fn encrypt(&self, ptext: &[u8], ctext: &mut [u8])
-    {
-        let lr = self.encrypt_block_inplace_prepare(ptext);
-        self.encrypt_block_inplace(lr, ctext);
-    }
+    /// * `key` - The new encryption key. Must be the same size as the key this object was
+    /// created with (`n*8` bytes).
+    pub fn set_key(&mut self, key: &[u8]) {
+        let key_len = self.ice_key.ik_size * 8;
+        assert_eq!(key.len(), key_len, "Ice key must be exactly {} bytes in length", key_len);
 
-   /// Prepare to encrypt 8-bytes of ciphertext in-place.
-   /// Pass the return value into `encrypt_inplace` to encrypt plaintext.
-   /// Using this instead of `encrypt` allows for slice reference aliasing to encrypt a buffer
-   /// in-place.
-   ///
-   /// # Arguments
-   ///
-   /// * `ptext` - A reference to 8-bytes of plaintext to encrypt
-   fn encrypt_block_inplace_prepare(&self, ptext: &[u8]) -> (u32, u32)
-   {
-        let l = ((ptext[0] as u32) << 24)
-            | ((ptext[1] as u32) << 16)
-            | ((ptext[2] as u32) << 8) | (ptext[3] as u32);
-
-        let r = ((ptext[4] as u32) << 24)
-            | ((ptext[5] as u32) << 16)
-            | ((ptext[6] as u32) << 8) | (ptext[7] as u32);
+        IceEncryption::ice_key_set(self.ice_key.borrow_mut(), key);
+    }
 
-        return (l, r)
-   }
 
-    /// Encrypt 8-bytes of plaintext in-place.
-    /// Pass the return value from `encrypt_block_inplace_prepare` to encrypt plaintext.
-    /// Using this instead of `encrypt` allows for slice reference aliasing to encrypt a buffer
-    /// in-place.
+    /// Encrypt a single 8-byte block
     ///
     /// # Arguments
     ///
-    /// * `lr` - The result of a call to `encrypt_block_inplace_prepare`
-    fn encrypt_block_inplace(&self, lr: (u32, u32), ctext: &mut [u8])
+    /// * `ptext` - The 8 bytes of plaintext to encrypt
+    pub fn encrypt(&self, ptext: &[u8; 8]) -> [u8; 8]
     {
-        let ik = &self.ice_key;
-        let mut i: usize = 0;
-        let (mut l, mut r) = lr;
+        let mut block = *ptext;
+        self.encrypt_block(&mut block);
 
-            loop {
-            if i >= ik.ik_rounds {
-                break;
-            }
-            l ^= self.ice_f(r, ik.ik_sched[i].borrow());
-            r ^= self.ice_f(l, ik.ik_sched[i + 1].borrow());
-
-            i += 2;
-        }
-
-        i = 0;
-        loop {
-            if i >= 4 {
-                break;
-            }
+        return block
+    }
 
-            ctext[3 - i] = (r & 0xff) as u8;
-            ctext[7 - i] = (l & 0xff) as u8;
+    /// Decrypt a single 8-byte block
+    ///
+    /// # Arguments
+    ///
+    /// * `ctext` - The 8 bytes of ciphertext to decrypt
+    pub fn decrypt(&self, ctext: &[u8; 8]) -> [u8; 8]
+    {
+        let mut block = *ctext;
+        self.decrypt_block(&mut block);
 
-            r >>= 8;
-            l >>= 8;
-            i += 1;
-        }
+        return block
     }
 
-    /// Encrypt an 8-byte aligned buffer in-place.
+    /// Encrypt an 8-byte aligned buffer in-place, one block at a time.
     /// Panics if the buffer length is not divisible by 8.
     ///
     /// # Arguments
@@ -175,136 +144,83 @@ impl IceEncryption {
     {
         assert_eq!(buffer.len() % 8, 0);
 
-        let nblocks = buffer.len() / 8;
-
-        // decrypt each block
-        for i in 0..nblocks {
-            // start of this block in bytes
-            let start_pos = i*8;
-            // end of this block in bytes
-            let end_pos = (i+1)*8;
-
-            let lr;
-            {
-                // reference to the full block to decrypt
-                let block = &buffer[start_pos..end_pos];
-                lr = self.encrypt_block_inplace_prepare(block);
-            }
-
-            // scratch space to decrypt to
-            let scratch_block = &mut buffer[start_pos..end_pos];
-            self.encrypt_block_inplace(lr, scratch_block);
+        for block in buffer.chunks_exact_mut(8) {
+            self.encrypt_block(block.try_into().unwrap());
         }
     }
 
-
-    /// Decrypt 8-bytes of ciphertext
+    /// Decrypt an 8-byte aligned buffer in-place, one block at a time.
+    /// Panics if the buffer length is not divisible by 8.
     ///
     /// # Arguments
     ///
-    /// * `ctext` - A reference to 8-bytes of ciphertext to decrypt
-    /// * `ptext` - A reference to a mutable array of at least 8-bytes for plaintext output
-    pub fn decrypt(&self, ctext: &[u8], ptext: &mut [u8])
+    /// * `buffer` - The buffer to decrypt in place.
+    pub fn decrypt_buffer_inplace(&self, buffer: &mut [u8])
     {
-        let lr = self.decrypt_block_inplace_prepare(ctext);
-        self.decrypt_block_inplace(lr, ptext);
+        assert_eq!(buffer.len() % 8, 0);
+
+        for block in buffer.chunks_exact_mut(8) {
+            self.decrypt_block(block.try_into().unwrap());
+        }
     }
 
-    /// Prepare to decrypt 8-bytes of ciphertext in-place.
-    /// Pass the return value into decrypt_inplace to decrypt plaintext.
-    /// Using this instead of `decrypt` allows for slice reference aliasing to decrypt a buffer
-    /// in-place.
-    ///
-    /// # Arguments
-    ///
-    /// * `ctext` - A reference to 8-bytes of ciphertext to decrypt
-    #[inline(always)]
-    pub fn decrypt_block_inplace_prepare(&self, ctext: &[u8]) -> (u32, u32)
+    /// Split a block into the two big endian halves the rounds work on
+    fn block_halves(block: &[u8; 8]) -> (u32, u32)
     {
-        let l = ((ctext[0] as u32) << 24)
-            | ((ctext[1] as u32) << 16)
-            | ((ctext[2] as u32) << 8) | (ctext[3] as u32);
-
-        let r = ((ctext[4] as u32) << 24)
-            | ((ctext[5] as u32) << 16)
-            | ((ctext[6] as u32) << 8) | (ctext[7] as u32);
+        let l = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+        let r = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
 
         return (l, r)
     }
 
-    /// Decrypt 8-bytes of ciphertext in-place.
-    /// Pass the return value from `decrypt_block_inplace_prepare` to decrypt plaintext.
-    /// Using this instead of `decrypt` allows for slice reference aliasing to decrypt a buffer
-    /// in-place.
-    ///
-    /// # Arguments
-    ///
-    /// * `lr` - The result of a call to `decrypt_block_inplace_prepare`
-    pub fn decrypt_block_inplace(&self, lr: (u32, u32), ptext: &mut [u8])
+    /// Write the halves back out after the last round, which leaves them swapped
+    fn write_halves(block: &mut [u8; 8], l: u32, r: u32)
     {
-        let ik = &self.ice_key;
-        let mut i: isize;
-
-        let (mut l, mut r) = lr;
-
-        i = (ik.ik_rounds as isize) - 1;
-        loop {
-            if i <= 0 {
-                break;
-            }
-            l ^= self.ice_f(r, ik.ik_sched[i as usize].borrow());
-            r ^= self.ice_f(l, ik.ik_sched[(i - 1) as usize].borrow());
+        block[..4].copy_from_slice(&r.to_be_bytes());
+        block[4..].copy_from_slice(&l.to_be_bytes());
+    }
 
-            i -= 2;
-        }
+    /// Encrypt a block in-place
+    fn encrypt_block(&self, block: &mut [u8; 8])
+    {
+        let ik = &self.ice_key;
+        let (mut l, mut r) = Self::block_halves(block);
 
         let mut i: usize = 0;
         loop {
-            if i >= 4 {
+            if i >= ik.ik_rounds {
                 break;
             }
+            l ^= self.ice_f(r, ik.ik_sched[i].borrow());
+            r ^= self.ice_f(l, ik.ik_sched[i + 1].borrow());
 
-            ptext[3 - i] = (r & 0xff) as u8;
-            ptext[7 - i] = (l & 0xff) as u8;
-
-            r >>= 8;
-            l >>= 8;
-            i += 1;
+            i += 2;
         }
+
+        Self::write_halves(block, l, r);
     }
 
-    /// Decrypt an 8-byte aligned buffer in-place.
-    /// Panics if the buffer length is not divisible by 8.
-    ///
-    /// # Arguments
-    ///
-    /// * `buffer` - The buffer to decrypt in place.
-    pub fn decrypt_buffer_inplace(&self, buffer: &mut [u8])
+    /// Decrypt a block in-place
+    fn decrypt_block(&self, block: &mut [u8; 8])
     {
-        assert_eq!(buffer.len() % 8, 0);
-
-        let nblocks = buffer.len() / 8;
-
-        // decrypt each block
-        for i in 0..nblocks {
-            // start of this block in bytes
-            let start_pos = i*8;
-            // end of this block in bytes
-            let end_pos = (i+1)*8;
+        let ik = &self.ice_key;
+        let (mut l, mut r) = Self::block_halves(block);
 
-            let lr;
-            {
-                // reference to the full block to decrypt
-                let block = &buffer[start_pos..end_pos];
-                lr = self.decrypt_block_inplace_prepare(block);
+        let mut i = (ik.ik_rounds as isize) - 1;
+        loop {
+            if i <= 0 {
+                break;
             }
+            l ^= self.ice_f(r, ik.ik_sched[i as usize].borrow());
+            r ^= self.ice_f(l, ik.ik_sched[(i - 1) as usize].borrow());
 
-            // slice of the block to decrypt to
-            let target_block = &mut buffer[start_pos..end_pos];
-            self.decrypt_block_inplace(lr, target_block);
+            i -= 2;
         }
+
+        Self::write_halves(block, l, r);
     }
 
+
     fn gf_mult(mut a: u32, mut b: u32, m: u32) -> u32 {
         let mut res: u32 = 0;
 
@@ -472,35 +388,52 @@ impl IceEncryption {
 #[test]
 fn test() {
     // n=2 test
-    let plaintext = "BBBBBBBB";
+    let plaintext = b"BBBBBBBB";
     let key = "AAAAAAAAAAAAAAAA";
-    let mut ctext = [0; 8];
-    let mut ptext = [0; 8];
 
     // create the key
     let state = IceEncryption::new(2, key.as_bytes());
 
     // encrypt the plaintext
-    state.encrypt(plaintext.as_bytes(), &mut ctext);
+    let ctext = state.encrypt(plaintext);
 
     // ensure it's the proper ciphertext
     assert_eq!(ctext, [0xac, 0x87, 0x14, 0xe3, 0x22, 0x82, 0x56, 0x80]);
 
-    // decrypt the plaintext
-    state.decrypt(&ctext, &mut ptext);
-
-    // ensure it matches the original plaintext
-    assert_eq!(ptext, plaintext.as_bytes());
+    // decrypt the plaintext and ensure it matches the original
+    assert_eq!(&state.decrypt(&ctext), plaintext);
 
     // n = 8 test
     let key = "kFc8zALkEPTgTyDTerPjnf8LZr7aLFs9G9tDdUQFYZzffAYVnz2VzyuJ5RQwc6uH";
     let state = IceEncryption::new(8, key.as_bytes());
 
-    state.encrypt(plaintext.as_bytes(), &mut ctext);
+    let ctext = state.encrypt(plaintext);
 
     assert_eq!(ctext, [0xf1, 0x75, 0x76, 0xab, 0x4a, 0x61, 0x34, 0xd7]);
 
-    state.decrypt(&ctext, &mut ptext);
+    assert_eq!(&state.decrypt(&ctext), plaintext);
+}
+
+#[test]
+fn test_set_key() {
+    // start with some other key, then switch to the n=2 test key
+    let mut state = IceEncryption::new(2, "CCCCCCCCCCCCCCCC".as_bytes());
+    state.set_key("AAAAAAAAAAAAAAAA".as_bytes());
 
-    assert_eq!(ptext, plaintext.as_bytes());
-}
\ No newline at end of file
+    // same ciphertext as a fresh object created with that key
+    assert_eq!(state.encrypt(b"BBBBBBBB"), [0xac, 0x87, 0x14, 0xe3, 0x22, 0x82, 0x56, 0x80]);
+}
+
+#[test]
+fn test_buffer_inplace() {
+    let state = IceEncryption::new(2, "AAAAAAAAAAAAAAAA".as_bytes());
+
+    // a buffer is encrypted block by block
+    let mut buffer = *b"BBBBBBBBCCCCCCCC";
+    state.encrypt_buffer_inplace(&mut buffer);
+    assert_eq!(buffer[..8], state.encrypt(b"BBBBBBBB"));
+    assert_eq!(buffer[8..], state.encrypt(b"CCCCCCCC"));
+
+    state.decrypt_buffer_inplace(&mut buffer);
+    assert_eq!(&buffer, b"BBBBBBBBCCCCCCCC");
+}
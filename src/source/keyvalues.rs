@@ -0,0 +1,237 @@
+use anyhow::Result;
+use crate::source::ChannelError;
+use crate::source::bitbuf::*;
+use crate::source::netmessages::NetMessage;
+use crate::source::protos::CSVCMsg_CmdKeyValues;
+
+// node types in the binary KeyValues format
+const TYPE_NONE: u8 = 0;
+const TYPE_STRING: u8 = 1;
+const TYPE_INT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_PTR: u8 = 4;
+const TYPE_WSTRING: u8 = 5;
+const TYPE_COLOR: u8 = 6;
+const TYPE_UINT64: u8 = 7;
+const TYPE_COMPILED_INT_BYTE: u8 = 8;
+const TYPE_COMPILED_INT_0: u8 = 9;
+const TYPE_COMPILED_INT_1: u8 = 10;
+
+// ends a list of keys, the engine uses the number of types as the marker
+const TYPE_END: u8 = 11;
+
+/// deepest nesting of subkeys accepted, the engine never sends anything close to this
+const MAX_DEPTH: usize = 64;
+
+/// The value of a single KeyValues node
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyValue
+{
+    /// a node holding more keys
+    SubKeys(Vec<KeyValues>),
+    String(String),
+    Int(i32),
+    Float(f32),
+
+    /// an rgba color
+    Color([u8; 4]),
+    Uint64(u64),
+}
+
+/// A named node of a KeyValues tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValues
+{
+    pub name: String,
+    pub value: KeyValue,
+}
+
+impl KeyValues
+{
+    /// decode a tree from the binary KeyValues format, returning its root node
+    pub fn parse(data: &[u8]) -> Result<KeyValues>
+    {
+        let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(data), LittleEndian);
+
+        // the root may have peers, but the engine only ever sends a single tree
+        let mut roots = Self::read_keys(&mut reader, 0)?;
+        if roots.is_empty() {
+            return Err(ChannelError::Protocol("keyvalues without a root key").into());
+        }
+
+        Ok(roots.swap_remove(0))
+    }
+
+    /// decode the KeyValues a svc_CmdKeyValues carries
+    pub fn from_cmd(msg: &CSVCMsg_CmdKeyValues) -> Result<KeyValues>
+    {
+        Self::parse(msg.get_keyvalues())
+    }
+
+    /// decode the KeyValues if this is a svc_CmdKeyValues message, otherwise None
+    pub fn from_message(message: &NetMessage) -> Result<Option<KeyValues>>
+    {
+        match message.downcast::<CSVCMsg_CmdKeyValues>() {
+            Some(cmd) => Ok(Some(Self::from_cmd(cmd)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// read keys until the end marker of the list they are in
+    fn read_keys(reader: &mut BitBufReaderType, depth: usize) -> Result<Vec<KeyValues>>
+    {
+        if depth > MAX_DEPTH {
+            return Err(ChannelError::Protocol("keyvalues nested too deeply").into());
+        }
+
+        let mut keys = Vec::new();
+        loop {
+            let node_type = reader.read_char()?;
+            if node_type == TYPE_END {
+                break;
+            }
+
+            // names aren't always valid utf-8, they come straight from the server's files
+            let name = Self::read_string(reader)?;
+
+            let value = match node_type {
+                TYPE_NONE => KeyValue::SubKeys(Self::read_keys(reader, depth + 1)?),
+                TYPE_STRING => KeyValue::String(Self::read_string(reader)?),
+                TYPE_INT | TYPE_PTR => KeyValue::Int(reader.read_i32()?),
+                TYPE_FLOAT => KeyValue::Float(f32::from_bits(reader.read_long()?)),
+                TYPE_COLOR => {
+                    let mut color = [0u8; 4];
+                    reader.read_bytes_into(&mut color)?;
+                    KeyValue::Color(color)
+                },
+                TYPE_UINT64 => KeyValue::Uint64(reader.read_longlong()?),
+                TYPE_COMPILED_INT_BYTE => KeyValue::Int(reader.read_char()? as i32),
+                TYPE_COMPILED_INT_0 => KeyValue::Int(0),
+                TYPE_COMPILED_INT_1 => KeyValue::Int(1),
+                TYPE_WSTRING => return Err(anyhow::anyhow!("KeyValues key {} is a wide string, which can't be sent in binary", name)),
+                other => return Err(anyhow::anyhow!("KeyValues key {} has unknown type {}", name, other)),
+            };
+
+            keys.push(KeyValues { name, value });
+        }
+
+        Ok(keys)
+    }
+
+    /// read a null terminated string, replacing anything that isn't utf-8
+    fn read_string(reader: &mut BitBufReaderType) -> Result<String>
+    {
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            let byte = reader.read_char()?;
+            if byte == 0 {
+                break;
+            }
+
+            buf.push(byte);
+        }
+
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// the keys under this node, empty if it holds a value instead
+    pub fn children(&self) -> &[KeyValues]
+    {
+        match &self.value {
+            KeyValue::SubKeys(keys) => keys,
+            _ => &[],
+        }
+    }
+
+    /// find a key directly under this node. like the engine, names are case insensitive
+    pub fn get(&self, name: &str) -> Option<&KeyValues>
+    {
+        self.children().iter().find(|key| key.name.eq_ignore_ascii_case(name))
+    }
+
+    /// the string value of a key under this node
+    pub fn get_str(&self, name: &str) -> Option<&str>
+    {
+        match &self.get(name)?.value {
+            KeyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// the int value of a key under this node
+    pub fn get_int(&self, name: &str) -> Option<i32>
+    {
+        match self.get(name)?.value {
+            KeyValue::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// the float value of a key under this node
+    pub fn get_float(&self, name: &str) -> Option<f32>
+    {
+        match self.get(name)?.value {
+            KeyValue::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_parse_keyvalues() {
+    use crate::source::protos::SVC_Messages;
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+
+        writer.write_char(TYPE_NONE).unwrap();
+        writer.write_string("ClanTagChanged").unwrap();
+
+        writer.write_char(TYPE_STRING).unwrap();
+        writer.write_string("tag").unwrap();
+        writer.write_string("[se]").unwrap();
+
+        writer.write_char(TYPE_INT).unwrap();
+        writer.write_string("team").unwrap();
+        writer.write_long(-5i32 as u32).unwrap();
+
+        writer.write_char(TYPE_FLOAT).unwrap();
+        writer.write_string("scale").unwrap();
+        writer.write_long(1.5f32.to_bits()).unwrap();
+
+        writer.write_char(TYPE_NONE).unwrap();
+        writer.write_string("style").unwrap();
+        writer.write_char(TYPE_COLOR).unwrap();
+        writer.write_string("color").unwrap();
+        writer.write_bytes(&[255, 0, 0, 128]).unwrap();
+        writer.write_char(TYPE_COMPILED_INT_1).unwrap();
+        writer.write_string("bold").unwrap();
+        writer.write_char(TYPE_END).unwrap();
+
+        writer.write_char(TYPE_END).unwrap();
+        writer.write_char(TYPE_END).unwrap();
+    }
+
+    let mut cmd = CSVCMsg_CmdKeyValues::new();
+    cmd.set_keyvalues(buf.clone());
+    let message = NetMessage::from_proto(Box::new(cmd), SVC_Messages::svc_CmdKeyValues as i32);
+    let kv = KeyValues::from_message(&message).unwrap().unwrap();
+
+    assert_eq!(kv.name, "ClanTagChanged");
+    assert_eq!(kv.get_str("Tag"), Some("[se]"));
+    assert_eq!(kv.get_int("team"), Some(-5));
+    assert_eq!(kv.get_float("scale"), Some(1.5));
+
+    let style = kv.get("style").unwrap();
+    assert_eq!(style.get("color").unwrap().value, KeyValue::Color([255, 0, 0, 128]));
+    assert_eq!(style.get_int("bold"), Some(1));
+    assert!(kv.get("missing").is_none());
+
+    // a tree cut off before its end marker is an error, not a partial tree
+    assert!(KeyValues::parse(&buf[..buf.len() - 2]).is_err());
+
+    // as is one nested past any sane depth
+    let deep: Vec<u8> = std::iter::repeat(&[TYPE_NONE, b'a', 0]).take(MAX_DEPTH + 2).flatten().copied().collect();
+    assert!(KeyValues::parse(&deep).is_err());
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use std::fmt;
-use byteorder::{ReadBytesExt, LittleEndian};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
 
 #[derive(Debug)]
 pub enum LzssError
@@ -44,6 +44,106 @@ pub struct Lzss
 
 const LZSS_HEADER: u32 = (('S' as u32)<<24) | (('S' as u32)<<16) | (('Z' as u32)<<8) | ('L' as u32);
 
+// how far back a reference can point, limited by the 12 bits used for the position
+const LZSS_WINDOW_SIZE: usize = 1 << 12;
+
+// longest run a single reference can copy, limited by the 4 bits used for the count
+const LZSS_LOOKAHEAD: usize = 1 << 4;
+
+// shortest run worth encoding as a reference instead of literal bytes
+const LZSS_MIN_MATCH: usize = 3;
+
+// earlier positions are hashed by their first LZSS_MIN_MATCH bytes into this many chains
+const LZSS_HASH_BITS: u32 = 12;
+const LZSS_HASH_SIZE: usize = 1 << LZSS_HASH_BITS;
+
+// the most earlier positions compared for each match, past this a better match is given up on
+const LZSS_MAX_CHAIN: usize = 64;
+
+// marks the end of a hash chain
+const LZSS_NO_POSITION: usize = usize::MAX;
+
+// finds matches through chains of earlier positions in the window which start with the same
+// bytes, rather than comparing against every position in the window
+struct MatchFinder
+{
+    // the latest position inserted for each hash
+    head: Vec<usize>,
+
+    // for each position in the window, the position inserted before it with the same hash
+    prev: Vec<usize>,
+}
+
+impl MatchFinder
+{
+    fn new() -> Self
+    {
+        MatchFinder {
+            head: vec![LZSS_NO_POSITION; LZSS_HASH_SIZE],
+            prev: vec![LZSS_NO_POSITION; LZSS_WINDOW_SIZE],
+        }
+    }
+
+    // hash the LZSS_MIN_MATCH bytes at pos
+    fn hash(input: &[u8], pos: usize) -> usize
+    {
+        let bytes = ((input[pos] as u32) << 16) | ((input[pos + 1] as u32) << 8) | (input[pos + 2] as u32);
+        return (bytes.wrapping_mul(2654435761) >> (32 - LZSS_HASH_BITS)) as usize;
+    }
+
+    // make the bytes at pos available to later matches
+    fn insert(&mut self, input: &[u8], pos: usize)
+    {
+        // too close to the end for a match to start here
+        if pos + LZSS_MIN_MATCH > input.len() {
+            return;
+        }
+
+        let hash = MatchFinder::hash(input, pos);
+        self.prev[pos % LZSS_WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = pos;
+    }
+
+    // find the longest run of earlier bytes (within the window) matching the bytes at pos
+    // returns the start of the match and its length
+    fn find(&self, input: &[u8], pos: usize) -> (usize, usize)
+    {
+        if pos + LZSS_MIN_MATCH > input.len() {
+            return (0, 0);
+        }
+
+        let window_start = pos.saturating_sub(LZSS_WINDOW_SIZE);
+        let max_len = std::cmp::min(LZSS_LOOKAHEAD, input.len() - pos);
+
+        let mut best: (usize, usize) = (0, 0);
+
+        // chains run from the nearest position back, and a position's slot in prev is only
+        // reused once it has fallen out of the window. runs may overlap the bytes being encoded
+        let mut candidate = self.head[MatchFinder::hash(input, pos)];
+        let mut checked = 0;
+        while candidate != LZSS_NO_POSITION && candidate >= window_start && checked < LZSS_MAX_CHAIN
+        {
+            let mut len = 0;
+            while len < max_len && input[candidate + len] == input[pos + len] {
+                len += 1;
+            }
+
+            if len > best.1 {
+                best = (candidate, len);
+
+                if len == max_len {
+                    break;
+                }
+            }
+
+            candidate = self.prev[candidate % LZSS_WINDOW_SIZE];
+            checked += 1;
+        }
+
+        best
+    }
+}
+
 impl Lzss
 {
     pub fn decode(mut input: &[u8]) -> Result<Vec<u8>, LzssError>
@@ -160,4 +260,102 @@ impl Lzss
         // all good, return the output
         Ok(output)
     }
-}
\ No newline at end of file
+
+    // compress data in the same format the engine uses (and decode() reads)
+    //
+    // every 8 items are preceded by a command byte where each bit (lowest first) says whether
+    // the item is a literal byte (0) or a 2 byte reference back into the output (1)
+    pub fn encode(input: &[u8]) -> Vec<u8>
+    {
+        let mut output: Vec<u8> = Vec::with_capacity(input.len() + 16);
+
+        // header and the size to expect when decoding
+        output.write_u32::<LittleEndian>(LZSS_HEADER).unwrap();
+        output.write_u32::<LittleEndian>(input.len() as u32).unwrap();
+
+        // position of the current command byte, and how many items it covers so far
+        let mut cmd_pos: usize = 0;
+        let mut cmd_count: u32 = 0;
+
+        let mut finder = MatchFinder::new();
+
+        let mut pos: usize = 0;
+        while pos < input.len()
+        {
+            // start a new command byte every 8 items
+            if cmd_count == 0 {
+                cmd_pos = output.len();
+                output.push(0);
+            }
+            cmd_count = (cmd_count + 1) & 0x07;
+
+            let (match_pos, match_len) = finder.find(input, pos);
+
+            let advance = if match_len >= LZSS_MIN_MATCH {
+                // reference back to an earlier run of bytes
+                let position = pos - match_pos - 1;
+
+                output[cmd_pos] = (output[cmd_pos] >> 1) | 0x80;
+                output.push((position >> 4) as u8);
+                output.push((((position & 0xF) << 4) | (match_len - 1)) as u8);
+
+                match_len
+            } else {
+                // plain literal byte
+                output[cmd_pos] >>= 1;
+                output.push(input[pos]);
+
+                1
+            };
+
+            // every position passed over can be referenced by later matches
+            for skipped in pos..(pos + advance) {
+                finder.insert(input, skipped);
+            }
+            pos += advance;
+        }
+
+        // finish with an end marker, a reference with a count of 1
+        if cmd_count == 0 {
+            output.push(0x01);
+        } else {
+            output[cmd_pos] = ((output[cmd_pos] >> 1) | 0x80) >> (7 - cmd_count);
+        }
+        output.push(0);
+        output.push(0);
+
+        output
+    }
+}
+
+#[test]
+fn test_encode_roundtrip() {
+    let inputs: [&[u8]; 4] = [
+        b"",
+        b"a",
+        b"abcabcabcabcabcabcabcabcabcabcabcabc",
+        &[0u8; 5000],
+    ];
+
+    for input in inputs.iter() {
+        let encoded = Lzss::encode(input);
+        assert_eq!(Lzss::decode(&encoded).unwrap(), *input);
+    }
+
+    // repetitive data actually gets smaller
+    assert!(Lzss::encode(&[0u8; 5000]).len() < 1000);
+}
+
+#[test]
+fn test_encode_large_roundtrip() {
+    // a few MB of text-like data, with plenty of repeats both inside and outside the window
+    let mut state: u32 = 1;
+    let input: Vec<u8> = (0..4 * 1024 * 1024).map(|_| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        b"abcdefgh "[((state >> 16) % 9) as usize]
+    }).collect();
+
+    let encoded = Lzss::encode(&input);
+    assert!(encoded.len() < input.len());
+    assert_eq!(Lzss::decode(&encoded).unwrap(), input);
+}
@@ -2,10 +2,24 @@ mod subchannel;
 mod channel;
 mod packetbase;
 mod bitbuf;
+mod transport;
+mod bufpool;
 pub mod protos;
 pub mod packets;
 pub mod ice;
 pub mod lzss;
 pub mod netmessages;
+pub mod usermessages;
+pub mod capture;
+pub mod signon;
+pub mod pinger;
+pub mod stringtables;
+pub mod entities;
+pub mod handshake;
+pub mod gameevents;
+pub mod keyvalues;
+pub mod voice;
 pub use channel::*;
 pub use packetbase::*;
+pub use transport::*;
+pub use subchannel::{TransferInfo, SubchannelStreamType, OutgoingTransfer};
@@ -7,8 +7,49 @@ use ::protobuf::ProtobufEnum;
 use NET_Messages::*;
 use SVC_Messages::*;
 
+use std::collections::HashMap;
+
 type ProtoMessage = Box<dyn ::protobuf::Message>;
 
+// decodes the payload of a netmessage with a user registered id
+type MessageDecoder = Box<dyn Fn(&[u8]) -> anyhow::Result<ProtoMessage> + Send>;
+
+// netmessage ids added on top of the ones `NetMessage::bind` knows, e.g. for modded servers
+#[derive(Default)]
+pub struct NetMessageRegistry
+{
+    decoders: HashMap<i32, MessageDecoder>,
+}
+
+impl NetMessageRegistry
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    // decode messages with `id` using `decoder`, replacing any decoder already registered for
+    // it. registered ids are tried before the built-in ones, so they can also override them
+    pub fn register<F>(&mut self, id: i32, decoder: F)
+        where F: Fn(&[u8]) -> anyhow::Result<ProtoMessage> + Send + 'static
+    {
+        self.decoders.insert(id, Box::new(decoder));
+    }
+
+    // decode messages with `id` as the proto message M
+    pub fn register_proto<M>(&mut self, id: i32)
+        where M: ::protobuf::Message
+    {
+        self.register(id, |buffer| Ok(Box::new(protoutil::deserialize::<M>(buffer)?)));
+    }
+
+    // true if a decoder is registered for this id
+    pub fn contains(&self, id: i32) -> bool
+    {
+        return self.decoders.contains_key(&id);
+    }
+}
+
 // a netmessage packet, either to be sent or received from the network
 pub struct NetMessage
 {
@@ -84,12 +125,49 @@ impl NetMessage
         Err(anyhow::anyhow!("Unknown netmessage id {}!", id))
     }
 
+    // decode a netmessage, trying the ids registered in `registry` before the built-in ones
+    pub fn bind_with(registry: &NetMessageRegistry, id: i32, buffer: &[u8]) -> anyhow::Result<NetMessage>
+    {
+        match registry.decoders.get(&id)
+        {
+            Some(decoder) => {
+                let message = decoder(buffer)?;
+
+                Ok(NetMessage {
+                    id,
+                    size: message.compute_size(),
+                    message,
+                })
+            },
+            None => Self::bind(id, buffer),
+        }
+    }
+
+    // true if `bind` knows how to decode messages with this id
+    pub fn is_known_id(id: i32) -> bool
+    {
+        return NET_Messages::from_i32(id).is_some() || SVC_Messages::from_i32(id).is_some();
+    }
+
     // get the inner proto message
     pub fn inner(&self) -> &ProtoMessage
     {
         return &self.message;
     }
 
+    // get the netmessage enum identifier for this message
+    pub fn get_id(&self) -> i32
+    {
+        return self.id;
+    }
+
+    // get the inner proto message as a concrete type, None if it is a different message type
+    pub fn downcast<M>(&self) -> Option<&M>
+        where M: ::protobuf::Message
+    {
+        return self.message.as_any().downcast_ref::<M>();
+    }
+
     // get the maximum size of the encoded message with the header
     pub fn get_max_size(&self) -> usize
     {
@@ -97,7 +175,7 @@ impl NetMessage
     }
 
     // write the netmessage (with header) to a vector, clears the vector beforehand
-    pub fn encode_to_buffer(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<()>
+    pub fn encode_to_buffer(&self, buf: &mut Vec<u8>) -> anyhow::Result<()>
     {
         // TODO: Encode message directly to buf
 
@@ -155,6 +233,29 @@ impl NetMessage
             return svc_enum.unwrap().descriptor().name();
         }
 
-        return "<Unknown Netmessage Id>";
+        // a registered message, its proto is the best name there is
+        return self.message.descriptor().name();
     }
 }
+
+#[test]
+fn test_registry_bind() {
+    let mut tick = CNETMsg_Tick::new();
+    tick.set_tick(9);
+    let mut encoded: Vec<u8> = Vec::new();
+    NetMessage::from_proto(Box::new(tick), 0).inner().write_to_vec(&mut encoded).unwrap();
+
+    // a modded server sending a tick under its own id
+    let mut registry = NetMessageRegistry::new();
+    assert!(NetMessage::bind_with(&registry, 200, &encoded).is_err());
+
+    registry.register_proto::<CNETMsg_Tick>(200);
+    let message = NetMessage::bind_with(&registry, 200, &encoded).unwrap();
+    assert_eq!(message.get_id(), 200);
+    assert_eq!(message.downcast::<CNETMsg_Tick>().unwrap().get_tick(), 9);
+    assert_eq!(message.get_type_name(), "CNETMsg_Tick");
+
+    // built-in ids still decode as before
+    let message = NetMessage::bind_with(&registry, NET_Messages::net_Tick as i32, &encoded).unwrap();
+    assert_eq!(message.downcast::<CNETMsg_Tick>().unwrap().get_tick(), 9);
+}
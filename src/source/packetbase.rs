@@ -1,77 +1,100 @@
 use super::channel::*;
+use super::transport::PacketTransport;
+use super::lzss::Lzss;
 use anyhow::Result;
 use super::packets::*;
 use super::bitbuf::*;
 
-#[allow(non_camel_case_types)]
-#[repr(u8)]
-#[derive(Debug, PartialEq)]
-pub enum ConnectionlessPacketType
-{
-    Invalid = 0 as u8,
-    A2A_ACK = 106 as u8,
-    A2A_PING = 105 as u8,
-    A2S_INFO = 84 as u8,
-    S2A_INFO_SRC = 73 as u8,
-    A2S_GETCHALLENGE = 113 as u8,
-    S2C_CHALLENGE = 65 as u8,
-    C2S_CONNECT = 107 as u8,
-    S2C_CONNECTION = 66 as u8,
+// declares ConnectionlessPacketType along with its conversions to and from the type byte, and
+// the ConnectionlessPacket enum of every type that has a packet struct (`TYPE = byte => Struct`)
+// along with the mapping between the two, so the list of packets below is the only place a
+// new packet needs to be added. the struct itself still implements ConnectionlessPacketTrait
+// and, if it can be received, ConnectionlessPacketReceive
+macro_rules! connectionless_packets {
+    ($($name:ident = $value:literal $(=> $packet:ident)?,)*) => {
+        #[allow(non_camel_case_types)]
+        #[repr(u8)]
+        #[derive(Debug, PartialEq, Clone, Copy)]
+        pub enum ConnectionlessPacketType
+        {
+            Invalid = 0,
+            $($name = $value,)*
+        }
+
+        impl From<u8> for ConnectionlessPacketType
+        {
+            fn from(x: u8) -> ConnectionlessPacketType
+            {
+                match x
+                {
+                    $($value => ConnectionlessPacketType::$name,)*
+                    _ => ConnectionlessPacketType::Invalid
+                }
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        #[enum_dispatch]
+        #[derive(Debug)]
+        pub enum ConnectionlessPacket
+        {
+            $($($packet,)?)*
+        }
+
+        impl ConnectionlessPacket
+        {
+            // get the type enum from a packet
+            pub fn get_type(&self) -> ConnectionlessPacketType
+            {
+                match self
+                {
+                    $($(ConnectionlessPacket::$packet(_) => ConnectionlessPacketType::$name,)?)*
+                }
+            }
+        }
+
+        $($(
+            impl ConnectionlessPacketKind for $packet
+            {
+                const PACKET_TYPE: ConnectionlessPacketType = ConnectionlessPacketType::$name;
+            }
+        )?)*
+    };
+}
+
+connectionless_packets! {
+    A2A_ACK = b'j' => A2aAck,
+    A2A_PING = b'i' => A2aPing,
+    A2A_PRINT = b'l',
+    A2S_INFO = b'T' => A2sInfo,
+    S2A_INFO_SRC = b'I' => S2aInfoSrc,
+    A2S_PLAYER = b'U' => A2sPlayer,
+    S2A_PLAYER = b'D' => S2aPlayer,
+    A2S_RULES = b'V' => A2sRules,
+    S2A_RULES = b'E' => S2aRules,
+    A2S_GETCHALLENGE = b'q' => A2sGetChallenge,
+    S2C_CHALLENGE = b'A' => S2cChallenge, // also the challenge reply to A2S_INFO, A2S_PLAYER and A2S_RULES
+    C2S_CONNECT = b'k' => C2sConnect,
+    S2C_CONNECTION = b'B' => S2cConnection,
+    S2C_CONNREJECT = b'9',
 }
 
-impl From<u8> for ConnectionlessPacketType
+impl From<ConnectionlessPacketType> for u8
 {
-    fn from(x: u8) -> ConnectionlessPacketType
+    fn from(x: ConnectionlessPacketType) -> u8
     {
-        match x
-        {
-            106 => ConnectionlessPacketType::A2A_ACK,
-            105 => ConnectionlessPacketType::A2A_PING,
-            84 => ConnectionlessPacketType::A2S_INFO,
-            73 => ConnectionlessPacketType::S2A_INFO_SRC,
-            113 => ConnectionlessPacketType::A2S_GETCHALLENGE,
-            65 => ConnectionlessPacketType::S2C_CHALLENGE,
-            107 => ConnectionlessPacketType::C2S_CONNECT,
-            66 => ConnectionlessPacketType::S2C_CONNECTION,
-            _ => ConnectionlessPacketType::Invalid
-        }
+        x as u8
     }
 }
 
-
-#[allow(non_camel_case_types)]
-#[enum_dispatch]
-#[derive(Debug)]
-pub enum ConnectionlessPacket
+// the packet type a packet struct is sent as, implemented by connectionless_packets!
+pub trait ConnectionlessPacketKind
 {
-    A2aAck,
-    A2aPing,
-    A2sInfo,
-    S2aInfoSrc,
-    A2sGetChallenge,
-    S2cChallenge,
-    C2sConnect,
-    S2cConnection,
+    const PACKET_TYPE: ConnectionlessPacketType;
 }
 
 impl ConnectionlessPacket
 {
-    // get the type enum from a packet
-    pub fn get_type(&self) -> ConnectionlessPacketType
-    {
-        match self
-        {
-            ConnectionlessPacket::A2aAck(_) => ConnectionlessPacketType::A2A_ACK,
-            ConnectionlessPacket::A2aPing(_) => ConnectionlessPacketType::A2A_PING,
-            ConnectionlessPacket::A2sInfo(_) => ConnectionlessPacketType::A2S_INFO,
-            ConnectionlessPacket::S2aInfoSrc(_) => ConnectionlessPacketType::S2A_INFO_SRC,
-            ConnectionlessPacket::A2sGetChallenge(_) => ConnectionlessPacketType::A2S_GETCHALLENGE,
-            ConnectionlessPacket::S2cChallenge(_) => ConnectionlessPacketType::S2C_CHALLENGE,
-            ConnectionlessPacket::C2sConnect(_) => ConnectionlessPacketType::C2S_CONNECT,
-            ConnectionlessPacket::S2cConnection(_) => ConnectionlessPacketType::S2C_CONNECTION,
-        }
-    }
-
     // serialize the packet to a byte array
     fn serialize_header(&self, target: &mut BitBufWriterType) -> Result<()>
     {
@@ -84,25 +107,63 @@ impl ConnectionlessPacket
         Ok(())
     }
 
+    // serialize the whole packet, header included, replacing the contents of `buf`
+    pub(crate) fn serialize_to_buffer(&self, buf: &mut Vec<u8>) -> Result<()>
+    {
+        // reset length ptr
+        buf.clear();
+
+        let mut writer: BitBufWriterType = CountingBitWriter::new(std::io::Cursor::new(buf));
+
+        self.serialize_header(&mut writer)?;
+        self.serialize_values(&mut writer)?;
+
+        Ok(())
+    }
+
     // serialize the packet to a channel
-    pub fn serialize_to_channel(&self, target: &mut BufUdp) -> Result<()>
+    pub fn serialize_to_channel<S>(&self, target: &mut BufUdp<S>) -> Result<()>
+        where S: PacketTransport
     {
-        {
-            let scratch_space = target.get_scratch_mut();
+        // serialize to scratch space
+        self.serialize_to_buffer(target.get_scratch_mut())?;
 
-            // reset length ptr
-            scratch_space.clear();
+        // compress large packets if asked to, but only if it actually saves space
+        let mut compressed_packet: Option<Vec<u8>> = None;
+        if let CompressionMode::Above(threshold) = target.get_compression()
+        {
+            let scratch = target.get_scratch();
+            if scratch.len() > threshold
+            {
+                let compressed = Lzss::encode(&scratch[..]);
+                if compressed.len() + 4 < scratch.len()
+                {
+                    // compressed packets are marked with their own header in front
+                    let mut packet: Vec<u8> = Vec::with_capacity(compressed.len() + 4);
+                    packet.extend_from_slice(&NET_HEADER_FLAG_COMPRESSEDPACKET.to_le_bytes());
+                    packet.extend_from_slice(&compressed);
 
-            // scratch space to serialize packet
-            let mut scratch: BitBufWriterType = BitWriter::endian(std::io::Cursor::new(scratch_space), LittleEndian);
+                    compressed_packet = Some(packet);
+                }
+            }
+        }
 
-            // serialize to scratch space
-            self.serialize_header(&mut scratch)?;
-            self.serialize_values(&mut scratch)?;
+        // anything bigger than a datagram the engine would send unsplit is likely to never
+        // arrive, and the server gives no sign of it either
+        let len = compressed_packet.as_ref().map_or(target.get_scratch().len(), |packet| packet.len());
+        if len > NET_MAX_ROUTABLE_PAYLOAD
+        {
+            return Err(anyhow::anyhow!("{:?} packet is {} bytes{}, over the {} byte datagram limit",
+                self.get_type(), len,
+                if compressed_packet.is_some() { " after compression" } else { ", try enabling compression" },
+                NET_MAX_ROUTABLE_PAYLOAD));
         }
 
         // send over channel
-        target.send_raw(&target.get_scratch()[..])?;
+        match compressed_packet {
+            Some(packet) => target.send_raw(&packet)?,
+            None => target.send_raw(&target.get_scratch()[..])?,
+        }
 
         Ok(())
     }
@@ -110,6 +171,37 @@ impl ConnectionlessPacket
 
 pub const CONNECTIONLESS_HEADER: u32 = 0xFFFFFFFF;
 
+// the largest datagram the engine sends without splitting it, bigger ones get fragmented
+// on the way and are easily lost
+pub const NET_MAX_ROUTABLE_PAYLOAD: usize = 1200;
+
+// check the connectionless header of a received datagram, returns the type of packet and a
+// reader positioned at the start of the packet's values
+pub(crate) fn read_connectionless_header(msg: &[u8]) -> Result<(ConnectionlessPacketType, BitBufReaderType)>
+{
+    // 4 byte header and the 1 byte packet type, anything shorter is truncated or bogus
+    if msg.len() < 5
+    {
+        return Err(ChannelError::Protocol("datagram too short for connectionless header").into());
+    }
+
+    // wrap in a bit buffer
+    let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(msg), LittleEndian);
+
+    // first ensure we have a proper connectionless header
+    let header = reader.read_long()?;
+    if header != CONNECTIONLESS_HEADER
+    {
+        return Err(anyhow::anyhow!("Invalid connectionless header"))
+    }
+
+    // read the type number and convert it to a packet type enum
+    Ok((ConnectionlessPacketType::from(reader.read_char()?), reader))
+}
+
+// header in front of an LZSS compressed packet
+pub const NET_HEADER_FLAG_COMPRESSEDPACKET: u32 = 0xFFFFFFFD;
+
 #[enum_dispatch(ConnectionlessPacket)]
 pub trait ConnectionlessPacketTrait
 {
@@ -122,10 +214,48 @@ pub trait ConnectionlessPacketTrait
 }
 
 // A packet we are allowed to receive from the network
-pub trait ConnectionlessPacketReceive: Sized
+pub trait ConnectionlessPacketReceive: ConnectionlessPacketKind + Sized
 {
-    fn get_type() -> ConnectionlessPacketType;
+    fn get_type() -> ConnectionlessPacketType
+    {
+        Self::PACKET_TYPE
+    }
 
     // serialize extra packet information
     fn read_values(packet: &mut BitBufReaderType) -> Result<Self>;
 }
+
+#[test]
+fn test_packet_type_roundtrip() {
+    // every type byte either maps to a packet type and back again, or is invalid
+    for x in 0..=255u8 {
+        match ConnectionlessPacketType::from(x) {
+            ConnectionlessPacketType::Invalid => (),
+            packet_type => assert_eq!(u8::from(packet_type), x),
+        }
+    }
+
+    assert_eq!(ConnectionlessPacketType::from(b'A'), ConnectionlessPacketType::S2C_CHALLENGE);
+    assert_eq!(ConnectionlessPacketType::from(b'D'), ConnectionlessPacketType::S2A_PLAYER);
+}
+
+#[test]
+fn test_packet_kind() {
+    // packets map to their type whether they are received or sent
+    assert_eq!(<S2cChallenge as ConnectionlessPacketReceive>::get_type(), ConnectionlessPacketType::S2C_CHALLENGE);
+    assert_eq!(ConnectionlessPacket::from(A2aAck {}).get_type(), ConnectionlessPacketType::A2A_ACK);
+    assert_eq!(A2sGetChallenge::PACKET_TYPE, ConnectionlessPacketType::A2S_GETCHALLENGE);
+}
+
+#[test]
+fn test_short_connectionless_header() {
+    for len in [0usize, 4].iter() {
+        let msg = vec![0xFF; *len];
+        let err = read_connectionless_header(&msg).err().unwrap();
+        assert!(matches!(err.downcast_ref::<ChannelError>(), Some(ChannelError::Protocol(_))));
+    }
+
+    // just the header and packet type is enough
+    let (packet_type, _reader) = read_connectionless_header(&[0xFF, 0xFF, 0xFF, 0xFF, b'A']).unwrap();
+    assert_eq!(packet_type, ConnectionlessPacketType::S2C_CHALLENGE);
+}
@@ -1,12 +1,12 @@
 use super::packetbase::ConnectionlessPacketTrait;
 use super::packetbase::ConnectionlessPacketReceive;
+use super::packetbase::{ConnectionlessPacketKind, CONNECTIONLESS_HEADER};
 
 use anyhow::Result;
 use num_traits::{FromPrimitive, ToPrimitive};
-use crate::source::ConnectionlessPacketType;
 use super::bitbuf::*;
 
-use super::protos::CCLCMsg_SplitPlayerConnect;
+use super::protos::{CCLCMsg_SplitPlayerConnect, CMsg_CVars, CMsg_CVars_CVar};
 use protobuf::Message;
 
 #[derive(Debug)]
@@ -22,7 +22,11 @@ impl ConnectionlessPacketTrait for A2aPing
 }
 
 #[derive(Debug, Default)]
-pub struct A2sInfo {}
+pub struct A2sInfo
+{
+    // servers enforcing the anti-reflection challenge want it appended after the query string
+    challenge: Option<u32>,
+}
 impl ConnectionlessPacketTrait for A2sInfo
 {
     fn serialize_values(&self, target: &mut BitBufWriterType) -> Result<()>
@@ -30,10 +34,26 @@ impl ConnectionlessPacketTrait for A2sInfo
         // write other header info
         target.write_string("Source Engine Query")?;
 
+        if let Some(challenge) = self.challenge
+        {
+            target.write_long(challenge)?;
+        }
+
         Ok(())
     }
 }
 
+impl A2sInfo
+{
+    // create an info query answering a challenge from the server
+    pub fn with_challenge(challenge: u32) -> A2sInfo
+    {
+        A2sInfo {
+            challenge: Some(challenge)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct S2aInfoSrc {
     protocol_num: u8,
@@ -57,11 +77,6 @@ impl ConnectionlessPacketTrait for S2aInfoSrc
 
 impl ConnectionlessPacketReceive for S2aInfoSrc
 {
-    fn get_type() -> ConnectionlessPacketType
-    {
-        ConnectionlessPacketType::S2A_INFO_SRC
-    }
-
     fn read_values(packet: &mut BitBufReaderType) -> Result<S2aInfoSrc>
     {
         Ok(S2aInfoSrc{
@@ -83,6 +98,267 @@ impl ConnectionlessPacketReceive for S2aInfoSrc
     }
 }
 
+// S2aInfoSrc with its strings borrowed from the receive buffer, for scanning many servers
+// without allocating for every reply. only valid until the buffer is received into again
+#[derive(Debug, PartialEq)]
+pub struct S2aInfoSrcRef<'a> {
+    pub protocol_num: u8,
+    pub host_name: &'a str,
+    pub map_name: &'a str,
+    pub mod_name: &'a str,
+    pub game_name: &'a str,
+    pub app_id: u16,
+    pub num_players: u8,
+    pub max_players: u8,
+    pub num_bots: u8,
+    pub dedicated_or_listen: u8,
+    pub host_os: u8,
+    pub has_password: u8,
+    pub is_secure: u8,
+    pub host_version_string: &'a str,
+}
+
+impl S2aInfoSrc
+{
+    // parse the values of an info reply (everything after the connectionless header) without
+    // copying its strings out of `data`
+    pub fn read_values_borrowed(data: &[u8]) -> Result<S2aInfoSrcRef<'_>>
+    {
+        let mut reader = SliceReader { data, pos: 0 };
+
+        Ok(S2aInfoSrcRef {
+            protocol_num: reader.read_char()?,
+            host_name: reader.read_str()?,
+            map_name: reader.read_str()?,
+            mod_name: reader.read_str()?,
+            game_name: reader.read_str()?,
+            app_id: reader.read_word()?,
+            num_players: reader.read_char()?,
+            max_players: reader.read_char()?,
+            num_bots: reader.read_char()?,
+            dedicated_or_listen: reader.read_char()?,
+            host_os: reader.read_char()?,
+            has_password: reader.read_char()?,
+            is_secure: reader.read_char()?,
+            host_version_string: reader.read_str()?,
+        })
+    }
+}
+
+impl S2aInfoSrcRef<'_>
+{
+    // copy the strings out into an owned S2aInfoSrc
+    pub fn into_owned(self) -> S2aInfoSrc
+    {
+        S2aInfoSrc {
+            protocol_num: self.protocol_num,
+            host_name: self.host_name.to_string(),
+            map_name: self.map_name.to_string(),
+            mod_name: self.mod_name.to_string(),
+            game_name: self.game_name.to_string(),
+            app_id: self.app_id,
+            num_players: self.num_players,
+            max_players: self.max_players,
+            num_bots: self.num_bots,
+            dedicated_or_listen: self.dedicated_or_listen,
+            host_os: self.host_os,
+            has_password: self.has_password,
+            is_secure: self.is_secure,
+            host_version_string: self.host_version_string.to_string(),
+        }
+    }
+}
+
+// reads byte aligned values straight out of a slice, so strings can be borrowed from it
+struct SliceReader<'a>
+{
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a>
+{
+    // take the next `n` bytes
+    fn take(&mut self, n: usize) -> Result<&'a [u8]>
+    {
+        let bytes = self.data.get(self.pos..self.pos + n)
+            .ok_or(anyhow::anyhow!("Unexpected end of packet"))?;
+        self.pos += n;
+
+        Ok(bytes)
+    }
+
+    fn read_char(&mut self) -> Result<u8>
+    {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_word(&mut self) -> Result<u16>
+    {
+        let bytes = self.take(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    // read a null terminated string, without the terminator
+    fn read_str(&mut self) -> Result<&'a str>
+    {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|&byte| byte == 0)
+            .ok_or(anyhow::anyhow!("Unterminated string in packet"))?;
+        let bytes = self.take(len + 1)?;
+
+        Ok(std::str::from_utf8(&bytes[..len])?)
+    }
+}
+
+// challenge sent with a player or rules query to ask the server for a real one
+pub const QUERY_CHALLENGE_REQUEST: u32 = 0xFFFFFFFF;
+
+// query the players on the server
+#[derive(Debug)]
+pub struct A2sPlayer
+{
+    challenge: u32,
+}
+impl ConnectionlessPacketTrait for A2sPlayer
+{
+    fn serialize_values(&self, target: &mut BitBufWriterType) -> Result<()>
+    {
+        target.write_long(self.challenge)?;
+
+        Ok(())
+    }
+}
+
+impl Default for A2sPlayer
+{
+    // a query without a challenge, which the server answers with one
+    fn default() -> A2sPlayer
+    {
+        A2sPlayer::with_challenge(QUERY_CHALLENGE_REQUEST)
+    }
+}
+
+impl A2sPlayer
+{
+    // create a player query answering a challenge from the server
+    pub fn with_challenge(challenge: u32) -> A2sPlayer
+    {
+        A2sPlayer {
+            challenge
+        }
+    }
+}
+
+// a single player in a player query reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerInfo
+{
+    pub index: u8, // always 0 on most servers
+    pub name: String,
+    pub score: i32,
+    pub duration: f32, // seconds connected
+}
+
+// server responds to a player query with everyone that is connected
+#[derive(Debug)]
+pub struct S2aPlayer
+{
+    pub players: Vec<PlayerInfo>,
+}
+impl ConnectionlessPacketTrait for S2aPlayer {}
+impl ConnectionlessPacketReceive for S2aPlayer
+{
+    fn read_values(packet: &mut BitBufReaderType) -> Result<S2aPlayer>
+    {
+        let count = packet.read_char()?;
+
+        let mut players = Vec::with_capacity(count as usize);
+        for _ in 0..count
+        {
+            players.push(PlayerInfo {
+                index: packet.read_char()?,
+                name: packet.read_string()?,
+                score: packet.read_i32()?,
+                duration: f32::from_bits(packet.read_long()?),
+            });
+        }
+
+        Ok(S2aPlayer { players })
+    }
+}
+
+// query the server's rules (its public convars)
+#[derive(Debug)]
+pub struct A2sRules
+{
+    challenge: u32,
+}
+impl ConnectionlessPacketTrait for A2sRules
+{
+    fn serialize_values(&self, target: &mut BitBufWriterType) -> Result<()>
+    {
+        target.write_long(self.challenge)?;
+
+        Ok(())
+    }
+}
+
+impl Default for A2sRules
+{
+    // a query without a challenge, which the server answers with one
+    fn default() -> A2sRules
+    {
+        A2sRules::with_challenge(QUERY_CHALLENGE_REQUEST)
+    }
+}
+
+impl A2sRules
+{
+    // create a rules query answering a challenge from the server
+    pub fn with_challenge(challenge: u32) -> A2sRules
+    {
+        A2sRules {
+            challenge
+        }
+    }
+}
+
+// server responds to a rules query with its rule names and values, in the order it sent them
+#[derive(Debug)]
+pub struct S2aRules
+{
+    pub rules: Vec<(String, String)>,
+}
+impl ConnectionlessPacketTrait for S2aRules {}
+impl ConnectionlessPacketReceive for S2aRules
+{
+    fn read_values(packet: &mut BitBufReaderType) -> Result<S2aRules>
+    {
+        let count = packet.read_word()?;
+
+        let mut rules = Vec::with_capacity(count as usize);
+        for _ in 0..count
+        {
+            rules.push((packet.read_string()?, packet.read_string()?));
+        }
+
+        Ok(S2aRules { rules })
+    }
+}
+
+impl S2aRules
+{
+    // look up the value of a rule by name
+    pub fn get(&self, name: &str) -> Option<&str>
+    {
+        self.rules.iter()
+            .find(|(rule, _)| rule == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
 // client requests challenge with server
 #[derive(Debug)]
 pub struct A2sGetChallenge
@@ -125,7 +401,7 @@ impl A2sGetChallenge
     }
 }
 
-#[derive(FromPrimitive, ToPrimitive, Debug)]
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq)]
 #[allow(non_camel_case_types)]
 #[repr(u32)]
 pub enum AuthProtocolType
@@ -135,6 +411,32 @@ pub enum AuthProtocolType
     PROTOCOL_STEAM =	0x03,	// auth with steam, default
 }
 
+// the kind of lobby a server is reserved for, if any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LobbyType
+{
+    None, // no lobby matching
+    Friends, // only friends of the lobby may join
+    Public, // public lobby
+}
+
+impl std::str::FromStr for LobbyType
+{
+    type Err = anyhow::Error;
+
+    // parse the lobby type string sent by the server
+    fn from_str(lobby_type: &str) -> Result<LobbyType>
+    {
+        match lobby_type
+        {
+            "" => Ok(LobbyType::None),
+            "friends" => Ok(LobbyType::Friends),
+            "public" => Ok(LobbyType::Public),
+            _ => Err(anyhow::anyhow!("Invalid lobby type: {}", lobby_type)),
+        }
+    }
+}
+
 // server responds to challenge with additional server info
 #[derive(Debug)]
 pub struct S2cChallenge
@@ -146,9 +448,9 @@ pub struct S2cChallenge
     pub vac_secured: u8, // 0 or 1
     pub context_response: String, // should be "connect0x...." on success, otherwise "connect-retry"
     pub host_version: u32, //server host version
-    pub lobby_type: String, // "", "friends", or "public"
+    pub lobby_type: LobbyType, // "", "friends", or "public"
     pub password_required: u8, // 1 if password is required to connect
-    pub lobby_id: u64, // -1 unless lobby matching is used
+    pub lobby_id: Option<u64>, // None unless lobby matching is used
     pub friends_required: u8, // 0, unless lobby matching is used
     pub valve_ds: u8, // 1 if this is a valve hosted dedicated server
     pub require_certificate: u8, // 0, unless certificate authentication is used
@@ -157,24 +459,33 @@ pub struct S2cChallenge
 impl ConnectionlessPacketTrait for S2cChallenge {}
 impl ConnectionlessPacketReceive for S2cChallenge
 {
-    fn get_type() -> ConnectionlessPacketType
-    {
-        ConnectionlessPacketType::S2C_CHALLENGE
-    }
-
     fn read_values(packet: &mut BitBufReaderType) -> Result<S2cChallenge>
     {
+        let challenge_num = packet.read_long()?;
+        let auth_protocol = FromPrimitive::from_u32(packet.read_long()?).ok_or(anyhow::anyhow!("Invalid auth protocol"))?;
+
+        // steam2 encryption is long gone, a server which still wants it would just reject the
+        // connect packet
+        let steam2_encryption_enabled = packet.read_word()?;
+        if steam2_encryption_enabled != 0 {
+            return Err(anyhow::anyhow!("Server requires steam2 encryption ({}), which is not supported", steam2_encryption_enabled));
+        }
+
         Ok(S2cChallenge {
-            challenge_num: packet.read_long()?,
-            auth_protocol: FromPrimitive::from_u32(packet.read_long()?).ok_or(anyhow::anyhow!("Invalid auth protocol"))?,
-            steam2_encryption_enabled: packet.read_word()?,
+            challenge_num,
+            auth_protocol,
+            steam2_encryption_enabled,
             gameserver_steamid: packet.read_longlong()?,
             vac_secured: packet.read_char()?,
             context_response: packet.read_string()?,
             host_version: packet.read_long()?,
-            lobby_type: packet.read_string()?,
+            lobby_type: packet.read_string()?.parse()?,
             password_required: packet.read_char()?,
-            lobby_id: packet.read_longlong()?,
+            lobby_id: match packet.read_longlong()? {
+                // -1 is sent when there's no lobby
+                u64::MAX => None,
+                id => Some(id),
+            },
             friends_required: packet.read_char()?,
             valve_ds: packet.read_char()?,
             require_certificate: packet.read_char()?,
@@ -189,6 +500,80 @@ impl S2cChallenge
     {
         self.context_response == "connect-retry"
     }
+
+    // parse the cookie out of a successful "connect0x...." context response
+    // returns None if the server responded with something else (e.g. "connect-retry")
+    pub fn context_cookie(&self) -> Option<u32>
+    {
+        let cookie = self.context_response.strip_prefix("connect0x")?;
+
+        u32::from_str_radix(cookie, 16).ok()
+    }
+
+    // the server is VAC secured
+    pub fn is_vac_secured(&self) -> bool
+    {
+        self.vac_secured != 0
+    }
+
+    // a password is needed to connect
+    pub fn is_password_required(&self) -> bool
+    {
+        self.password_required != 0
+    }
+
+    // only friends of the lobby may connect
+    pub fn is_friends_required(&self) -> bool
+    {
+        self.friends_required != 0
+    }
+
+    // the server is a valve hosted dedicated server
+    pub fn is_valve_ds(&self) -> bool
+    {
+        self.valve_ds != 0
+    }
+
+    // certificate authentication is needed to connect
+    pub fn is_certificate_required(&self) -> bool
+    {
+        self.require_certificate != 0
+    }
+
+    // check that this challenge answers the A2sGetChallenge sent with `cookie`, a response to
+    // some other request (e.g. a stale one from before a retry) is a ChallengeMismatch
+    pub fn verify_cookie(&self, cookie: u32) -> Result<()>
+    {
+        if self.context_cookie() != Some(cookie)
+        {
+            return Err(ChallengeMismatch {
+                sent: cookie,
+                response: self.context_response.clone(),
+            }.into());
+        }
+
+        Ok(())
+    }
+}
+
+// the server answered a challenge with a context response for a different cookie
+#[derive(Debug)]
+pub struct ChallengeMismatch
+{
+    pub sent: u32, // the cookie sent in the connect string
+    pub response: String, // the context response the server sent back
+}
+
+impl std::fmt::Display for ChallengeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Challenge response \"{}\" does not match cookie {:#010x}", self.response, self.sent)
+    }
+}
+
+impl std::error::Error for ChallengeMismatch {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
 }
 
 #[derive(FromPrimitive, ToPrimitive, Debug)]
@@ -200,6 +585,7 @@ pub enum CrossplayPlatform
     Ps3
 }
 
+// the steam identity used to authenticate with a server
 #[derive(Debug)]
 pub struct SteamAuthInfo
 {
@@ -207,6 +593,63 @@ pub struct SteamAuthInfo
     pub auth_ticket: Vec<u8>,
 }
 
+impl SteamAuthInfo
+{
+    // create auth info from a steamid and an auth session ticket
+    // the ticket can come from a live steam client, another process, or a previous capture
+    pub fn new(steamid: u64, auth_ticket: Vec<u8>) -> SteamAuthInfo
+    {
+        SteamAuthInfo {
+            steamid,
+            auth_ticket,
+        }
+    }
+}
+
+// userinfo convars sent in every connect unless they are overridden, servers check some of
+// these during authentication so they need to be sensible values
+pub const DEFAULT_CONNECT_CONVARS: &[(&str, &str)] = &[
+    ("rate", "196608"),
+    ("cl_cmdrate", "64"),
+    ("cl_updaterate", "64"),
+    ("cl_interp", "0.031250"),
+    ("cl_interp_ratio", "2"),
+    ("cl_lagcompensation", "1"),
+    ("cl_predict", "1"),
+    ("cl_predictweapons", "1"),
+];
+
+// build the userinfo convars sent in a connect packet, starting from DEFAULT_CONNECT_CONVARS
+// a var given here replaces the default of the same name, anything else is added after them
+pub fn build_convars(vars: &[(&str, &str)]) -> CMsg_CVars
+{
+    let mut merged: Vec<(&str, &str)> = DEFAULT_CONNECT_CONVARS.to_vec();
+
+    for &(name, value) in vars
+    {
+        match merged.iter_mut().find(|(default_name, _)| *default_name == name)
+        {
+            Some(default) => default.1 = value,
+            None => merged.push((name, value)),
+        }
+    }
+
+    let mut convars = CMsg_CVars::new();
+    for (name, value) in merged
+    {
+        let mut cvar = CMsg_CVars_CVar::new();
+        cvar.set_name(name.to_string());
+        cvar.set_value(value.to_string());
+
+        convars.cvars.push(cvar);
+    }
+
+    convars
+}
+
+// the auth block length is sent as a u16 which includes the 8 byte steamid
+pub const MAX_AUTH_TICKET_LEN: usize = (u16::MAX as usize) - 8;
+
 #[derive(Debug)]
 pub struct C2sConnect
 {
@@ -220,14 +663,127 @@ pub struct C2sConnect
     pub low_violence: bool,
     pub lobby_cookie: u64,
     pub crossplay_platform: CrossplayPlatform,
-    pub encryption_key_index: u32,
+    pub encryption_key_index: u32, // index of the server's network encryption key, 0 for none
     pub auth_info: SteamAuthInfo,
+    pub certificate_required: bool, // the challenge asked for a server encryption key
+}
+
+impl C2sConnect
+{
+    // create a connect packet answering a successful challenge from the server, authenticated
+    // with `auth_info`. other fields default to a single pc player with no password or lobby,
+    // sending the DEFAULT_CONNECT_CONVARS so the server doesn't throttle us for a missing rate
+    pub fn new(challenge: &S2cChallenge, auth_info: SteamAuthInfo) -> C2sConnect
+    {
+        let mut split_connect = CCLCMsg_SplitPlayerConnect::new();
+        split_connect.set_convars(build_convars(&[]));
+
+        C2sConnect {
+            host_version: challenge.host_version,
+            auth_protocol: challenge.auth_protocol,
+            challenge_num: challenge.challenge_num,
+            player_name: String::new(),
+            server_password: String::new(),
+            num_players: 1,
+            split_player_connect: vec![split_connect],
+            low_violence: false,
+            lobby_cookie: 0,
+            crossplay_platform: CrossplayPlatform::Pc,
+            encryption_key_index: 0,
+            auth_info,
+            certificate_required: challenge.is_certificate_required(),
+        }
+    }
+
+    // set the userinfo convars for the (first) connecting player, see build_convars
+    // the player name set with set_player_name is kept
+    pub fn set_convars(&mut self, convars: CMsg_CVars)
+    {
+        let mut split_connect = CCLCMsg_SplitPlayerConnect::new();
+        split_connect.set_convars(convars);
+
+        self.split_player_connect = vec![split_connect];
+        self.apply_player_name();
+    }
+
+    // set the name of the connecting player. cs:go takes the name from the "name" convar and
+    // ignores player_name, older engines do the opposite, so it is set in both places
+    pub fn set_player_name(&mut self, name: &str)
+    {
+        self.player_name = name.to_string();
+        self.apply_player_name();
+    }
+
+    // the size of the packet on the wire before any compression, header included. anything
+    // over NET_MAX_ROUTABLE_PAYLOAD has to be compressed to be sent
+    pub fn serialized_size(&self) -> Result<usize>
+    {
+        return Ok(self.serialize_to_vec()?.len());
+    }
+
+    // serialize the packet exactly as it is sent, header included, without a channel. sending
+    // takes the packet by value, this doesn't, so the bytes can be checked on their own
+    pub fn serialize_to_vec(&self) -> Result<Vec<u8>>
+    {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer: BitBufWriterType = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+
+            // same header ConnectionlessPacket::serialize_to_buffer writes
+            writer.write_long(CONNECTIONLESS_HEADER)?;
+            writer.write_char(Self::PACKET_TYPE as u8)?;
+
+            self.serialize_values(&mut writer)?;
+        }
+
+        return Ok(buf);
+    }
+
+    // copy player_name into the first player's "name" convar
+    fn apply_player_name(&mut self)
+    {
+        if self.player_name.is_empty()
+        {
+            return;
+        }
+
+        let split_connect = match self.split_player_connect.first_mut() {
+            Some(split_connect) => split_connect,
+            None => return,
+        };
+
+        let convars = split_connect.mut_convars();
+        match convars.cvars.iter_mut().find(|cvar| cvar.get_name() == "name")
+        {
+            Some(cvar) => cvar.set_value(self.player_name.clone()),
+            None => {
+                let mut cvar = CMsg_CVars_CVar::new();
+                cvar.set_name(String::from("name"));
+                cvar.set_value(self.player_name.clone());
+
+                convars.cvars.push(cvar);
+            }
+        }
+    }
 }
 
 impl ConnectionlessPacketTrait for C2sConnect
 {
     fn serialize_values(&self, target: &mut BitBufWriterType) -> Result<()>
     {
+        // a server with its own encryption key rejects index 0 without saying why. the key and
+        // its index have to come from the server operator, see NetChannel::upgrade_with_encryption_key
+        if self.certificate_required && self.encryption_key_index == 0
+        {
+            return Err(anyhow::anyhow!("Server requires a network encryption key, but no encryption key index was set"));
+        }
+
+        // the auth block length can't represent a longer ticket, don't send a corrupt packet
+        if self.auth_info.auth_ticket.len() > MAX_AUTH_TICKET_LEN
+        {
+            return Err(anyhow::anyhow!("Auth ticket is {} bytes, longer than the maximum of {}", self.auth_info.auth_ticket.len(), MAX_AUTH_TICKET_LEN));
+        }
+
         // write fields
         target.write_long(self.host_version)?;
         target.write_long(ToPrimitive::to_u32(&self.auth_protocol).ok_or(anyhow::anyhow!("Invalid auth protocol"))?)?;
@@ -259,9 +815,8 @@ impl ConnectionlessPacketTrait for C2sConnect
 
         // what genius though "oh, let's use a single bit to represent
         // low_violence and just leave this entire thing unaligned to a single byte...
-        for _i in 0..7 {
-            target.write_bit(false)?;
-        }
+        target.byte_align()?;
+
         Ok(())
     }
 }
@@ -277,11 +832,6 @@ pub struct S2cConnection
 impl ConnectionlessPacketTrait for S2cConnection {}
 impl ConnectionlessPacketReceive for S2cConnection
 {
-    fn get_type() -> ConnectionlessPacketType
-    {
-        ConnectionlessPacketType::S2C_CONNECTION
-    }
-
     fn read_values(packet: &mut BitBufReaderType) -> Result<S2cConnection>
     {
         Ok(S2cConnection {
@@ -289,3 +839,290 @@ impl ConnectionlessPacketReceive for S2cConnection
         })
     }
 }
+
+impl S2cConnection
+{
+    // the lobby cookie the server echoed back in the connection string, read as hex. servers
+    // which don't echo it send all zeroes, which gives None
+    pub fn lobby_cookie(&self) -> Option<u64>
+    {
+        let cookie = self.connection_string.as_str();
+        let cookie = cookie.strip_prefix("0x").unwrap_or(cookie);
+
+        match u64::from_str_radix(cookie, 16) {
+            Ok(0) | Err(_) => None,
+            Ok(cookie) => Some(cookie),
+        }
+    }
+
+    // check that the server accepted the lobby cookie (reservation id) sent in C2sConnect. a
+    // server which doesn't echo a cookie can't be checked and passes
+    pub fn verify_lobby_cookie(&self, cookie: u64) -> Result<()>
+    {
+        match self.lobby_cookie() {
+            Some(echoed) if echoed != cookie => Err(LobbyCookieMismatch {
+                sent: cookie,
+                echoed,
+            }.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// the server acknowledged a connection with a different lobby cookie than the one we sent,
+// usually because the GC reservation didn't line up with the connect
+#[derive(Debug)]
+pub struct LobbyCookieMismatch
+{
+    pub sent: u64, // the lobby cookie sent in C2sConnect
+    pub echoed: u64, // the lobby cookie the server sent back
+}
+
+impl std::fmt::Display for LobbyCookieMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Server acknowledged lobby cookie {:#x}, but {:#x} was sent", self.echoed, self.sent)
+    }
+}
+
+impl std::error::Error for LobbyCookieMismatch {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// a successful challenge answering `cookie`, with every flag off
+#[cfg(test)]
+pub(crate) fn challenge_for_cookie(cookie: u32) -> S2cChallenge
+{
+    S2cChallenge {
+        challenge_num: cookie,
+        auth_protocol: AuthProtocolType::PROTOCOL_STEAM,
+        steam2_encryption_enabled: 0,
+        gameserver_steamid: 0,
+        vac_secured: 0,
+        context_response: format!("connect{:#010x}", cookie),
+        host_version: 0,
+        lobby_type: LobbyType::None,
+        password_required: 0,
+        lobby_id: None,
+        friends_required: 0,
+        valve_ds: 0,
+        require_certificate: 0,
+    }
+}
+
+#[test]
+fn test_challenge_verify_cookie() {
+    let mut challenge = challenge_for_cookie(0x1234ABCD);
+
+    assert!(challenge.verify_cookie(0x1234ABCD).is_ok());
+
+    // a response for some other cookie
+    let err = challenge.verify_cookie(0x1234ABCE).err().unwrap();
+    assert!(matches!(err.downcast_ref::<ChallengeMismatch>(), Some(ChallengeMismatch { sent: 0x1234ABCE, .. })));
+
+    // the server asking for a retry doesn't answer the cookie either
+    challenge.context_response = String::from("connect-retry");
+    assert!(challenge.verify_cookie(0x1234ABCD).is_err());
+}
+
+#[test]
+fn test_info_with_challenge() {
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+        A2sInfo::default().serialize_values(&mut writer).unwrap();
+    }
+    assert_eq!(buf, b"Source Engine Query\0");
+
+    // the challenge follows the query string
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = CountingBitWriter::new(std::io::Cursor::new(&mut buf));
+        A2sInfo::with_challenge(0x11223344).serialize_values(&mut writer).unwrap();
+    }
+    assert_eq!(&buf[..20], b"Source Engine Query\0");
+    assert_eq!(&buf[20..], &[0x44, 0x33, 0x22, 0x11]);
+}
+
+#[test]
+fn test_challenge_steam2_encryption() {
+    let mut data = vec![1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    data.extend_from_slice(b"connect0x00000001\0");
+    data.extend_from_slice(&[0xBE, 0x35, 0, 0]);
+    data.extend_from_slice(b"\0");
+    data.extend_from_slice(&[0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0]);
+
+    let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+    assert_eq!(S2cChallenge::read_values(&mut reader).unwrap().host_version, 13758);
+
+    // the same challenge with steam2 encryption turned on
+    data[8] = 1;
+    let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+    assert!(S2cChallenge::read_values(&mut reader).is_err());
+}
+
+#[test]
+fn test_connection_lobby_cookie() {
+    // the usual placeholder carries no cookie
+    let connection = S2cConnection { connection_string: String::from("0000000000") };
+    assert_eq!(connection.lobby_cookie(), None);
+    assert!(connection.verify_lobby_cookie(0x1234).is_ok());
+
+    let connection = S2cConnection { connection_string: String::from("0000001234") };
+    assert_eq!(connection.lobby_cookie(), Some(0x1234));
+    assert!(connection.verify_lobby_cookie(0x1234).is_ok());
+
+    let err = connection.verify_lobby_cookie(0x5678).err().unwrap();
+    assert!(err.downcast_ref::<LobbyCookieMismatch>().is_some());
+}
+
+#[test]
+fn test_challenge_flags() {
+    let mut challenge = challenge_for_cookie(0);
+    assert!(!challenge.is_vac_secured());
+    assert!(!challenge.is_valve_ds());
+
+    // anything other than 0 is set, not just 1
+    challenge.vac_secured = 1;
+    challenge.valve_ds = 2;
+    assert!(challenge.is_vac_secured());
+    assert!(challenge.is_valve_ds());
+}
+
+#[test]
+fn test_build_convars() {
+    let convars = build_convars(&[("rate", "786432"), ("cl_session", "$0x1")]);
+
+    let find = |name: &str| convars.cvars.iter().find(|cvar| cvar.get_name() == name).map(|cvar| cvar.get_value().to_string());
+
+    // overrides a default in place, adds anything new
+    assert_eq!(find("rate").as_deref(), Some("786432"));
+    assert_eq!(find("cl_interp").as_deref(), Some("0.031250"));
+    assert_eq!(find("cl_session").as_deref(), Some("$0x1"));
+    assert_eq!(convars.cvars.len(), DEFAULT_CONNECT_CONVARS.len() + 1);
+}
+
+#[test]
+fn test_connect_auth_ticket_too_long() {
+    let challenge = challenge_for_cookie(0);
+
+    // the longest ticket that fits is fine
+    let connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, vec![0u8; MAX_AUTH_TICKET_LEN]));
+    let mut buf: Vec<u8> = Vec::new();
+    assert!(connect.serialize_values(&mut CountingBitWriter::new(std::io::Cursor::new(&mut buf))).is_ok());
+
+    // one more byte would wrap the length around
+    let connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, vec![0u8; MAX_AUTH_TICKET_LEN + 1]));
+    let mut buf: Vec<u8> = Vec::new();
+    assert!(connect.serialize_values(&mut CountingBitWriter::new(std::io::Cursor::new(&mut buf))).is_err());
+}
+
+#[test]
+fn test_connect_serialized_size() {
+    use crate::source::packetbase::ConnectionlessPacket;
+
+    let challenge = challenge_for_cookie(0);
+
+    // matches what is actually sent
+    let connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, vec![0u8; 234]));
+    let size = connect.serialized_size().unwrap();
+    let mut buf: Vec<u8> = Vec::new();
+    ConnectionlessPacket::from(connect).serialize_to_buffer(&mut buf).unwrap();
+    assert_eq!(size, buf.len());
+}
+
+#[test]
+fn test_connect_serialize_to_vec() {
+    use crate::source::packetbase::ConnectionlessPacket;
+
+    let mut challenge = challenge_for_cookie(0x1234ABCD);
+    challenge.host_version = 13758;
+
+    let mut connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0x0110000100001234, vec![0xAA, 0xBB, 0xCC, 0xDD]));
+    connect.server_password = String::from("pw");
+    connect.set_convars(CMsg_CVars::new());
+    connect.lobby_cookie = 0x0102030405060708;
+
+    // everything after the low violence bit is shifted over by one
+    let expected: Vec<u8> = vec![
+        0xff, 0xff, 0xff, 0xff, 0x6b, 0xbe, 0x35, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0xcd, 0xab, 0x34, 0x12, 0x00, 0x70, 0x77, 0x00, 0x01, 0x00, 0x02,
+        0x0a, 0x00, 0x10, 0x0e, 0x0c, 0x0a, 0x08, 0x06, 0x04, 0x02, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x18, 0x00, 0x68, 0x24, 0x00, 0x00, 0x02, 0x00, 0x20,
+        0x02, 0x54, 0x77, 0x99, 0xbb, 0x01,
+    ];
+    assert_eq!(connect.serialize_to_vec().unwrap(), expected);
+
+    // and it's the same as what a channel would send
+    let mut buf: Vec<u8> = Vec::new();
+    ConnectionlessPacket::from(connect).serialize_to_buffer(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_connect_encryption_key_index() {
+    let mut challenge = challenge_for_cookie(0);
+    challenge.require_certificate = 1;
+
+    // index 0 would be rejected by the server, so it isn't sent
+    let mut connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, vec![0u8; 16]));
+    let mut buf: Vec<u8> = Vec::new();
+    assert!(connect.serialize_values(&mut CountingBitWriter::new(std::io::Cursor::new(&mut buf))).is_err());
+
+    connect.encryption_key_index = 3;
+    let mut buf: Vec<u8> = Vec::new();
+    assert!(connect.serialize_values(&mut CountingBitWriter::new(std::io::Cursor::new(&mut buf))).is_ok());
+}
+
+#[test]
+fn test_connect_player_name() {
+    let challenge = challenge_for_cookie(0);
+    let mut connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, Vec::new()));
+
+    let name_convar = |connect: &C2sConnect| connect.split_player_connect[0].get_convars().cvars.iter()
+        .find(|cvar| cvar.get_name() == "name")
+        .map(|cvar| cvar.get_value().to_string());
+
+    connect.set_player_name("player one");
+    assert_eq!(connect.player_name, "player one");
+    assert_eq!(name_convar(&connect).as_deref(), Some("player one"));
+
+    // replacing the convars keeps the name, and doesn't add it twice
+    connect.set_convars(build_convars(&[("name", "someone else")]));
+    assert_eq!(name_convar(&connect).as_deref(), Some("player one"));
+    assert_eq!(connect.split_player_connect[0].get_convars().cvars.iter().filter(|cvar| cvar.get_name() == "name").count(), 1);
+}
+
+#[test]
+fn test_connect_default_rates() {
+    let challenge = challenge_for_cookie(0);
+    let connect = C2sConnect::new(&challenge, SteamAuthInfo::new(0, Vec::new()));
+
+    // a connect packet sends sane networking rates even if the convars are never set
+    let convars = connect.split_player_connect[0].get_convars();
+    for &(name, value) in DEFAULT_CONNECT_CONVARS {
+        assert!(convars.cvars.iter().any(|cvar| cvar.get_name() == name && cvar.get_value() == value));
+    }
+}
+
+#[test]
+fn test_info_borrowed() {
+    let mut data = vec![17];
+    data.extend_from_slice(b"host\0de_dust2\0csgo\0Counter-Strike\0");
+    data.extend_from_slice(&[0xDA, 0x02, 3, 10, 0, b'd', b'l', 0, 1]);
+    data.extend_from_slice(b"1.38.0.0\0");
+
+    let info = S2aInfoSrc::read_values_borrowed(&data).unwrap();
+    assert_eq!(info.map_name, "de_dust2");
+    assert_eq!(info.app_id, 730);
+    assert_eq!(info.host_version_string, "1.38.0.0");
+
+    // matches what the allocating path reads
+    let mut reader: BitBufReaderType = BitReader::endian(std::io::Cursor::new(&data[..]), LittleEndian);
+    let owned = S2aInfoSrc::read_values(&mut reader).unwrap();
+    assert_eq!(format!("{:?}", info.into_owned()), format!("{:?}", owned));
+
+    // a string running off the end of the packet
+    assert!(S2aInfoSrc::read_values_borrowed(&data[..10]).is_err());
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{UdpSocket, SocketAddr};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use log::{trace, warn};
+use crate::source::packetbase::{read_connectionless_header, ConnectionlessPacket, ConnectionlessPacketType, ConnectionlessPacketReceive};
+use crate::source::packets::{A2sInfo, S2aInfoSrc};
+use crate::source::bitbuf::WireReader;
+
+// info replies fit in a single datagram, so there's no need for a full payload sized buffer
+const PINGER_BUFFER_SIZE: usize = 4096;
+
+// Queries the info of many servers at once from a single socket
+//
+// Unlike a ConnectionlessChannel per server, every query is sent from the same unconnected
+// socket and every reply is read into the same buffer, which keeps scanning thousands of
+// servers cheap. Servers which ask for the anti-reflection challenge are queried again with it.
+pub struct ServerPinger
+{
+    // socket every query is sent from
+    socket: UdpSocket,
+
+    // receive buffer shared by every reply
+    buffer: Vec<u8>,
+
+    // serialized query packets are built here before sending
+    send_buffer: Vec<u8>,
+
+    // the most servers waiting on a reply at once
+    concurrency: usize,
+
+    // how long to wait for each batch of servers to reply
+    timeout: Duration,
+}
+
+impl ServerPinger
+{
+    // bind a socket to query from, up to `concurrency` servers are queried at a time and given
+    // `timeout` to reply
+    pub fn new(concurrency: usize, timeout: Duration) -> Result<Self>
+    {
+        if concurrency == 0 || timeout.as_nanos() == 0 {
+            return Err(anyhow::anyhow!("Pinger concurrency and timeout must be non-zero"));
+        }
+
+        Ok(Self {
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            buffer: vec![0u8; PINGER_BUFFER_SIZE],
+            send_buffer: Vec::with_capacity(64),
+            concurrency,
+            timeout,
+        })
+    }
+
+    // query every server for its info, servers which didn't reply in time are left out
+    pub fn ping_all(&mut self, servers: &[SocketAddr]) -> Result<HashMap<SocketAddr, S2aInfoSrc>>
+    {
+        let mut results: HashMap<SocketAddr, S2aInfoSrc> = HashMap::with_capacity(servers.len());
+
+        for batch in servers.chunks(self.concurrency)
+        {
+            let mut pending: HashSet<SocketAddr> = HashSet::with_capacity(batch.len());
+
+            for server in batch
+            {
+                // e.g. an ipv6 server can't be reached from our ipv4 socket, which is no reason
+                // to give up on the others
+                if let Err(e) = self.send_query(*server, A2sInfo::default()) {
+                    warn!("Failed to query {}: {}", server, e);
+                    continue;
+                }
+
+                pending.insert(*server);
+            }
+
+            let deadline = Instant::now() + self.timeout;
+            while !pending.is_empty()
+            {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.as_nanos() == 0 {
+                    break;
+                }
+                self.socket.set_read_timeout(Some(remaining))?;
+
+                let (len, from) = match self.socket.recv_from(&mut self.buffer) {
+                    Ok(res) => res,
+                    Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => break,
+
+                    // e.g. an icmp unreachable from one server, which shouldn't stop the others
+                    Err(e) => {
+                        trace!("Pinger receive failed: {}", e);
+                        continue;
+                    }
+                };
+
+                // replies from anything we aren't waiting on are ignored
+                if !pending.contains(&from) {
+                    continue;
+                }
+
+                match self.parse_reply(len) {
+                    Ok(PingReply::Info(info)) => {
+                        pending.remove(&from);
+                        results.insert(from, info);
+                    },
+                    Ok(PingReply::Challenge(challenge)) => {
+                        if let Err(e) = self.send_query(from, A2sInfo::with_challenge(challenge)) {
+                            warn!("Failed to query {} with its challenge: {}", from, e);
+                            pending.remove(&from);
+                        }
+                    },
+                    Err(e) => trace!("Ignoring reply from {}: {}", from, e),
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    // send an info query to a server
+    fn send_query(&mut self, server: SocketAddr, query: A2sInfo) -> Result<()>
+    {
+        ConnectionlessPacket::from(query).serialize_to_buffer(&mut self.send_buffer)?;
+        self.socket.send_to(&self.send_buffer, server)?;
+
+        Ok(())
+    }
+
+    // parse a reply that was received into the shared buffer
+    fn parse_reply(&self, len: usize) -> Result<PingReply>
+    {
+        let (packet_type, mut reader) = read_connectionless_header(&self.buffer[..len])?;
+
+        match packet_type
+        {
+            ConnectionlessPacketType::S2A_INFO_SRC => Ok(PingReply::Info(S2aInfoSrc::read_values(&mut reader)?)),
+            ConnectionlessPacketType::S2C_CHALLENGE => Ok(PingReply::Challenge(reader.read_long()?)),
+            _ => Err(anyhow::anyhow!("Unexpected packet {:?}", packet_type)),
+        }
+    }
+}
+
+// what a server can answer an info query with
+enum PingReply
+{
+    Info(S2aInfoSrc),
+
+    // query again with this challenge
+    Challenge(u32),
+}
+
+#[test]
+fn test_ping_all() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    // a server which never replies
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent_addr = silent.local_addr().unwrap();
+
+    let handle = std::thread::spawn(move || {
+        let mut buf = [0u8; 256];
+
+        // ask for a challenge first
+        let (_len, from) = server.recv_from(&mut buf).unwrap();
+        server.send_to(&[0xFF, 0xFF, 0xFF, 0xFF, b'A', 0x44, 0x33, 0x22, 0x11], from).unwrap();
+
+        // then answer the query which carries it
+        let (len, from) = server.recv_from(&mut buf).unwrap();
+        assert!(buf[..len].ends_with(&[0x44, 0x33, 0x22, 0x11]));
+
+        let mut info = vec![0xFF, 0xFF, 0xFF, 0xFF, b'I', 17];
+        info.extend_from_slice(b"host\0map\0csgo\0Counter-Strike\0");
+        info.extend_from_slice(&[0xDA, 0x02, 1, 10, 0, b'd', b'l', 0, 1]);
+        info.extend_from_slice(b"1.0\0");
+        server.send_to(&info, from).unwrap();
+    });
+
+    // a server our ipv4 socket can't even send to
+    let unreachable_addr: SocketAddr = "[::1]:27015".parse().unwrap();
+
+    let mut pinger = ServerPinger::new(8, Duration::from_millis(500)).unwrap();
+    let results = pinger.ping_all(&[unreachable_addr, server_addr, silent_addr]).unwrap();
+    handle.join().unwrap();
+
+    assert!(results.contains_key(&server_addr));
+    assert!(!results.contains_key(&silent_addr));
+    assert!(!results.contains_key(&unreachable_addr));
+}
@@ -0,0 +1,1486 @@
+// This file is generated by rust-protobuf 2.18.0. Do not edit
+// @generated
+
+// https://github.com/rust-lang/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![allow(unused_attributes)]
+#![rustfmt::skip]
+
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unused_imports)]
+#![allow(unused_results)]
+//! Generated file from `cstrike15_usermessages.proto`
+
+/// Generated files are compatible only with the same version
+/// of protobuf runtime.
+// const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_2_18_0;
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_HudText {
+    // message fields
+    text: ::protobuf::SingularField<::std::string::String>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_HudText {
+    fn default() -> &'a CCSUsrMsg_HudText {
+        <CCSUsrMsg_HudText as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_HudText {
+    pub fn new() -> CCSUsrMsg_HudText {
+        ::std::default::Default::default()
+    }
+
+    // optional string text = 1;
+
+
+    pub fn get_text(&self) -> &str {
+        match self.text.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+    pub fn clear_text(&mut self) {
+        self.text.clear();
+    }
+
+    pub fn has_text(&self) -> bool {
+        self.text.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_text(&mut self, v: ::std::string::String) {
+        self.text = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_text(&mut self) -> &mut ::std::string::String {
+        if self.text.is_none() {
+            self.text.set_default();
+        }
+        self.text.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_text(&mut self) -> ::std::string::String {
+        self.text.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_HudText {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.text)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(ref v) = self.text.as_ref() {
+            my_size += ::protobuf::rt::string_size(1, &v);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(ref v) = self.text.as_ref() {
+            os.write_string(1, &v)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_HudText {
+        CCSUsrMsg_HudText::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "text",
+                |m: &CCSUsrMsg_HudText| { &m.text },
+                |m: &mut CCSUsrMsg_HudText| { &mut m.text },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_HudText>(
+                "CCSUsrMsg_HudText",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_HudText {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_HudText> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_HudText::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_HudText {
+    fn clear(&mut self) {
+        self.text.clear();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_HudText {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_HudText {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_SayText {
+    // message fields
+    ent_idx: ::std::option::Option<i32>,
+    text: ::protobuf::SingularField<::std::string::String>,
+    chat: ::std::option::Option<bool>,
+    textallchat: ::std::option::Option<bool>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_SayText {
+    fn default() -> &'a CCSUsrMsg_SayText {
+        <CCSUsrMsg_SayText as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_SayText {
+    pub fn new() -> CCSUsrMsg_SayText {
+        ::std::default::Default::default()
+    }
+
+    // optional int32 ent_idx = 1;
+
+
+    pub fn get_ent_idx(&self) -> i32 {
+        self.ent_idx.unwrap_or(0)
+    }
+    pub fn clear_ent_idx(&mut self) {
+        self.ent_idx = ::std::option::Option::None;
+    }
+
+    pub fn has_ent_idx(&self) -> bool {
+        self.ent_idx.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ent_idx(&mut self, v: i32) {
+        self.ent_idx = ::std::option::Option::Some(v);
+    }
+
+    // optional string text = 2;
+
+
+    pub fn get_text(&self) -> &str {
+        match self.text.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+    pub fn clear_text(&mut self) {
+        self.text.clear();
+    }
+
+    pub fn has_text(&self) -> bool {
+        self.text.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_text(&mut self, v: ::std::string::String) {
+        self.text = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_text(&mut self) -> &mut ::std::string::String {
+        if self.text.is_none() {
+            self.text.set_default();
+        }
+        self.text.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_text(&mut self) -> ::std::string::String {
+        self.text.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    // optional bool chat = 3;
+
+
+    pub fn get_chat(&self) -> bool {
+        self.chat.unwrap_or(false)
+    }
+    pub fn clear_chat(&mut self) {
+        self.chat = ::std::option::Option::None;
+    }
+
+    pub fn has_chat(&self) -> bool {
+        self.chat.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_chat(&mut self, v: bool) {
+        self.chat = ::std::option::Option::Some(v);
+    }
+
+    // optional bool textallchat = 4;
+
+
+    pub fn get_textallchat(&self) -> bool {
+        self.textallchat.unwrap_or(false)
+    }
+    pub fn clear_textallchat(&mut self) {
+        self.textallchat = ::std::option::Option::None;
+    }
+
+    pub fn has_textallchat(&self) -> bool {
+        self.textallchat.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_textallchat(&mut self, v: bool) {
+        self.textallchat = ::std::option::Option::Some(v);
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_SayText {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_int32()?;
+                    self.ent_idx = ::std::option::Option::Some(tmp);
+                },
+                2 => {
+                    ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.text)?;
+                },
+                3 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_bool()?;
+                    self.chat = ::std::option::Option::Some(tmp);
+                },
+                4 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_bool()?;
+                    self.textallchat = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(v) = self.ent_idx {
+            my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
+        }
+        if let Some(ref v) = self.text.as_ref() {
+            my_size += ::protobuf::rt::string_size(2, &v);
+        }
+        if let Some(v) = self.chat {
+            my_size += 2;
+        }
+        if let Some(v) = self.textallchat {
+            my_size += 2;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.ent_idx {
+            os.write_int32(1, v)?;
+        }
+        if let Some(ref v) = self.text.as_ref() {
+            os.write_string(2, &v)?;
+        }
+        if let Some(v) = self.chat {
+            os.write_bool(3, v)?;
+        }
+        if let Some(v) = self.textallchat {
+            os.write_bool(4, v)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_SayText {
+        CCSUsrMsg_SayText::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeInt32>(
+                "ent_idx",
+                |m: &CCSUsrMsg_SayText| { &m.ent_idx },
+                |m: &mut CCSUsrMsg_SayText| { &mut m.ent_idx },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "text",
+                |m: &CCSUsrMsg_SayText| { &m.text },
+                |m: &mut CCSUsrMsg_SayText| { &mut m.text },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeBool>(
+                "chat",
+                |m: &CCSUsrMsg_SayText| { &m.chat },
+                |m: &mut CCSUsrMsg_SayText| { &mut m.chat },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeBool>(
+                "textallchat",
+                |m: &CCSUsrMsg_SayText| { &m.textallchat },
+                |m: &mut CCSUsrMsg_SayText| { &mut m.textallchat },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_SayText>(
+                "CCSUsrMsg_SayText",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_SayText {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_SayText> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_SayText::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_SayText {
+    fn clear(&mut self) {
+        self.ent_idx = ::std::option::Option::None;
+        self.text.clear();
+        self.chat = ::std::option::Option::None;
+        self.textallchat = ::std::option::Option::None;
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_SayText {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_SayText {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_SayText2 {
+    // message fields
+    ent_idx: ::std::option::Option<i32>,
+    chat: ::std::option::Option<bool>,
+    msg_name: ::protobuf::SingularField<::std::string::String>,
+    pub params: ::protobuf::RepeatedField<::std::string::String>,
+    textallchat: ::std::option::Option<bool>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_SayText2 {
+    fn default() -> &'a CCSUsrMsg_SayText2 {
+        <CCSUsrMsg_SayText2 as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_SayText2 {
+    pub fn new() -> CCSUsrMsg_SayText2 {
+        ::std::default::Default::default()
+    }
+
+    // optional int32 ent_idx = 1;
+
+
+    pub fn get_ent_idx(&self) -> i32 {
+        self.ent_idx.unwrap_or(0)
+    }
+    pub fn clear_ent_idx(&mut self) {
+        self.ent_idx = ::std::option::Option::None;
+    }
+
+    pub fn has_ent_idx(&self) -> bool {
+        self.ent_idx.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ent_idx(&mut self, v: i32) {
+        self.ent_idx = ::std::option::Option::Some(v);
+    }
+
+    // optional bool chat = 2;
+
+
+    pub fn get_chat(&self) -> bool {
+        self.chat.unwrap_or(false)
+    }
+    pub fn clear_chat(&mut self) {
+        self.chat = ::std::option::Option::None;
+    }
+
+    pub fn has_chat(&self) -> bool {
+        self.chat.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_chat(&mut self, v: bool) {
+        self.chat = ::std::option::Option::Some(v);
+    }
+
+    // optional string msg_name = 3;
+
+
+    pub fn get_msg_name(&self) -> &str {
+        match self.msg_name.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+    pub fn clear_msg_name(&mut self) {
+        self.msg_name.clear();
+    }
+
+    pub fn has_msg_name(&self) -> bool {
+        self.msg_name.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_msg_name(&mut self, v: ::std::string::String) {
+        self.msg_name = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_msg_name(&mut self) -> &mut ::std::string::String {
+        if self.msg_name.is_none() {
+            self.msg_name.set_default();
+        }
+        self.msg_name.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_msg_name(&mut self) -> ::std::string::String {
+        self.msg_name.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    // repeated string params = 4;
+
+
+    pub fn get_params(&self) -> &[::std::string::String] {
+        &self.params
+    }
+    pub fn clear_params(&mut self) {
+        self.params.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_params(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.params = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_params(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.params
+    }
+
+    // Take field
+    pub fn take_params(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.params, ::protobuf::RepeatedField::new())
+    }
+
+    // optional bool textallchat = 5;
+
+
+    pub fn get_textallchat(&self) -> bool {
+        self.textallchat.unwrap_or(false)
+    }
+    pub fn clear_textallchat(&mut self) {
+        self.textallchat = ::std::option::Option::None;
+    }
+
+    pub fn has_textallchat(&self) -> bool {
+        self.textallchat.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_textallchat(&mut self, v: bool) {
+        self.textallchat = ::std::option::Option::Some(v);
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_SayText2 {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_int32()?;
+                    self.ent_idx = ::std::option::Option::Some(tmp);
+                },
+                2 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_bool()?;
+                    self.chat = ::std::option::Option::Some(tmp);
+                },
+                3 => {
+                    ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.msg_name)?;
+                },
+                4 => {
+                    ::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.params)?;
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_bool()?;
+                    self.textallchat = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(v) = self.ent_idx {
+            my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
+        }
+        if let Some(v) = self.chat {
+            my_size += 2;
+        }
+        if let Some(ref v) = self.msg_name.as_ref() {
+            my_size += ::protobuf::rt::string_size(3, &v);
+        }
+        for value in &self.params {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        if let Some(v) = self.textallchat {
+            my_size += 2;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.ent_idx {
+            os.write_int32(1, v)?;
+        }
+        if let Some(v) = self.chat {
+            os.write_bool(2, v)?;
+        }
+        if let Some(ref v) = self.msg_name.as_ref() {
+            os.write_string(3, &v)?;
+        }
+        for v in &self.params {
+            os.write_string(4, &v)?;
+        };
+        if let Some(v) = self.textallchat {
+            os.write_bool(5, v)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_SayText2 {
+        CCSUsrMsg_SayText2::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeInt32>(
+                "ent_idx",
+                |m: &CCSUsrMsg_SayText2| { &m.ent_idx },
+                |m: &mut CCSUsrMsg_SayText2| { &mut m.ent_idx },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeBool>(
+                "chat",
+                |m: &CCSUsrMsg_SayText2| { &m.chat },
+                |m: &mut CCSUsrMsg_SayText2| { &mut m.chat },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "msg_name",
+                |m: &CCSUsrMsg_SayText2| { &m.msg_name },
+                |m: &mut CCSUsrMsg_SayText2| { &mut m.msg_name },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_repeated_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "params",
+                |m: &CCSUsrMsg_SayText2| { &m.params },
+                |m: &mut CCSUsrMsg_SayText2| { &mut m.params },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeBool>(
+                "textallchat",
+                |m: &CCSUsrMsg_SayText2| { &m.textallchat },
+                |m: &mut CCSUsrMsg_SayText2| { &mut m.textallchat },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_SayText2>(
+                "CCSUsrMsg_SayText2",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_SayText2 {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_SayText2> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_SayText2::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_SayText2 {
+    fn clear(&mut self) {
+        self.ent_idx = ::std::option::Option::None;
+        self.chat = ::std::option::Option::None;
+        self.msg_name.clear();
+        self.params.clear();
+        self.textallchat = ::std::option::Option::None;
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_SayText2 {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_SayText2 {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_TextMsg {
+    // message fields
+    msg_dst: ::std::option::Option<i32>,
+    pub params: ::protobuf::RepeatedField<::std::string::String>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_TextMsg {
+    fn default() -> &'a CCSUsrMsg_TextMsg {
+        <CCSUsrMsg_TextMsg as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_TextMsg {
+    pub fn new() -> CCSUsrMsg_TextMsg {
+        ::std::default::Default::default()
+    }
+
+    // optional int32 msg_dst = 1;
+
+
+    pub fn get_msg_dst(&self) -> i32 {
+        self.msg_dst.unwrap_or(0)
+    }
+    pub fn clear_msg_dst(&mut self) {
+        self.msg_dst = ::std::option::Option::None;
+    }
+
+    pub fn has_msg_dst(&self) -> bool {
+        self.msg_dst.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_msg_dst(&mut self, v: i32) {
+        self.msg_dst = ::std::option::Option::Some(v);
+    }
+
+    // repeated string params = 3;
+
+
+    pub fn get_params(&self) -> &[::std::string::String] {
+        &self.params
+    }
+    pub fn clear_params(&mut self) {
+        self.params.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_params(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.params = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_params(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.params
+    }
+
+    // Take field
+    pub fn take_params(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.params, ::protobuf::RepeatedField::new())
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_TextMsg {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_int32()?;
+                    self.msg_dst = ::std::option::Option::Some(tmp);
+                },
+                3 => {
+                    ::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.params)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(v) = self.msg_dst {
+            my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
+        }
+        for value in &self.params {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.msg_dst {
+            os.write_int32(1, v)?;
+        }
+        for v in &self.params {
+            os.write_string(3, &v)?;
+        };
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_TextMsg {
+        CCSUsrMsg_TextMsg::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeInt32>(
+                "msg_dst",
+                |m: &CCSUsrMsg_TextMsg| { &m.msg_dst },
+                |m: &mut CCSUsrMsg_TextMsg| { &mut m.msg_dst },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_repeated_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "params",
+                |m: &CCSUsrMsg_TextMsg| { &m.params },
+                |m: &mut CCSUsrMsg_TextMsg| { &mut m.params },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_TextMsg>(
+                "CCSUsrMsg_TextMsg",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_TextMsg {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_TextMsg> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_TextMsg::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_TextMsg {
+    fn clear(&mut self) {
+        self.msg_dst = ::std::option::Option::None;
+        self.params.clear();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_TextMsg {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_TextMsg {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_HintText {
+    // message fields
+    text: ::protobuf::SingularField<::std::string::String>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_HintText {
+    fn default() -> &'a CCSUsrMsg_HintText {
+        <CCSUsrMsg_HintText as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_HintText {
+    pub fn new() -> CCSUsrMsg_HintText {
+        ::std::default::Default::default()
+    }
+
+    // optional string text = 1;
+
+
+    pub fn get_text(&self) -> &str {
+        match self.text.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+    pub fn clear_text(&mut self) {
+        self.text.clear();
+    }
+
+    pub fn has_text(&self) -> bool {
+        self.text.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_text(&mut self, v: ::std::string::String) {
+        self.text = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_text(&mut self) -> &mut ::std::string::String {
+        if self.text.is_none() {
+            self.text.set_default();
+        }
+        self.text.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_text(&mut self) -> ::std::string::String {
+        self.text.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_HintText {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.text)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(ref v) = self.text.as_ref() {
+            my_size += ::protobuf::rt::string_size(1, &v);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(ref v) = self.text.as_ref() {
+            os.write_string(1, &v)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_HintText {
+        CCSUsrMsg_HintText::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "text",
+                |m: &CCSUsrMsg_HintText| { &m.text },
+                |m: &mut CCSUsrMsg_HintText| { &mut m.text },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_HintText>(
+                "CCSUsrMsg_HintText",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_HintText {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_HintText> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_HintText::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_HintText {
+    fn clear(&mut self) {
+        self.text.clear();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_HintText {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_HintText {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(PartialEq,Clone,Default)]
+pub struct CCSUsrMsg_KeyHintText {
+    // message fields
+    pub hints: ::protobuf::RepeatedField<::std::string::String>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a CCSUsrMsg_KeyHintText {
+    fn default() -> &'a CCSUsrMsg_KeyHintText {
+        <CCSUsrMsg_KeyHintText as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl CCSUsrMsg_KeyHintText {
+    pub fn new() -> CCSUsrMsg_KeyHintText {
+        ::std::default::Default::default()
+    }
+
+    // repeated string hints = 1;
+
+
+    pub fn get_hints(&self) -> &[::std::string::String] {
+        &self.hints
+    }
+    pub fn clear_hints(&mut self) {
+        self.hints.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_hints(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.hints = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_hints(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.hints
+    }
+
+    // Take field
+    pub fn take_hints(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.hints, ::protobuf::RepeatedField::new())
+    }
+}
+
+impl ::protobuf::Message for CCSUsrMsg_KeyHintText {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    ::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.hints)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in &self.hints {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        for v in &self.hints {
+            os.write_string(1, &v)?;
+        };
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> CCSUsrMsg_KeyHintText {
+        CCSUsrMsg_KeyHintText::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_repeated_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "hints",
+                |m: &CCSUsrMsg_KeyHintText| { &m.hints },
+                |m: &mut CCSUsrMsg_KeyHintText| { &mut m.hints },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<CCSUsrMsg_KeyHintText>(
+                "CCSUsrMsg_KeyHintText",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static CCSUsrMsg_KeyHintText {
+        static instance: ::protobuf::rt::LazyV2<CCSUsrMsg_KeyHintText> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(CCSUsrMsg_KeyHintText::new)
+    }
+}
+
+impl ::protobuf::Clear for CCSUsrMsg_KeyHintText {
+    fn clear(&mut self) {
+        self.hints.clear();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for CCSUsrMsg_KeyHintText {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for CCSUsrMsg_KeyHintText {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum ECstrike15UserMessages {
+    CS_UM_VGUIMenu = 1,
+    CS_UM_Geiger = 2,
+    CS_UM_Train = 3,
+    CS_UM_HudText = 4,
+    CS_UM_SayText = 5,
+    CS_UM_SayText2 = 6,
+    CS_UM_TextMsg = 7,
+    CS_UM_HudMsg = 8,
+    CS_UM_ResetHud = 9,
+    CS_UM_GameTitle = 10,
+    CS_UM_Shake = 12,
+    CS_UM_Fade = 13,
+    CS_UM_Rumble = 14,
+    CS_UM_CloseCaption = 15,
+    CS_UM_CloseCaptionDirect = 16,
+    CS_UM_SendAudio = 17,
+    CS_UM_RawAudio = 18,
+    CS_UM_VoiceMask = 19,
+    CS_UM_RequestState = 20,
+    CS_UM_Damage = 21,
+    CS_UM_RadioText = 22,
+    CS_UM_HintText = 23,
+    CS_UM_KeyHintText = 24,
+}
+
+impl ::protobuf::ProtobufEnum for ECstrike15UserMessages {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<ECstrike15UserMessages> {
+        match value {
+            1 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_VGUIMenu),
+            2 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Geiger),
+            3 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Train),
+            4 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_HudText),
+            5 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_SayText),
+            6 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_SayText2),
+            7 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_TextMsg),
+            8 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_HudMsg),
+            9 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_ResetHud),
+            10 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_GameTitle),
+            12 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Shake),
+            13 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Fade),
+            14 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Rumble),
+            15 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_CloseCaption),
+            16 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_CloseCaptionDirect),
+            17 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_SendAudio),
+            18 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_RawAudio),
+            19 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_VoiceMask),
+            20 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_RequestState),
+            21 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_Damage),
+            22 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_RadioText),
+            23 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_HintText),
+            24 => ::std::option::Option::Some(ECstrike15UserMessages::CS_UM_KeyHintText),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [ECstrike15UserMessages] = &[
+            ECstrike15UserMessages::CS_UM_VGUIMenu,
+            ECstrike15UserMessages::CS_UM_Geiger,
+            ECstrike15UserMessages::CS_UM_Train,
+            ECstrike15UserMessages::CS_UM_HudText,
+            ECstrike15UserMessages::CS_UM_SayText,
+            ECstrike15UserMessages::CS_UM_SayText2,
+            ECstrike15UserMessages::CS_UM_TextMsg,
+            ECstrike15UserMessages::CS_UM_HudMsg,
+            ECstrike15UserMessages::CS_UM_ResetHud,
+            ECstrike15UserMessages::CS_UM_GameTitle,
+            ECstrike15UserMessages::CS_UM_Shake,
+            ECstrike15UserMessages::CS_UM_Fade,
+            ECstrike15UserMessages::CS_UM_Rumble,
+            ECstrike15UserMessages::CS_UM_CloseCaption,
+            ECstrike15UserMessages::CS_UM_CloseCaptionDirect,
+            ECstrike15UserMessages::CS_UM_SendAudio,
+            ECstrike15UserMessages::CS_UM_RawAudio,
+            ECstrike15UserMessages::CS_UM_VoiceMask,
+            ECstrike15UserMessages::CS_UM_RequestState,
+            ECstrike15UserMessages::CS_UM_Damage,
+            ECstrike15UserMessages::CS_UM_RadioText,
+            ECstrike15UserMessages::CS_UM_HintText,
+            ECstrike15UserMessages::CS_UM_KeyHintText,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static() -> &'static ::protobuf::reflect::EnumDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::EnumDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            ::protobuf::reflect::EnumDescriptor::new_pb_name::<ECstrike15UserMessages>("ECstrike15UserMessages", file_descriptor_proto())
+        })
+    }
+}
+
+impl ::std::marker::Copy for ECstrike15UserMessages {
+}
+
+// Note, `Default` is implemented although default value is not 0
+impl ::std::default::Default for ECstrike15UserMessages {
+    fn default() -> Self {
+        ECstrike15UserMessages::CS_UM_VGUIMenu
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ECstrike15UserMessages {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Enum(::protobuf::ProtobufEnum::descriptor(self))
+    }
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x1ccstrike15_usermessages.proto\"+\n\x11CCSUsrMsg_HudText\x12\x14\n\
+    \x04text\x18\x01\x20\x01(\tR\x04textB\0:\0\"\x80\x01\n\x11CCSUsrMsg_SayT\
+    ext\x12\x19\n\x07ent_idx\x18\x01\x20\x01(\x05R\x06entIdxB\0\x12\x14\n\
+    \x04text\x18\x02\x20\x01(\tR\x04textB\0\x12\x14\n\x04chat\x18\x03\x20\
+    \x01(\x08R\x04chatB\0\x12\"\n\x0btextallchat\x18\x04\x20\x01(\x08R\x0bte\
+    xtallchatB\0:\0\"\xa2\x01\n\x12CCSUsrMsg_SayText2\x12\x19\n\x07ent_idx\
+    \x18\x01\x20\x01(\x05R\x06entIdxB\0\x12\x14\n\x04chat\x18\x02\x20\x01(\
+    \x08R\x04chatB\0\x12\x1b\n\x08msg_name\x18\x03\x20\x01(\tR\x07msgNameB\0\
+    \x12\x18\n\x06params\x18\x04\x20\x03(\tR\x06paramsB\0\x12\"\n\x0btextall\
+    chat\x18\x05\x20\x01(\x08R\x0btextallchatB\0:\0\"J\n\x11CCSUsrMsg_TextMs\
+    g\x12\x19\n\x07msg_dst\x18\x01\x20\x01(\x05R\x06msgDstB\0\x12\x18\n\x06p\
+    arams\x18\x03\x20\x03(\tR\x06paramsB\0:\0\",\n\x12CCSUsrMsg_HintText\x12\
+    \x14\n\x04text\x18\x01\x20\x01(\tR\x04textB\0:\0\"1\n\x15CCSUsrMsg_KeyHi\
+    ntText\x12\x16\n\x05hints\x18\x01\x20\x03(\tR\x05hintsB\0:\0*\xea\x03\n\
+    \x16ECstrike15UserMessages\x12\x12\n\x0eCS_UM_VGUIMenu\x10\x01\x12\x10\n\
+    \x0cCS_UM_Geiger\x10\x02\x12\x0f\n\x0bCS_UM_Train\x10\x03\x12\x11\n\rCS_\
+    UM_HudText\x10\x04\x12\x11\n\rCS_UM_SayText\x10\x05\x12\x12\n\x0eCS_UM_S\
+    ayText2\x10\x06\x12\x11\n\rCS_UM_TextMsg\x10\x07\x12\x10\n\x0cCS_UM_HudM\
+    sg\x10\x08\x12\x12\n\x0eCS_UM_ResetHud\x10\t\x12\x13\n\x0fCS_UM_GameTitl\
+    e\x10\n\x12\x0f\n\x0bCS_UM_Shake\x10\x0c\x12\x0e\n\nCS_UM_Fade\x10\r\x12\
+    \x10\n\x0cCS_UM_Rumble\x10\x0e\x12\x16\n\x12CS_UM_CloseCaption\x10\x0f\
+    \x12\x1c\n\x18CS_UM_CloseCaptionDirect\x10\x10\x12\x13\n\x0fCS_UM_SendAu\
+    dio\x10\x11\x12\x12\n\x0eCS_UM_RawAudio\x10\x12\x12\x13\n\x0fCS_UM_Voice\
+    Mask\x10\x13\x12\x16\n\x12CS_UM_RequestState\x10\x14\x12\x10\n\x0cCS_UM_\
+    Damage\x10\x15\x12\x13\n\x0fCS_UM_RadioText\x10\x16\x12\x12\n\x0eCS_UM_H\
+    intText\x10\x17\x12\x15\n\x11CS_UM_KeyHintText\x10\x18\x1a\0B\0b\x06prot\
+    o2\
+";
+
+static file_descriptor_proto_lazy: ::protobuf::rt::LazyV2<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::LazyV2::INIT;
+
+fn parse_descriptor_proto() -> ::protobuf::descriptor::FileDescriptorProto {
+    ::protobuf::Message::parse_from_bytes(file_descriptor_proto_data).unwrap()
+}
+
+pub fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    file_descriptor_proto_lazy.get(|| {
+        parse_descriptor_proto()
+    })
+}
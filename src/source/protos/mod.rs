@@ -1,2 +1,3 @@
 pub mod netmessages;
-pub use netmessages::*;
\ No newline at end of file
+pub use netmessages::*;
+pub mod cstrike15_usermessages;
\ No newline at end of file
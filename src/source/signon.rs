@@ -0,0 +1,160 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use crate::source::netmessages::NetMessage;
+use crate::source::protos::CNETMsg_SignonState;
+use crate::source::NetDatagram;
+
+/// signon state once the client is fully connected and in game
+pub const SIGNONSTATE_FULL: i32 = 6;
+
+/// Errors raised while progressing through signon
+#[derive(Debug)]
+pub enum SignonError
+{
+    /// The server stopped progressing the signon state, even after resending
+    Stuck { state: i32 },
+}
+
+impl fmt::Display for SignonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self
+        {
+            SignonError::Stuck { state } => write!(f, "Signon stalled at state {}", state),
+        }
+    }
+}
+
+impl std::error::Error for SignonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Keeps track of signon progress so lost signon messages can be resent
+///
+/// Every signon message sent to the server should be passed to `sent`, and every datagram
+/// received passed to `observe`. `poll` then says when the last message should be sent again.
+pub struct SignonTracker
+{
+    /// the latest signon state the server has told us about
+    state: i32,
+
+    /// the last signon message sent (id, encoded message), resent if nothing happens
+    last_sent: Option<(i32, Vec<u8>)>,
+
+    /// when the server last made progress (or we last sent something)
+    last_progress: Instant,
+
+    /// how long to wait for progress before resending
+    timeout: Duration,
+
+    /// number of times the last message has been resent without progress
+    retries: u32,
+
+    /// number of resends allowed before giving up
+    max_retries: u32,
+}
+
+impl SignonTracker
+{
+    /// track signon, resending after `timeout` with no progress up to `max_retries` times
+    pub fn new(timeout: Duration, max_retries: u32) -> Self
+    {
+        SignonTracker {
+            state: 0,
+            last_sent: None,
+            last_progress: Instant::now(),
+            timeout,
+            retries: 0,
+            max_retries,
+        }
+    }
+
+    /// the latest signon state the server has told us about
+    pub fn get_state(&self) -> i32
+    {
+        self.state
+    }
+
+    /// true once signon has completed
+    pub fn is_complete(&self) -> bool
+    {
+        self.state >= SIGNONSTATE_FULL
+    }
+
+    /// remember a signon message that was just sent, so it can be resent if it gets lost
+    pub fn sent(&mut self, message: &NetMessage) -> Result<()>
+    {
+        let mut encoded = Vec::new();
+        message.inner().write_to_vec(&mut encoded)?;
+
+        self.last_sent = Some((message.get_id(), encoded));
+        self.last_progress = Instant::now();
+        self.retries = 0;
+
+        Ok(())
+    }
+
+    /// look for signon progress from the server in a received datagram
+    pub fn observe(&mut self, datagram: &NetDatagram)
+    {
+        let messages = match datagram.get_messages() {
+            Some(messages) => messages,
+            None => return,
+        };
+
+        for message in messages.iter() {
+            if let Some(signon) = message.downcast::<CNETMsg_SignonState>() {
+                if signon.get_signon_state() as i32 != self.state {
+                    self.state = signon.get_signon_state() as i32;
+                    self.last_progress = Instant::now();
+                    self.retries = 0;
+                }
+            }
+        }
+    }
+
+    /// check whether the last signon message needs to be resent, returns the message to send
+    /// again if so. errors with `SignonError::Stuck` once the retry budget is used up
+    pub fn poll(&mut self) -> Result<Option<NetMessage>>
+    {
+        if self.is_complete() || self.last_progress.elapsed() < self.timeout {
+            return Ok(None);
+        }
+
+        let (id, encoded) = match &self.last_sent {
+            Some(last_sent) => last_sent,
+            None => return Ok(None),
+        };
+
+        if self.retries >= self.max_retries {
+            return Err(SignonError::Stuck { state: self.state }.into());
+        }
+
+        self.retries += 1;
+        self.last_progress = Instant::now();
+
+        Ok(Some(NetMessage::bind(*id, encoded)?))
+    }
+}
+
+#[test]
+fn test_signon_resend() {
+    use crate::source::protos::NET_Messages;
+
+    let mut tracker = SignonTracker::new(Duration::from_millis(0), 2);
+
+    let mut signon = CNETMsg_SignonState::new();
+    signon.set_signon_state(2);
+    tracker.sent(&NetMessage::from_proto(Box::new(signon), NET_Messages::net_SignonState as i32)).unwrap();
+
+    // no progress, so the message is resent until the budget runs out
+    for _i in 0..2 {
+        let resend = tracker.poll().unwrap().expect("signon message should be resent");
+        assert_eq!(resend.downcast::<CNETMsg_SignonState>().unwrap().get_signon_state(), 2);
+    }
+
+    let err = tracker.poll().err().unwrap();
+    assert!(matches!(err.downcast_ref::<SignonError>(), Some(SignonError::Stuck { state: 0 })));
+}
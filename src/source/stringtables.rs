@@ -0,0 +1,474 @@
+use bitstream_io::{BitReader, LittleEndian};
+use anyhow::Result;
+use crate::source::bitbuf::WireReader;
+use crate::source::lzss::Lzss;
+use crate::source::netmessages::NetMessage;
+use crate::source::protos::{CSVCMsg_CreateStringTable, CSVCMsg_UpdateStringTable};
+
+// number of recently read strings that a new entry can take a prefix from
+const STRING_HISTORY_SIZE: usize = 32;
+
+// bit widths of the history index and prefix length in a substring reference
+const SUBSTRING_BITS: u32 = 5;
+
+// bit width of the byte count of variable sized user data
+const MAX_USERDATA_BITS: u32 = 14;
+
+// a single string in a string table, along with the data the server attached to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringTableEntry
+{
+    pub string: String,
+    pub user_data: Vec<u8>,
+}
+
+// a string table shared by the server (e.g. "userinfo", "instancebaseline")
+pub struct StringTable
+{
+    // name of the table
+    name: String,
+
+    // the most entries this table can hold, decides the bit width of entry indexes
+    max_entries: usize,
+
+    // whether every entry's user data is the same size
+    user_data_fixed_size: bool,
+
+    // size of fixed size user data in bytes and bits
+    user_data_size: usize,
+    user_data_size_bits: u32,
+
+    // current entries, by index
+    entries: Vec<StringTableEntry>,
+}
+
+impl StringTable
+{
+    // create an empty table from the description in svc_CreateStringTable
+    fn new(msg: &CSVCMsg_CreateStringTable) -> Result<Self>
+    {
+        if msg.get_max_entries() <= 0 {
+            return Err(anyhow::anyhow!("String table {} has no room for entries", msg.get_name()));
+        }
+
+        Ok(StringTable {
+            name: msg.get_name().to_string(),
+            max_entries: msg.get_max_entries() as usize,
+            user_data_fixed_size: msg.get_user_data_fixed_size(),
+            user_data_size: msg.get_user_data_size() as usize,
+            user_data_size_bits: msg.get_user_data_size_bits() as u32,
+            entries: Vec::new(),
+        })
+    }
+
+    // name of the table
+    pub fn get_name(&self) -> &str
+    {
+        return &self.name;
+    }
+
+    // every entry currently in the table, by index
+    pub fn entries(&self) -> &[StringTableEntry]
+    {
+        return &self.entries;
+    }
+
+    // get the entry at an index
+    pub fn get(&self, index: usize) -> Option<&StringTableEntry>
+    {
+        return self.entries.get(index);
+    }
+
+    // find the entry with a particular string
+    pub fn find(&self, string: &str) -> Option<&StringTableEntry>
+    {
+        return self.entries.iter().find(|entry| entry.string == string);
+    }
+
+    // number of bits used to send an entry index
+    fn entry_bits(&self) -> u32
+    {
+        // floor(log2(max_entries))
+        return (usize::BITS - 1) - self.max_entries.leading_zeros();
+    }
+
+    // apply `num_entries` new or changed entries from a table's string data
+    fn apply_string_data(&mut self, data: &[u8], num_entries: i32) -> Result<()>
+    {
+        let mut reader = BitReader::endian(std::io::Cursor::new(data), LittleEndian);
+
+        // dictionary encoding is never used by servers we talk to
+        if reader.read_bit()? {
+            return Err(anyhow::anyhow!("Dictionary encoded string table {} not supported", self.name));
+        }
+
+        let entry_bits = self.entry_bits();
+        let mut history: Vec<String> = Vec::with_capacity(STRING_HISTORY_SIZE);
+        let mut last_index: i64 = -1;
+
+        for _i in 0..num_entries
+        {
+            // entries are usually sent in order, otherwise the index is given
+            let index = if reader.read_bit()? {
+                (last_index + 1) as usize
+            } else if entry_bits > 0 {
+                reader.read::<u32>(entry_bits)? as usize
+            } else {
+                0
+            };
+            last_index = index as i64;
+
+            if index >= self.max_entries {
+                return Err(anyhow::anyhow!("String table {} entry {} out of range", self.name, index));
+            }
+
+            // the string, which may start with part of a recently read one
+            let mut string: Option<String> = None;
+            if reader.read_bit()?
+            {
+                let mut value = String::new();
+
+                if reader.read_bit()?
+                {
+                    let history_index = reader.read::<u32>(SUBSTRING_BITS)? as usize;
+                    let prefix_len = reader.read::<u32>(SUBSTRING_BITS)? as usize;
+
+                    let previous = history.get(history_index)
+                        .ok_or(anyhow::anyhow!("String table {} substring of missing entry", self.name))?;
+                    value.push_str(previous.get(..prefix_len)
+                        .ok_or(anyhow::anyhow!("String table {} substring out of range", self.name))?);
+                }
+
+                value.push_str(&reader.read_string()?);
+                string = Some(value);
+            }
+
+            // user data attached to the entry
+            let mut user_data: Option<Vec<u8>> = None;
+            if reader.read_bit()?
+            {
+                if self.user_data_fixed_size {
+                    user_data = Some(self.read_fixed_user_data(&mut reader)?);
+                } else {
+                    let size = reader.read::<u32>(MAX_USERDATA_BITS)? as usize;
+                    user_data = Some(reader.read_bytes_vec(size)?);
+                }
+            }
+
+            // keep the last few strings to take prefixes from. an existing entry sent without
+            // a string still goes in the history as the string it already has
+            if history.len() == STRING_HISTORY_SIZE {
+                history.remove(0);
+            }
+            history.push(match &string {
+                Some(string) => string.clone(),
+                None if index < self.entries.len() => self.entries[index].string.clone(),
+                None => String::new(),
+            });
+
+            if index < self.entries.len()
+            {
+                // changed entry, only the user data can change
+                if let Some(user_data) = user_data {
+                    self.entries[index].user_data = user_data;
+                }
+            }
+            else if index == self.entries.len()
+            {
+                self.entries.push(StringTableEntry {
+                    string: string.unwrap_or_default(),
+                    user_data: user_data.unwrap_or_default(),
+                });
+            }
+            else
+            {
+                return Err(anyhow::anyhow!("String table {} skipped from entry {} to {}", self.name, self.entries.len(), index));
+            }
+        }
+
+        Ok(())
+    }
+
+    // read user data which is always user_data_size_bits long
+    fn read_fixed_user_data<T>(&self, reader: &mut BitReader<T, LittleEndian>) -> Result<Vec<u8>>
+        where T: std::io::Read
+    {
+        let mut user_data = vec![0u8; self.user_data_size];
+        let mut bits_left = self.user_data_size_bits;
+
+        for byte in user_data.iter_mut()
+        {
+            if bits_left == 0 {
+                break;
+            }
+
+            let bits = std::cmp::min(bits_left, 8);
+            *byte = reader.read::<u8>(bits)?;
+            bits_left -= bits;
+        }
+
+        Ok(user_data)
+    }
+}
+
+// Keeps every string table the server has created up to date as updates arrive
+//
+// Tables are numbered in the order the server created them, which is how updates refer to them.
+#[derive(Default)]
+pub struct StringTableManager
+{
+    tables: Vec<StringTable>,
+}
+
+impl StringTableManager
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    // create a table from svc_CreateStringTable, along with its initial entries
+    pub fn create(&mut self, msg: &CSVCMsg_CreateStringTable) -> Result<()>
+    {
+        let mut table = StringTable::new(msg)?;
+
+        let decompressed = Self::decompress_string_data(msg.get_string_data())
+            .map_err(|e| anyhow::anyhow!("String table {} data failed to decompress: {}", msg.get_name(), e))?;
+        match decompressed {
+            Some(data) => table.apply_string_data(&data, msg.get_num_entries())?,
+            None => table.apply_string_data(msg.get_string_data(), msg.get_num_entries())?,
+        }
+
+        self.tables.push(table);
+
+        Ok(())
+    }
+
+    // decompress the string data of a new table if it is compressed, None if it isn't.
+    // this version of svc_CreateStringTable has no flag for it, so compressed data is recognised
+    // by the layout the engine writes it in: the uncompressed and compressed sizes, followed by
+    // the LZSS compressed bytes
+    fn decompress_string_data(data: &[u8]) -> Result<Option<Vec<u8>>>
+    {
+        if data.len() < 12 || &data[8..12] != b"LZSS" {
+            return Ok(None);
+        }
+
+        let uncompressed_size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let compressed_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+        let compressed = data[8..].get(..compressed_size)
+            .ok_or(anyhow::anyhow!("compressed size {} is past the end of the data", compressed_size))?;
+
+        let decompressed = Lzss::decode(compressed)?;
+        if decompressed.len() != uncompressed_size {
+            return Err(anyhow::anyhow!("decompressed to {} bytes, expected {}", decompressed.len(), uncompressed_size));
+        }
+
+        Ok(Some(decompressed))
+    }
+
+    // apply the changed entries in svc_UpdateStringTable to the table it refers to
+    pub fn update(&mut self, msg: &CSVCMsg_UpdateStringTable) -> Result<()>
+    {
+        let table = self.tables.get_mut(msg.get_table_id() as usize)
+            .ok_or(anyhow::anyhow!("Update for unknown string table {}", msg.get_table_id()))?;
+
+        table.apply_string_data(msg.get_string_data(), msg.get_num_changed_entries())
+    }
+
+    // create or update a table if this is a string table message, other messages are ignored
+    pub fn handle_message(&mut self, message: &NetMessage) -> Result<()>
+    {
+        if let Some(create) = message.downcast::<CSVCMsg_CreateStringTable>() {
+            self.create(create)?;
+        } else if let Some(update) = message.downcast::<CSVCMsg_UpdateStringTable>() {
+            self.update(update)?;
+        }
+
+        Ok(())
+    }
+
+    // get a table by the id updates refer to it with
+    pub fn get_table(&self, id: usize) -> Option<&StringTable>
+    {
+        return self.tables.get(id);
+    }
+
+    // find a table by name
+    pub fn find_table(&self, name: &str) -> Option<&StringTable>
+    {
+        return self.tables.iter().find(|table| table.name == name);
+    }
+}
+
+// packs bits least significant first, the same way the engine writes them
+#[cfg(test)]
+#[derive(Default)]
+struct TestBits
+{
+    bits: Vec<bool>,
+}
+
+#[cfg(test)]
+impl TestBits
+{
+    fn push(&mut self, value: u32, bits: u32)
+    {
+        for i in 0..bits {
+            self.bits.push((value >> i) & 1 == 1);
+        }
+    }
+
+    fn push_str(&mut self, s: &str)
+    {
+        for byte in s.bytes().chain(std::iter::once(0)) {
+            self.push(byte as u32, 8);
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8>
+    {
+        let mut bytes = vec![0u8; (self.bits.len() + 7) / 8];
+        for (i, bit) in self.bits.iter().enumerate() {
+            if *bit {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+        bytes
+    }
+}
+
+#[test]
+fn test_string_table_updates() {
+    let mut data = TestBits::default();
+    data.push(0, 1); // not dictionary encoded
+
+    // "player0" with two bytes of user data
+    data.push(1, 1);
+    data.push(1, 1);
+    data.push(0, 1);
+    data.push_str("player0");
+    data.push(1, 1);
+    data.push(2, MAX_USERDATA_BITS);
+    data.push(0xAA, 8);
+    data.push(0xBB, 8);
+
+    // "player1", taking "player" from the previous string
+    data.push(1, 1);
+    data.push(1, 1);
+    data.push(1, 1);
+    data.push(0, SUBSTRING_BITS);
+    data.push(6, SUBSTRING_BITS);
+    data.push_str("1");
+    data.push(0, 1);
+
+    let mut create = CSVCMsg_CreateStringTable::new();
+    create.set_name(String::from("userinfo"));
+    create.set_max_entries(8);
+    create.set_num_entries(2);
+    create.set_string_data(data.into_bytes());
+
+    let mut manager = StringTableManager::new();
+    manager.create(&create).unwrap();
+
+    // change the user data of entry 0 and add "bot" at entry 2
+    let mut data = TestBits::default();
+    data.push(0, 1);
+
+    data.push(0, 1);
+    data.push(0, 3);
+    data.push(0, 1);
+    data.push(1, 1);
+    data.push(1, MAX_USERDATA_BITS);
+    data.push(0x09, 8);
+
+    data.push(0, 1);
+    data.push(2, 3);
+    data.push(1, 1);
+    data.push(0, 1);
+    data.push_str("bot");
+    data.push(0, 1);
+
+    let mut update = CSVCMsg_UpdateStringTable::new();
+    update.set_table_id(0);
+    update.set_num_changed_entries(2);
+    update.set_string_data(data.into_bytes());
+    manager.update(&update).unwrap();
+
+    let table = manager.find_table("userinfo").unwrap();
+    assert_eq!(table.entries().len(), 3);
+    assert_eq!(table.get(0).unwrap(), &StringTableEntry { string: String::from("player0"), user_data: vec![0x09] });
+    assert_eq!(table.get(1).unwrap(), &StringTableEntry { string: String::from("player1"), user_data: Vec::new() });
+    assert_eq!(table.find("bot").unwrap().user_data.len(), 0);
+
+    // updates for a table that was never created
+    update.set_table_id(1);
+    assert!(manager.update(&update).is_err());
+
+    // change only the user data of entry 1, then add an entry that takes its prefix from it.
+    // the unchanged entry's string is what goes in the history
+    let mut data = TestBits::default();
+    data.push(0, 1);
+
+    data.push(0, 1);
+    data.push(1, 3);
+    data.push(0, 1);
+    data.push(1, 1);
+    data.push(1, MAX_USERDATA_BITS);
+    data.push(0x01, 8);
+
+    data.push(0, 1);
+    data.push(3, 3);
+    data.push(1, 1);
+    data.push(1, 1);
+    data.push(0, SUBSTRING_BITS);
+    data.push(6, SUBSTRING_BITS);
+    data.push_str("2");
+    data.push(0, 1);
+
+    update.set_table_id(0);
+    update.set_string_data(data.into_bytes());
+    manager.update(&update).unwrap();
+
+    let table = manager.find_table("userinfo").unwrap();
+    assert_eq!(table.get(1).unwrap(), &StringTableEntry { string: String::from("player1"), user_data: vec![0x01] });
+    assert_eq!(table.get(3).unwrap().string, "player2");
+}
+
+#[test]
+fn test_string_table_compressed() {
+    let mut data = TestBits::default();
+    data.push(0, 1);
+    for i in 0..4 {
+        data.push(1, 1);
+        data.push(1, 1);
+        data.push(0, 1);
+        data.push_str(&format!("materials/models/player/{}", i));
+        data.push(0, 1);
+    }
+    let data = data.into_bytes();
+
+    let compressed = Lzss::encode(&data);
+    let mut string_data: Vec<u8> = Vec::new();
+    string_data.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    string_data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    string_data.extend_from_slice(&compressed);
+
+    let mut create = CSVCMsg_CreateStringTable::new();
+    create.set_name(String::from("modelprecache"));
+    create.set_max_entries(8);
+    create.set_num_entries(4);
+    create.set_string_data(string_data);
+
+    let mut manager = StringTableManager::new();
+    manager.create(&create).unwrap();
+    assert_eq!(manager.get_table(0).unwrap().get(3).unwrap().string, "materials/models/player/3");
+
+    // compressed data that doesn't decompress is an error rather than being read as raw string data
+    let mut corrupt = (data.len() as u32).to_le_bytes().to_vec();
+    corrupt.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    corrupt.extend_from_slice(&compressed[..compressed.len() / 2]);
+    create.set_string_data(corrupt);
+    assert!(manager.create(&create).is_err());
+}
@@ -1,12 +1,42 @@
-use bitstream_io::{BitReader, LittleEndian};
+use bitstream_io::{BitReader, BitWriter, LittleEndian};
 use crate::source::bitbuf::WireReader;
 use log::{warn, trace};
 use crate::source::lzss::Lzss;
+use crate::source::channel::CompressionMode;
 use pretty_hex::PrettyHex;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
-const MAX_FILE_SIZE: usize = (1<<26) - 1;
+// the number of streams (message and file) that reliable data is sent over
+pub const MAX_STREAMS: usize = 2;
+
+// the number of subchannels a sender may have in flight, each one gets its own bit
+// in the reliable state. the subchannel index in a datagram only selects which bit to flip,
+// the data itself is always received into the per-stream buffers
+pub const MAX_SUBCHANNELS: usize = 8;
+
+const MAX_FILE_SIZE_BITS: u32 = 26;
+const MAX_FILE_SIZE: usize = (1<<MAX_FILE_SIZE_BITS) - 1;
+
+// single blocks must fit within one datagram payload
+const NET_MAX_PAYLOAD_BITS: u32 = 18;
+const NET_MAX_PAYLOAD: usize = 262144 - 4;
 const FRAGMENT_SIZE: usize = 1<<8;
 
+// the most fragments that can be sent in one datagram, limited by the 3 bit fragment count
+const MAX_FRAGMENTS_PER_PACKET: usize = 7;
+
+// how long to wait on an acknowledgement before there's a round trip time to go on
+const INITIAL_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// never retransmit faster than this, however quick the round trip
+const MIN_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// the subchannel our reliable data is sent on. only one batch is ever in flight at a time,
+// so one subchannel (and one reliable state bit) is all that's needed
+const SEND_SUBCHANNEL: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SubchannelStreamType
 {
     // reliable messages
@@ -48,16 +78,42 @@ struct FileFragments {
 
 // a current in-progress transfer
 pub struct TransferBuffer {
-    // the buffer holding current transfer data
+    // the buffer holding current transfer data, empty if the transfer is being discarded
     buffer: Vec<u8>,
 
+    // the number of bytes being sent over the network
+    size: usize,
+
+    // if set, fragments are read past without being buffered
+    discard: bool,
+
     // the number of fragments in this transfer
     num_fragments: usize,
 
+    // which fragments have been received, a fragment sent again is only counted once
+    received: Vec<bool>,
+
     // number of acknowledged fragments
     num_fragments_ack: usize,
 }
 
+// a snapshot of a transfer which is still being received
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferInfo
+{
+    // which stream the transfer is being sent over
+    pub stream: SubchannelStreamType,
+
+    // name of the file being sent, if this is a file transfer
+    pub filename: Option<String>,
+
+    // total number of bytes being sent over the network (before decompression)
+    pub total_size: usize,
+
+    // number of bytes received so far
+    pub bytes_received: usize,
+}
+
 pub struct SubChannel
 {
     // file information if the payload is a file
@@ -75,10 +131,11 @@ pub struct SubChannel
     // current in-progress transfer
     transfer: Option<TransferBuffer>,
 
-    // contains the reliable state for this SubChannel
-    // reliable state is a bit which flips back and forth acknowledging
-    // transfers as they are received, shifted by the SubChannel index
-    in_reliable_state: bool,
+    // whether completed payloads are kept, a disabled stream is still read past
+    enabled: bool,
+
+    // if set, a transfer which fails to decompress is an error rather than being dropped
+    strict: bool,
 }
 
 impl TransferBuffer {
@@ -95,7 +152,24 @@ impl TransferBuffer {
         let buffer = vec![0; transfer_size];
         return TransferBuffer{
             buffer,
+            size: transfer_size,
+            discard: false,
             num_fragments,
+            received: vec![false; num_fragments],
+            num_fragments_ack: 0,
+        }
+    }
+
+    // create a transfer which is read off the network but never kept, for disabled streams
+    fn discarding(transfer_size: usize) -> Self {
+        let num_fragments = (transfer_size+FRAGMENT_SIZE-1)/(FRAGMENT_SIZE);
+
+        return TransferBuffer{
+            buffer: Vec::new(),
+            size: transfer_size,
+            discard: true,
+            num_fragments,
+            received: vec![false; num_fragments],
             num_fragments_ack: 0,
         }
     }
@@ -109,7 +183,6 @@ impl TransferBuffer {
         let mut total_recv_length: usize = num_fragments * FRAGMENT_SIZE;
         let last_recv_fragment = start_frag+num_fragments;
         let total_fragments_in_payload = self.num_fragments;
-        let mut transfer_complete = false;
 
         trace!("[read_fragments] start_frag: {}, num_fragments: {}", start_frag, num_fragments);
         trace!("[read_fragments] total_recv_length: {}, total_fragments_in_payload: {}", total_recv_length, total_fragments_in_payload);
@@ -119,14 +192,13 @@ impl TransferBuffer {
         {
             // this is the last fragment, adjust the receiving length so that we only receive
             // the bytes of the final fragment that we want to finish this off
-            let final_part = FRAGMENT_SIZE - ( self.buffer.len() % FRAGMENT_SIZE );
+            let final_part = FRAGMENT_SIZE - ( self.size % FRAGMENT_SIZE );
             if final_part < FRAGMENT_SIZE
             {
                 total_recv_length -= final_part;
             }
 
-            transfer_complete = true;
-            trace!("[read_fragments] Completing transfer! (final_part={})", final_part);
+            trace!("[read_fragments] Received final fragment (final_part={})", final_part);
         }
         else if last_recv_fragment > total_fragments_in_payload
         {
@@ -141,16 +213,27 @@ impl TransferBuffer {
 
         trace!("[read_fragments] buffer[start..end] = buffer[{}..{}]", start, start+total_recv_length);
 
-        // receive the bytes on the network
-        reader.read_bytes(&mut self.buffer[start..(start+total_recv_length)])?;
+        // receive the bytes on the network, or just step over them if nobody wants them
+        if self.discard {
+            reader.skip((total_recv_length * 8) as u32)?;
+        } else {
+            reader.read_bytes_into(&mut self.buffer[start..(start+total_recv_length)])?;
+        }
 
-        // acknowledge these packets
-        self.num_fragments_ack += num_fragments;
+        // acknowledge these packets, the sender resends a batch it thinks was lost so any of
+        // them may have been received already
+        for received in self.received[start_frag..last_recv_fragment].iter_mut() {
+            if !*received {
+                *received = true;
+                self.num_fragments_ack += 1;
+            }
+        }
 
         trace!("[read_fragments] Transfer status: [{}/{}]", self.num_fragments_ack, self.num_fragments);
 
-        // have we finished this transfer entirely?
-        if transfer_complete
+        // have we finished this transfer entirely? the final fragment can arrive before an
+        // earlier batch, so it's every fragment that has to be in
+        if self.num_fragments_ack == self.num_fragments
         {
             trace!("Transfer complete.");
             return Ok(true)
@@ -183,11 +266,15 @@ impl TransferBuffer {
         Ok(())
     }
 
+    // number of bytes of the payload received so far
+    fn bytes_received(&self) -> usize
+    {
+        return std::cmp::min(self.num_fragments_ack * FRAGMENT_SIZE, self.size);
+    }
+
     // get the final payload once the transfer is complete
     pub fn unwrap_payload(self) -> Vec<u8>
     {
-        assert_eq!(self.num_fragments, self.num_fragments_ack);
-
         return self.buffer;
     }
 }
@@ -201,10 +288,58 @@ impl SubChannel {
             is_replay: false,
             payload_size: 0,
             transfer: None,
-            in_reliable_state: false,
+            enabled: true,
+            strict: false,
         }
     }
 
+    // forget the transfer in progress, keeping how the stream is configured
+    pub fn reset(&mut self)
+    {
+        self.file = None;
+        self.compressed = None;
+        self.is_replay = false;
+        self.payload_size = 0;
+        self.transfer = None;
+    }
+
+    // fail on a transfer which can't be decompressed instead of dropping it
+    pub fn set_strict(&mut self, strict: bool)
+    {
+        self.strict = strict;
+    }
+
+    // whether a transfer which can't be decompressed is an error
+    pub fn is_strict(&self) -> bool
+    {
+        return self.strict;
+    }
+
+    // turn buffering of this stream's payloads on or off, this takes effect from the next transfer
+    pub fn set_enabled(&mut self, enabled: bool)
+    {
+        self.enabled = enabled;
+    }
+
+    // whether this stream's payloads are buffered and returned
+    pub fn is_enabled(&self) -> bool
+    {
+        return self.enabled;
+    }
+
+    // describe the transfer currently being received on this stream, if there is one
+    pub fn transfer_info(&self, stream: SubchannelStreamType) -> Option<TransferInfo>
+    {
+        let transfer = self.transfer.as_ref()?;
+
+        Some(TransferInfo {
+            stream,
+            filename: self.file.as_ref().map(|file| file.filename.clone()),
+            total_size: self.payload_size,
+            bytes_received: transfer.bytes_received(),
+        })
+    }
+
     // read information about a file fragment
     fn read_file_info<T>(&mut self, reader: &mut BitReader<T, LittleEndian>) -> anyhow::Result<()>
         where T: std::io::Read
@@ -250,7 +385,7 @@ impl SubChannel {
         trace!("compressed: {}", compressed);
 
         if compressed {
-            let uncompressed_size = reader.read::<u32>(26)? as usize;
+            let uncompressed_size = reader.read::<u32>(MAX_FILE_SIZE_BITS)? as usize;
 
             // mark it as compressed and read its uncompressed size
             self.compressed = Some(CompressedFragments {
@@ -264,21 +399,37 @@ impl SubChannel {
     }
 
     // called when a full payload has been received and needs to be processed before returning
-    fn complete_transfer(&mut self) -> anyhow::Result<TransferBuffer>
+    // returns None if the payload was corrupt and has been dropped. a dropped transfer is still
+    // acknowledged, the sender only resends the last batch of a multi-block transfer and would
+    // resend the same corrupt bytes anyway
+    fn complete_transfer(&mut self) -> anyhow::Result<Option<TransferBuffer>>
     {
+        let mut transfer_out = self.transfer.take().unwrap();
+
+        // the engine gives up on a transfer whose fragment count doesn't add up
+        if transfer_out.num_fragments_ack != transfer_out.num_fragments {
+            return Err(anyhow::anyhow!("Too many fragments ({}/{})", transfer_out.num_fragments_ack, transfer_out.num_fragments));
+        }
+
         if let Some(data) = &self.compressed {
             trace!("Fragments were LZSS compressed, decompressing... (uncompressed_size={})", data.uncompressed_size);
 
-            // if this is a compressed payload, decompress it here
-            self.transfer.as_mut().unwrap().decompress_payload(data.uncompressed_size)?;
+            // if this is a compressed payload, decompress it here. unless we're strict a corrupt
+            // one is dropped, losing its data, rather than taking the whole channel down
+            if let Err(e) = transfer_out.decompress_payload(data.uncompressed_size) {
+                if self.strict {
+                    return Err(e);
+                }
+
+                warn!("Dropping subchannel transfer which failed to decompress: {}", e);
+                return Ok(None);
+            }
 
             trace!("Fragments successfully decompressed");
         }
 
-        let transfer_out = self.transfer.take().unwrap();
-
         // return the completed transfer
-        return Ok(transfer_out);
+        return Ok(Some(transfer_out));
     }
     // read all of the SubChannel data for this SubChannel from the network
     // when the transfer is complete, returns Some(TransferBuffer) which contains the completed payload
@@ -305,11 +456,22 @@ impl SubChannel {
             // the number of fragments in this packet
             num_frags = reader.read::<u32>(3)? as usize;
 
+            // the engine never sends an update without any fragments in it, accepting one would
+            // let a transfer be held open without making progress
+            if num_frags == 0
+            {
+                return Err(anyhow::anyhow!("Multi-block update with no fragments (start_frag={})", start_frag));
+            }
+
             trace!("Multi-fragment receive: (start_frag={}, num_frags={})", start_frag, num_frags);
         }
 
         // are we reading from the first packet?
         if start_frag == 0 {
+            // nothing about the previous transfer carries over to this one
+            self.file = None;
+            self.compressed = None;
+
             // if it is a single block
             if single {
                 trace!("Starting new transfer (single block)");
@@ -317,8 +479,19 @@ impl SubChannel {
                 // read if it's compressed
                 self.read_compress_info(reader)?;
 
-                // read the total amount of data being sent
-                self.payload_size = reader.read::<u32>(18)? as usize;
+                // read the total amount of data being sent, for a compressed block this is the
+                // compressed size, the transfer buffer holds the bytes as they are on the wire
+                // and complete_transfer decompresses them to uncompressed_size
+                self.payload_size = reader.read::<u32>(NET_MAX_PAYLOAD_BITS)? as usize;
+
+                // the engine never sends a single block larger than one payload
+                if self.payload_size > NET_MAX_PAYLOAD
+                {
+                    return Err(anyhow::anyhow!("Single block size {} exceeds max payload size!", self.payload_size));
+                }
+
+                // the whole block is contained in this packet
+                num_frags = (self.payload_size+FRAGMENT_SIZE-1)/FRAGMENT_SIZE;
             } else {
                 trace!("Starting new transfer (multi-block)");
 
@@ -329,7 +502,7 @@ impl SubChannel {
                 self.read_compress_info(reader)?;
 
                 // read the total amount of data being sent
-                self.payload_size = reader.read::<u32>(26)? as usize;
+                self.payload_size = reader.read::<u32>(MAX_FILE_SIZE_BITS)? as usize;
             }
 
             trace!("payload_size: {}", self.payload_size);
@@ -357,7 +530,11 @@ impl SubChannel {
                 warn!("Reinitializing transfer buffer due to fragment abort...");
             }
 
-            self.transfer = Some(TransferBuffer::new(self.payload_size));
+            self.transfer = Some(if self.enabled {
+                TransferBuffer::new(self.payload_size)
+            } else {
+                TransferBuffer::discarding(self.payload_size)
+            });
         } else {
             trace!("Continuing existing transfer...");
         }
@@ -370,17 +547,781 @@ impl SubChannel {
         }else if let Some(transfer) = &mut self.transfer {
             // read the actual bytes off the network
             complete = transfer.read_fragments(start_frag, num_frags, reader)?;
-
-            // if we're still here, we received this chunk, flip the reliable state bit to ack
-            // on the other end
-            self.in_reliable_state = !self.in_reliable_state;
         }
 
         // has the full payload been received? if so, return the payload up.
         if complete {
-            return Ok(Some(self.complete_transfer()?));
+            // a discarded transfer has nothing to hand back
+            if self.transfer.as_ref().map_or(false, |transfer| transfer.discard) {
+                trace!("Discarded transfer on disabled stream");
+                self.transfer = None;
+                return Ok(None);
+            }
+
+            return self.complete_transfer();
         }
 
         Ok(None)
     }
-}
\ No newline at end of file
+}
+
+// a payload being sent over a subchannel stream, the reverse of a TransferBuffer
+pub struct OutgoingTransfer {
+    // the bytes sent over the network, LZSS compressed if that saved space
+    buffer: Vec<u8>,
+
+    // size of the payload before compression, if it was compressed
+    uncompressed_size: Option<usize>,
+
+    // the number of fragments in this transfer
+    num_fragments: usize,
+
+    // the first fragment which has not been written yet
+    next_fragment: usize,
+
+    // batches of fragments that have been sent and not acknowledged yet, oldest first
+    in_flight: VecDeque<InFlightBatch>,
+
+    // smoothed round trip time of acknowledged batches, None until one has been acknowledged
+    srtt: Option<Duration>,
+}
+
+// a datagram's worth of fragments waiting for the peer to acknowledge it
+struct InFlightBatch {
+    // the first fragment in the batch
+    start_frag: usize,
+
+    // when the batch was sent
+    sent_at: Instant,
+
+    // when the batch counts as overdue, pushed back each time it's checked on
+    deadline: Instant,
+}
+
+impl OutgoingTransfer {
+    // prepare a payload to be sent, compressing it first if the compression mode asks for it
+    pub fn new(payload: &[u8], compression: CompressionMode) -> anyhow::Result<Self> {
+        if payload.is_empty() || payload.len() > MAX_FILE_SIZE {
+            return Err(anyhow::anyhow!("Invalid subchannel payload size {}", payload.len()));
+        }
+
+        let mut buffer = payload.to_vec();
+        let mut uncompressed_size = None;
+
+        // only keep the compressed payload if it actually saves space
+        if let CompressionMode::Above(threshold) = compression {
+            if payload.len() > threshold {
+                let compressed = Lzss::encode(payload);
+                if compressed.len() < payload.len() {
+                    trace!("Compressed subchannel payload {} -> {} bytes", payload.len(), compressed.len());
+                    buffer = compressed;
+                    uncompressed_size = Some(payload.len());
+                }
+            }
+        }
+
+        let num_fragments = (buffer.len()+FRAGMENT_SIZE-1)/FRAGMENT_SIZE;
+
+        Ok(Self {
+            buffer,
+            uncompressed_size,
+            num_fragments,
+            next_fragment: 0,
+            in_flight: VecDeque::new(),
+            srtt: None,
+        })
+    }
+
+    // whether every fragment has been written and acknowledged by the peer
+    pub fn is_acknowledged(&self) -> bool {
+        return self.is_complete() && self.in_flight.is_empty();
+    }
+
+    // the peer has acknowledged the oldest batch still in flight, i.e. the reliable state bit
+    // of the subchannel it was sent on has flipped. the round trip feeds the retransmit timeout
+    pub fn acknowledge(&mut self, now: Instant) {
+        if let Some(batch) = self.in_flight.pop_front() {
+            let rtt = now.saturating_duration_since(batch.sent_at);
+
+            // the usual 7/8 smoothing, so one slow ack doesn't swing the timeout
+            self.srtt = Some(match self.srtt {
+                Some(srtt) => (srtt * 7 + rtt) / 8,
+                None => rtt,
+            });
+        }
+    }
+
+    // how long a batch may go unacknowledged before it is sent again
+    pub fn retransmit_timeout(&self) -> Duration {
+        match self.srtt {
+            Some(srtt) => std::cmp::max(srtt * 2, MIN_RETRANSMIT_TIMEOUT),
+            None => INITIAL_RETRANSMIT_TIMEOUT,
+        }
+    }
+
+    // check whether the oldest unacknowledged batch has gone past the retransmit timeout. if
+    // it has, its deadline moves on by another timeout and true is returned. it isn't rewound
+    // here: the peer may have the batch and only its ack was lost, and sending it again would
+    // flip the peer's reliable bit back. the channel asks the peer instead, see ReliableSender
+    pub fn check_retransmit(&mut self, now: Instant) -> bool {
+        let timeout = self.retransmit_timeout();
+
+        let oldest = match self.in_flight.front_mut() {
+            Some(oldest) => oldest,
+            None => return false,
+        };
+
+        if now < oldest.deadline {
+            return false;
+        }
+
+        warn!("Subchannel fragments from {} not acknowledged after {:?}", oldest.start_frag, now.saturating_duration_since(oldest.sent_at));
+        oldest.deadline = now + timeout;
+
+        return true;
+    }
+
+    // the batches in flight were lost, rewind to write them again from the oldest one
+    pub fn retransmit(&mut self) {
+        if let Some(oldest) = self.in_flight.front() {
+            self.next_fragment = oldest.start_frag;
+        }
+
+        self.in_flight.clear();
+    }
+
+    // start waiting on an acknowledgement for the batch just written
+    fn push_in_flight(&mut self, start_frag: usize) {
+        let sent_at = Instant::now();
+        let deadline = sent_at + self.retransmit_timeout();
+
+        self.in_flight.push_back(InFlightBatch { start_frag, sent_at, deadline });
+    }
+
+    // whether every fragment has been written
+    pub fn is_complete(&self) -> bool {
+        return self.next_fragment >= self.num_fragments;
+    }
+
+    // whether the payload is being sent compressed
+    pub fn is_compressed(&self) -> bool {
+        return self.uncompressed_size.is_some();
+    }
+
+    // write the compression header, the mirror of SubChannel::read_compress_info
+    fn write_compress_info<T>(&self, writer: &mut BitWriter<T, LittleEndian>) -> anyhow::Result<()>
+        where T: std::io::Write
+    {
+        writer.write_bit(self.uncompressed_size.is_some())?;
+
+        if let Some(uncompressed_size) = self.uncompressed_size {
+            writer.write(MAX_FILE_SIZE_BITS, uncompressed_size as u32)?;
+        }
+
+        Ok(())
+    }
+
+    // write the next datagram's worth of subchannel data for this stream, in the layout
+    // SubChannel::read_subchannel_data expects
+    pub fn write_next<T>(&mut self, writer: &mut BitWriter<T, LittleEndian>) -> anyhow::Result<()>
+        where T: std::io::Write
+    {
+        if self.is_complete() {
+            return Err(anyhow::anyhow!("Subchannel transfer has already been sent"));
+        }
+
+        // a payload that fits in one datagram is sent as a single block
+        let single = self.num_fragments <= MAX_FRAGMENTS_PER_PACKET && self.buffer.len() <= NET_MAX_PAYLOAD;
+        writer.write_bit(!single)?;
+
+        if single {
+            self.write_compress_info(writer)?;
+            writer.write(NET_MAX_PAYLOAD_BITS, self.buffer.len() as u32)?;
+            writer.write_bytes(&self.buffer)?;
+
+            self.push_in_flight(0);
+            self.next_fragment = self.num_fragments;
+            return Ok(());
+        }
+
+        let start_frag = self.next_fragment;
+        let num_frags = std::cmp::min(MAX_FRAGMENTS_PER_PACKET, self.num_fragments - start_frag);
+
+        writer.write(18, start_frag as u32)?;
+        writer.write(3, num_frags as u32)?;
+
+        // the first datagram of a transfer describes the whole payload
+        if start_frag == 0 {
+            // not a file
+            writer.write_bit(false)?;
+
+            self.write_compress_info(writer)?;
+            writer.write(MAX_FILE_SIZE_BITS, self.buffer.len() as u32)?;
+        }
+
+        // the last fragment is only as long as what's left of the payload
+        let start = start_frag * FRAGMENT_SIZE;
+        let end = std::cmp::min((start_frag + num_frags) * FRAGMENT_SIZE, self.buffer.len());
+        writer.write_bytes(&self.buffer[start..end])?;
+
+        self.push_in_flight(start_frag);
+        self.next_fragment += num_frags;
+
+        Ok(())
+    }
+}
+
+// the send side of the reliable streams, payloads waiting to go out over the message stream
+// and the batch of fragments waiting on an acknowledgement
+//
+// like the engine, a batch counts as received once the peer has acknowledged the datagram it
+// was sent in and flipped the subchannel's bit in its reliable state to match ours. if the
+// peer acknowledges the datagram without flipping the bit, the batch was lost
+pub struct ReliableSender {
+    // payloads waiting to be sent, the front one is being sent
+    queue: VecDeque<OutgoingTransfer>,
+
+    // our reliable state, a subchannel's bit flips each time it's given new data to send
+    out_state: u8,
+
+    // sequence number of the datagram the batch in flight was sent in
+    sent_sequence: Option<u32>,
+
+    // whether the next batch written is the lost one again, which doesn't flip the bit
+    resend: bool,
+}
+
+impl ReliableSender {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            out_state: 0,
+            sent_sequence: None,
+            resend: false,
+        }
+    }
+
+    // queue a payload to be sent after any already waiting
+    pub fn queue(&mut self, transfer: OutgoingTransfer) {
+        self.queue.push_back(transfer);
+    }
+
+    // whether every queued payload has been sent and acknowledged
+    pub fn is_idle(&self) -> bool {
+        return self.queue.is_empty();
+    }
+
+    // check the latest datagram from the peer, which acknowledged our sequence `sequence_ack`
+    // with the reliable state `peer_state`, for the fate of the batch in flight
+    pub fn update(&mut self, sequence_ack: u32, peer_state: u8, now: Instant) {
+        let sent_sequence = match self.sent_sequence {
+            Some(sequence) => sequence,
+            None => return,
+        };
+
+        // the peer hasn't seen the datagram the batch went out in yet
+        if sequence_ack < sent_sequence {
+            return;
+        }
+
+        let transfer = match self.queue.front_mut() {
+            Some(transfer) => transfer,
+            None => return,
+        };
+
+        self.sent_sequence = None;
+
+        let bit = 1 << SEND_SUBCHANNEL;
+        if (peer_state & bit) == (self.out_state & bit) {
+            transfer.acknowledge(now);
+
+            if transfer.is_acknowledged() {
+                trace!("Reliable payload fully acknowledged");
+                self.queue.pop_front();
+            }
+        } else {
+            warn!("Reliable fragments in datagram {} were lost, sending them again", sent_sequence);
+
+            transfer.retransmit();
+            self.resend = true;
+        }
+    }
+
+    // whether the batch in flight has gone unanswered past its retransmit timeout. the peer
+    // should then be sent a datagram: acknowledging it says whether the batch arrived, and
+    // if it didn't, update rewinds it to go out again
+    pub fn check_retransmit(&mut self, now: Instant) -> bool {
+        if self.sent_sequence.is_none() {
+            return false;
+        }
+
+        return self.queue.front_mut().map_or(false, |transfer| transfer.check_retransmit(now));
+    }
+
+    // whether the next datagram should carry a batch of reliable data
+    pub fn has_pending(&self) -> bool {
+        return self.sent_sequence.is_none() && self.queue.front().map_or(false, |transfer| !transfer.is_complete());
+    }
+
+    // write the reliable section of the datagram being sent as `sequence`, the mirror of
+    // NetChannel::parse_reliable_data. only call this when has_pending says there's data
+    pub fn write_next<T>(&mut self, writer: &mut BitWriter<T, LittleEndian>, sequence: u32) -> anyhow::Result<()>
+        where T: std::io::Write
+    {
+        let transfer = match self.queue.front_mut() {
+            Some(transfer) => transfer,
+            None => return Err(anyhow::anyhow!("No reliable data to send")),
+        };
+
+        // new data flips the subchannel's bit, the peer flips its own to match once it has it
+        if !self.resend {
+            self.out_state ^= 1 << SEND_SUBCHANNEL;
+        }
+        self.resend = false;
+
+        writer.write(3, SEND_SUBCHANNEL)?;
+
+        // the message stream has data, the file stream never does
+        writer.write_bit(true)?;
+        transfer.write_next(writer)?;
+        writer.write_bit(false)?;
+
+        self.sent_sequence = Some(sequence);
+
+        Ok(())
+    }
+}
+
+// build a single block subchannel payload with the given size field and data
+#[cfg(test)]
+fn single_block(payload_size: u32, data: &[u8]) -> Vec<u8>
+{
+    use bitstream_io::BitWriter;
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+
+        // single block, not compressed
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write(NET_MAX_PAYLOAD_BITS, payload_size).unwrap();
+        writer.write_bytes(data).unwrap();
+
+        // pad out the final byte (20 bits of header)
+        writer.write(4, 0u8).unwrap();
+    }
+
+    buf
+}
+
+#[test]
+fn test_single_block_max_payload() {
+    let data = vec![0xAB; NET_MAX_PAYLOAD];
+    let packet = single_block(NET_MAX_PAYLOAD as u32, &data);
+
+    let mut subchan = SubChannel::new();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&packet), LittleEndian);
+
+    // the largest single block completes in one read
+    let transfer = subchan.read_subchannel_data(&mut reader).unwrap().expect("single block should complete");
+    assert_eq!(transfer.unwrap_payload(), data);
+}
+
+#[test]
+fn test_single_block_over_max_payload() {
+    // largest value the 18 bit size field can hold, which is over the max payload
+    let payload_size = (1 << NET_MAX_PAYLOAD_BITS) - 1;
+    let packet = single_block(payload_size, &[]);
+
+    let mut subchan = SubChannel::new();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&packet), LittleEndian);
+
+    assert!(subchan.read_subchannel_data(&mut reader).is_err());
+}
+
+#[test]
+fn test_transfer_info() {
+    use bitstream_io::BitWriter;
+
+    // first packet of a 1000 byte file sent in 4 fragments
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+
+        // not a single block, starting at fragment 0 with 1 fragment in this packet
+        writer.write_bit(true).unwrap();
+        writer.write(18, 0u32).unwrap();
+        writer.write(3, 1u32).unwrap();
+
+        // a file with a transfer id and name, not a replay
+        writer.write_bit(true).unwrap();
+        writer.write(32, 5u32).unwrap();
+        writer.write_bytes(b"maps/de_dust2.bsp\0").unwrap();
+        writer.write_bit(false).unwrap();
+
+        // not compressed, 1000 bytes total
+        writer.write_bit(false).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, 1000u32).unwrap();
+
+        writer.write_bytes(&[0xAA; FRAGMENT_SIZE]).unwrap();
+
+        // pad out the final byte (227 bits of header, name included)
+        writer.write(5, 0u8).unwrap();
+    }
+
+    let mut subchannel = SubChannel::new();
+    assert!(subchannel.transfer_info(SubchannelStreamType::File).is_none());
+
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert!(subchannel.read_subchannel_data(&mut reader).unwrap().is_none());
+
+    assert_eq!(subchannel.transfer_info(SubchannelStreamType::File), Some(TransferInfo {
+        stream: SubchannelStreamType::File,
+        filename: Some(String::from("maps/de_dust2.bsp")),
+        total_size: 1000,
+        bytes_received: FRAGMENT_SIZE,
+    }));
+}
+
+#[test]
+fn test_zero_fragment_update() {
+    // first packet of a 1000 byte message sent in 4 fragments
+    let mut first: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut first), LittleEndian);
+        writer.write_bit(true).unwrap();
+        writer.write(18, 0u32).unwrap();
+        writer.write(3, 1u32).unwrap();
+
+        // not a file, not compressed, 1000 bytes total
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, 1000u32).unwrap();
+        writer.write_bytes(&[0xAA; FRAGMENT_SIZE]).unwrap();
+
+        // pad out the final byte (50 bits of header)
+        writer.write(6, 0u8).unwrap();
+    }
+
+    // a continuation at fragment 1 carrying no fragments at all
+    let mut empty: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut empty), LittleEndian);
+        writer.write_bit(true).unwrap();
+        writer.write(18, 1u32).unwrap();
+        writer.write(3, 0u32).unwrap();
+        writer.write(2, 0u8).unwrap();
+    }
+
+    let mut subchannel = SubChannel::new();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&first[..]), LittleEndian);
+    assert!(subchannel.read_subchannel_data(&mut reader).unwrap().is_none());
+
+    let mut reader = BitReader::endian(std::io::Cursor::new(&empty[..]), LittleEndian);
+    assert!(subchannel.read_subchannel_data(&mut reader).is_err());
+
+    // the transfer in progress is untouched
+    let info = subchannel.transfer_info(SubchannelStreamType::Message).unwrap();
+    assert_eq!(info.total_size, 1000);
+    assert_eq!(info.bytes_received, FRAGMENT_SIZE);
+}
+
+#[test]
+fn test_outgoing_transfer_compressed() {
+    // compressible payload spanning more fragments than fit in one datagram
+    let payload: Vec<u8> = (0..8000u32).map(|i| (i % 7) as u8).collect();
+
+    for compression in [CompressionMode::Never, CompressionMode::Above(128)].iter() {
+        let mut outgoing = OutgoingTransfer::new(&payload, *compression).unwrap();
+        assert_eq!(outgoing.is_compressed(), *compression != CompressionMode::Never);
+
+        let mut subchannel = SubChannel::new();
+        let mut received = None;
+
+        // each datagram is written and read back on its own, as if sent over the network
+        while !outgoing.is_complete() {
+            let mut buf: Vec<u8> = Vec::new();
+            {
+                let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+                outgoing.write_next(&mut writer).unwrap();
+
+                // pad out the final byte
+                writer.write(7, 0u8).unwrap();
+            }
+
+            assert!(received.is_none());
+            let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+            received = subchannel.read_subchannel_data(&mut reader).unwrap();
+        }
+
+        assert_eq!(received.expect("transfer should complete").unwrap_payload(), payload);
+    }
+}
+
+#[test]
+fn test_repeated_fragments() {
+    let payload: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    let mut outgoing = OutgoingTransfer::new(&payload, CompressionMode::Never).unwrap();
+
+    // every batch of the transfer, each as its own datagram
+    let mut batches: Vec<Vec<u8>> = Vec::new();
+    while !outgoing.is_complete() {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+            outgoing.write_next(&mut writer).unwrap();
+            writer.write(7, 0u8).unwrap();
+        }
+        batches.push(buf);
+    }
+    assert_eq!(batches.len(), 3);
+
+    let mut subchannel = SubChannel::new();
+    let mut read = |buf: &Vec<u8>| {
+        let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+        subchannel.read_subchannel_data(&mut reader).unwrap()
+    };
+
+    // the first batch arrives twice, then the last one ahead of the middle one
+    assert!(read(&batches[0]).is_none());
+    assert!(read(&batches[0]).is_none());
+    assert!(read(&batches[2]).is_none());
+
+    let transfer = read(&batches[1]).expect("transfer should complete once every fragment is in");
+    assert_eq!(transfer.unwrap_payload(), payload);
+}
+
+#[test]
+fn test_single_block_compressed() {
+    use bitstream_io::BitWriter;
+
+    let expected: Vec<u8> = b"reliable netmessages ".iter().cycle().take(2000).cloned().collect();
+    let compressed = Lzss::encode(&expected);
+    assert!(compressed.len() < expected.len());
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+
+        // single block, compressed from 2000 bytes, with the size on the wire being the compressed size
+        writer.write_bit(false).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, expected.len() as u32).unwrap();
+        writer.write(NET_MAX_PAYLOAD_BITS, compressed.len() as u32).unwrap();
+        writer.write_bytes(&compressed).unwrap();
+
+        // pad out the final byte (46 bits of header)
+        writer.write(2, 0u8).unwrap();
+    }
+
+    let mut subchan = SubChannel::new();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+
+    // the buffer holds the compressed bytes until the block completes, then the decompressed ones
+    let transfer = subchan.read_subchannel_data(&mut reader).unwrap().expect("single block should complete");
+    assert_eq!(transfer.unwrap_payload(), expected);
+}
+
+#[test]
+fn test_new_transfer_resets_state() {
+    use bitstream_io::BitWriter;
+
+    let mut subchannel = SubChannel::new();
+
+    // a compressed single block, same as test_single_block_compressed
+    let expected: Vec<u8> = b"reliable netmessages ".iter().cycle().take(2000).cloned().collect();
+    let compressed = Lzss::encode(&expected);
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+        writer.write_bit(false).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, expected.len() as u32).unwrap();
+        writer.write(NET_MAX_PAYLOAD_BITS, compressed.len() as u32).unwrap();
+        writer.write_bytes(&compressed).unwrap();
+        writer.write(2, 0u8).unwrap();
+    }
+
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert!(subchannel.read_subchannel_data(&mut reader).unwrap().is_some());
+
+    // the first packet of a file, which never finishes
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+        writer.write_bit(true).unwrap();
+        writer.write(18, 0u32).unwrap();
+        writer.write(3, 1u32).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write(32, 5u32).unwrap();
+        writer.write_bytes(b"maps/de_dust2.bsp\0").unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, 1000u32).unwrap();
+        writer.write_bytes(&[0xAA; FRAGMENT_SIZE]).unwrap();
+        writer.write(5, 0u8).unwrap();
+    }
+
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert!(subchannel.read_subchannel_data(&mut reader).unwrap().is_none());
+
+    // a plain message started after them is neither a file nor decompressed
+    let payload: Vec<u8> = (0..3000u32).map(|i| i as u8).collect();
+    let mut outgoing = OutgoingTransfer::new(&payload, CompressionMode::Never).unwrap();
+    let mut received = None;
+
+    while !outgoing.is_complete() {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+            outgoing.write_next(&mut writer).unwrap();
+            writer.write(7, 0u8).unwrap();
+        }
+
+        let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+        received = subchannel.read_subchannel_data(&mut reader).unwrap();
+
+        if received.is_none() {
+            assert_eq!(subchannel.transfer_info(SubchannelStreamType::Message).unwrap().filename, None);
+        }
+    }
+
+    assert_eq!(received.expect("transfer should complete").unwrap_payload(), payload);
+}
+
+#[test]
+fn test_disabled_stream_discards() {
+    let payload: Vec<u8> = (0..3000u32).map(|i| i as u8).collect();
+    let mut outgoing = OutgoingTransfer::new(&payload, CompressionMode::Never).unwrap();
+
+    let mut subchannel = SubChannel::new();
+    subchannel.set_enabled(false);
+
+    while !outgoing.is_complete() {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+            outgoing.write_next(&mut writer).unwrap();
+
+            // something else following the subchannel data, then pad out the final byte
+            writer.write(8, 0x5Au8).unwrap();
+            writer.write(7, 0u8).unwrap();
+        }
+
+        // nothing comes out, but the reader still ends up just past the subchannel data
+        let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+        assert!(subchannel.read_subchannel_data(&mut reader).unwrap().is_none());
+        assert_eq!(reader.read::<u8>(8).unwrap(), 0x5A);
+    }
+
+    assert!(subchannel.transfer_info(SubchannelStreamType::Message).is_none());
+}
+
+#[test]
+fn test_corrupt_compressed_transfer() {
+    let expected: Vec<u8> = b"reliable netmessages ".iter().cycle().take(2000).cloned().collect();
+    let mut compressed = Lzss::encode(&expected);
+
+    // break the LZSS header
+    compressed[0] ^= 0xFF;
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+        writer.write_bit(false).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write(MAX_FILE_SIZE_BITS, expected.len() as u32).unwrap();
+        writer.write(NET_MAX_PAYLOAD_BITS, compressed.len() as u32).unwrap();
+        writer.write_bytes(&compressed).unwrap();
+        writer.write(2, 0u8).unwrap();
+    }
+
+    // by default the transfer is dropped
+    let mut subchan = SubChannel::new();
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert!(subchan.read_subchannel_data(&mut reader).unwrap().is_none());
+    assert!(subchan.transfer_info(SubchannelStreamType::Message).is_none());
+
+    // strict fails outright
+    let mut subchan = SubChannel::new();
+    subchan.set_strict(true);
+    let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+    assert!(subchan.read_subchannel_data(&mut reader).is_err());
+}
+
+#[test]
+fn test_corrupt_compressed_multi_block_transfer() {
+    let send = |outgoing: &mut OutgoingTransfer, subchan: &mut SubChannel| {
+        let mut received = None;
+        while !outgoing.is_complete() {
+            let mut buf: Vec<u8> = Vec::new();
+            {
+                let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+                outgoing.write_next(&mut writer).unwrap();
+                writer.write(7, 0u8).unwrap();
+            }
+
+            let mut reader = BitReader::endian(std::io::Cursor::new(&buf[..]), LittleEndian);
+            received = subchan.read_subchannel_data(&mut reader).unwrap();
+        }
+        received
+    };
+
+    // a transfer spanning several datagrams which claims to be compressed, but isn't LZSS
+    let garbage: Vec<u8> = (0..3000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let mut outgoing = OutgoingTransfer::new(&garbage, CompressionMode::Never).unwrap();
+    outgoing.uncompressed_size = Some(6000);
+
+    // it is dropped without an error once the last batch arrives
+    let mut subchan = SubChannel::new();
+    assert!(send(&mut outgoing, &mut subchan).is_none());
+    assert!(subchan.transfer_info(SubchannelStreamType::Message).is_none());
+
+    // and the stream carries on with the next transfer
+    let payload: Vec<u8> = (0..3000u32).map(|i| (i % 7) as u8).collect();
+    let mut outgoing = OutgoingTransfer::new(&payload, CompressionMode::Above(128)).unwrap();
+    assert_eq!(send(&mut outgoing, &mut subchan).expect("transfer should complete").unwrap_payload(), payload);
+}
+
+#[test]
+fn test_outgoing_transfer_retransmit() {
+    let payload = vec![0x11u8; 3000];
+    let mut outgoing = OutgoingTransfer::new(&payload, CompressionMode::Never).unwrap();
+
+    let write = |outgoing: &mut OutgoingTransfer| {
+        let mut buf: Vec<u8> = Vec::new();
+        let mut writer = BitWriter::endian(std::io::Cursor::new(&mut buf), LittleEndian);
+        outgoing.write_next(&mut writer).unwrap();
+    };
+
+    // nothing in flight, nothing to resend
+    let start = Instant::now();
+    assert!(!outgoing.check_retransmit(start + Duration::from_secs(10)));
+
+    // the first batch is acknowledged, which gives a round trip to go on
+    write(&mut outgoing);
+    outgoing.acknowledge(Instant::now() + Duration::from_millis(50));
+    assert!(outgoing.retransmit_timeout() >= MIN_RETRANSMIT_TIMEOUT);
+    assert!(outgoing.retransmit_timeout() < INITIAL_RETRANSMIT_TIMEOUT);
+
+    // the second batch is lost
+    write(&mut outgoing);
+    assert!(!outgoing.check_retransmit(Instant::now()));
+
+    // timing out only says so, and doesn't again until another timeout has passed
+    let late = Instant::now() + Duration::from_secs(1);
+    assert!(outgoing.check_retransmit(late));
+    assert!(!outgoing.check_retransmit(late));
+    assert!(outgoing.is_complete());
+
+    // once it's known to be lost it is written again from where it started
+    outgoing.retransmit();
+    assert_eq!(outgoing.next_fragment, MAX_FRAGMENTS_PER_PACKET);
+    while !outgoing.is_complete() {
+        write(&mut outgoing);
+        outgoing.acknowledge(Instant::now());
+    }
+    assert!(outgoing.is_acknowledged());
+}
@@ -0,0 +1,64 @@
+use std::net::{UdpSocket, SocketAddr};
+use std::time::Duration;
+
+/// A datagram transport that channels send and receive packets over
+///
+/// This is implemented for `UdpSocket`, which is what a channel uses unless told otherwise.
+/// Other implementations can be swapped in for testing or to route packets somewhere else
+/// (e.g. through a relay).
+pub trait PacketTransport: Sized
+{
+    /// send a single datagram to the remote end
+    fn send(&self, buf: &[u8]) -> std::io::Result<usize>;
+
+    /// receive a single datagram into `buf`, returning its length
+    fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize>;
+
+    /// the address of the remote end
+    fn peer_addr(&self) -> std::io::Result<SocketAddr>;
+
+    /// the local address the transport is bound to, if it has one
+    fn local_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "transport has no local address"))
+    }
+
+    /// how long `recv` may block for, `None` blocks until a datagram arrives
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+
+    /// create another handle to the same transport, used when splitting a channel in two
+    fn try_clone(&self) -> std::io::Result<Self>;
+}
+
+impl PacketTransport for UdpSocket
+{
+    fn send(&self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        UdpSocket::send(self, buf)
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        UdpSocket::recv(self, buf)
+    }
+
+    fn peer_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        UdpSocket::peer_addr(self)
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr>
+    {
+        UdpSocket::local_addr(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        UdpSocket::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone(&self) -> std::io::Result<Self>
+    {
+        UdpSocket::try_clone(self)
+    }
+}
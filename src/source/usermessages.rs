@@ -0,0 +1,96 @@
+use crate::protoutil;
+use crate::source::protos::CSVCMsg_UserMessage;
+use crate::source::protos::cstrike15_usermessages::*;
+use ::protobuf::ProtobufEnum;
+use ECstrike15UserMessages::*;
+
+type ProtoMessage = Box<dyn ::protobuf::Message>;
+
+// a game specific user message, decoded from the payload of a svc_UserMessage
+pub struct UserMessage
+{
+    // the user message enum identifier for this message
+    msg_type: i32,
+
+    // the internal protobuf message for this user message
+    message: ProtoMessage,
+}
+
+// decodes the inner user message carried by a svc_UserMessage
+pub struct UserMessageDecoder
+{
+
+}
+
+impl UserMessageDecoder
+{
+    // decode the payload of a svc_UserMessage into its CS:GO user message
+    pub fn decode(user_message: &CSVCMsg_UserMessage) -> anyhow::Result<UserMessage>
+    {
+        let msg_type = user_message.get_msg_type();
+        let buffer = user_message.get_msg_data();
+
+        let um_enum = ECstrike15UserMessages::from_i32(msg_type);
+        if um_enum.is_some()
+        {
+            return match um_enum.unwrap()
+            {
+                CS_UM_HudText => UserMessage::from_buffer::<CCSUsrMsg_HudText>(buffer, msg_type),
+                CS_UM_SayText => UserMessage::from_buffer::<CCSUsrMsg_SayText>(buffer, msg_type),		// chat message from the server
+                CS_UM_SayText2 => UserMessage::from_buffer::<CCSUsrMsg_SayText2>(buffer, msg_type),		// chat message from a player
+                CS_UM_TextMsg => UserMessage::from_buffer::<CCSUsrMsg_TextMsg>(buffer, msg_type),		// localized text to print
+                CS_UM_HintText => UserMessage::from_buffer::<CCSUsrMsg_HintText>(buffer, msg_type),
+                CS_UM_KeyHintText => UserMessage::from_buffer::<CCSUsrMsg_KeyHintText>(buffer, msg_type),
+                other => Err(anyhow::anyhow!("User message {:?} not supported yet!", other)),
+            }
+        }
+
+        Err(anyhow::anyhow!("Unknown user message type {}!", msg_type))
+    }
+}
+
+impl UserMessage
+{
+    // create a user message from a network buffer
+    fn from_buffer<M>(message: &[u8], msg_type: i32) -> anyhow::Result<Self>
+        where M: ::protobuf::Message
+    {
+        let msg: ProtoMessage = Box::new(protoutil::deserialize::<M>(message)?);
+
+        Ok(UserMessage{
+            msg_type,
+            message: msg,
+        })
+    }
+
+    // get the inner proto message
+    pub fn inner(&self) -> &ProtoMessage
+    {
+        return &self.message;
+    }
+
+    // get the user message enum identifier for this message
+    pub fn get_type(&self) -> i32
+    {
+        return self.msg_type;
+    }
+
+    // get the inner proto message as a concrete type, None if it is a different message type
+    pub fn downcast<M>(&self) -> Option<&M>
+        where M: ::protobuf::Message
+    {
+        return self.message.as_any().downcast_ref::<M>();
+    }
+
+    // get the type name of this user message
+    pub fn get_type_name(&self) -> &'static str
+    {
+        let um_enum = ECstrike15UserMessages::from_i32(self.msg_type);
+        if um_enum.is_some()
+        {
+            return um_enum.unwrap().descriptor().name();
+        }
+
+        return "<Unknown User Message Type>";
+    }
+}
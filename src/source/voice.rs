@@ -0,0 +1,138 @@
+use anyhow::Result;
+use crate::source::channel::VoiceSettings;
+
+// codec name the server announces in svc_VoiceInit when it wants opus voice
+pub const OPUS_CODEC: &str = "vaudio_opus";
+
+// sample rate of the 16-bit mono PCM given to VoiceEncoder::encode
+pub const VOICE_SAMPLE_RATE: usize = 24000;
+
+// samples in one 20ms frame, PCM is encoded a whole frame at a time
+pub const VOICE_FRAME_SAMPLES: usize = VOICE_SAMPLE_RATE / 50;
+
+// bitrate given for each step of the server's voice quality setting
+#[cfg(feature = "opus")]
+const OPUS_BITRATE_PER_QUALITY: i32 = 8000;
+
+// largest packet opus can encode a single frame to
+#[cfg(feature = "opus")]
+const MAX_OPUS_PACKET: usize = 1275;
+
+// Encodes PCM into clc_VoiceData payloads with the codec the server announced
+//
+// Only opus is supported, and only when built with the `opus` feature. Each encoded frame is
+// sent with its length in front of it as a little endian u16.
+pub struct VoiceEncoder
+{
+    // the server settings this encoder was created for
+    settings: VoiceSettings,
+
+    #[cfg(feature = "opus")]
+    opus: audiopus::coder::Encoder,
+}
+
+impl VoiceEncoder
+{
+    // create an encoder for the server's voice settings, fails if we can't encode its codec
+    pub fn new(settings: &VoiceSettings) -> Result<Self>
+    {
+        if settings.codec != OPUS_CODEC {
+            return Err(anyhow::anyhow!("Voice codec {} is not supported, only {} is", settings.codec, OPUS_CODEC));
+        }
+
+        Self::new_opus(settings)
+    }
+
+    #[cfg(feature = "opus")]
+    fn new_opus(settings: &VoiceSettings) -> Result<Self>
+    {
+        use audiopus::{Application, Bitrate, Channels, SampleRate};
+
+        let mut opus = audiopus::coder::Encoder::new(SampleRate::Hz24000, Channels::Mono, Application::Voip)?;
+
+        // better quality settings get more bits to work with
+        let quality = std::cmp::max(settings.quality, 1);
+        opus.set_bitrate(Bitrate::BitsPerSecond(quality * OPUS_BITRATE_PER_QUALITY))?;
+
+        Ok(VoiceEncoder { settings: settings.clone(), opus })
+    }
+
+    #[cfg(not(feature = "opus"))]
+    fn new_opus(settings: &VoiceSettings) -> Result<Self>
+    {
+        Err(anyhow::anyhow!("Voice codec {} needs the opus feature", settings.codec))
+    }
+
+    // the server settings this encoder was created for
+    pub fn settings(&self) -> &VoiceSettings
+    {
+        return &self.settings;
+    }
+
+    // encode 16-bit mono PCM at VOICE_SAMPLE_RATE, which must be a whole number of frames
+    pub fn encode(&mut self, pcm: &[i16]) -> Result<Vec<u8>>
+    {
+        if pcm.is_empty() || pcm.len() % VOICE_FRAME_SAMPLES != 0 {
+            return Err(anyhow::anyhow!("Voice must be whole frames of {} samples, got {} samples", VOICE_FRAME_SAMPLES, pcm.len()));
+        }
+
+        self.encode_frames(pcm)
+    }
+
+    #[cfg(feature = "opus")]
+    fn encode_frames(&mut self, pcm: &[i16]) -> Result<Vec<u8>>
+    {
+        let mut data = Vec::new();
+        let mut packet = [0u8; MAX_OPUS_PACKET];
+
+        for frame in pcm.chunks(VOICE_FRAME_SAMPLES) {
+            let len = self.opus.encode(frame, &mut packet)?;
+            data.extend_from_slice(&(len as u16).to_le_bytes());
+            data.extend_from_slice(&packet[..len]);
+        }
+
+        Ok(data)
+    }
+
+    #[cfg(not(feature = "opus"))]
+    fn encode_frames(&mut self, _pcm: &[i16]) -> Result<Vec<u8>>
+    {
+        Err(anyhow::anyhow!("Voice codec {} needs the opus feature", self.settings.codec))
+    }
+}
+
+#[cfg(test)]
+fn settings(codec: &str) -> VoiceSettings
+{
+    VoiceSettings { codec: codec.to_string(), quality: 5, version: 0 }
+}
+
+#[test]
+fn test_voice_unsupported_codec() {
+    assert!(VoiceEncoder::new(&settings("vaudio_celt")).is_err());
+    assert!(VoiceEncoder::new(&settings("steam")).is_err());
+}
+
+#[cfg(not(feature = "opus"))]
+#[test]
+fn test_voice_opus_needs_feature() {
+    let err = VoiceEncoder::new(&settings(OPUS_CODEC)).err().unwrap();
+    assert!(err.to_string().contains("opus feature"));
+}
+
+#[cfg(feature = "opus")]
+#[test]
+fn test_voice_opus_encode() {
+    let mut encoder = VoiceEncoder::new(&settings(OPUS_CODEC)).unwrap();
+
+    // partial frames can't be encoded
+    assert!(encoder.encode(&[0; 100]).is_err());
+
+    let pcm = vec![0i16; VOICE_FRAME_SAMPLES * 2];
+    let data = encoder.encode(&pcm).unwrap();
+
+    // two length prefixed frames
+    let first = u16::from_le_bytes([data[0], data[1]]) as usize;
+    let second = u16::from_le_bytes([data[2 + first], data[3 + first]]) as usize;
+    assert_eq!(data.len(), 2 + first + 2 + second);
+}
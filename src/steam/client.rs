@@ -2,11 +2,15 @@ use steamworks::*;
 use std::time::Duration;
 use std::thread::JoinHandle;
 use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::net::{Ipv4Addr};
 use anyhow::Context;
 use csgogcprotos::gcsystemmsgs::{EGCBaseClientMsg};
-use csgogcprotos::cstrike15_gcmessages::{ECsgoGCMsg, CMsgGCCStrike15_v2_MatchmakingGC2ClientHello, CMsgGCCStrike15_v2_ClientRequestJoinServerData};
+use csgogcprotos::cstrike15_gcmessages::{ECsgoGCMsg, CMsgGCCStrike15_v2_MatchmakingGC2ClientHello, CMsgGCCStrike15_v2_ClientRequestJoinServerData, CMsgGCCStrike15_v2_ClientRequestJoinFriendData, CMsgGCCStrike15_v2_MatchmakingGC2ClientReserve};
 use crate::protoutil;
+use crate::source::packets::SteamAuthInfo;
+use log::warn;
+use std::fmt;
 
 /// Represents the state of a logged in steam client
 pub struct SteamClient
@@ -18,7 +22,11 @@ pub struct SteamClient
     gc_queue: GCMessageQueue<ClientManager>,
 
     /// Thread object responsible for constantly calling Steam callbacks
-    _main_thread: JoinHandle<()>,
+    /// Taken and joined when the client is dropped
+    main_thread: Option<JoinHandle<()>>,
+
+    /// Set to tell the callback thread to exit
+    stop_main_thread: Arc<AtomicBool>,
 
     /// The current internal state of this client
     state: Arc<Mutex<SteamClientState>>,
@@ -29,9 +37,105 @@ pub struct SteamClientState
 {
     /// CS:GO Matchmaking Account ID, received from matchmaking hello
     accountid: u32,
+
+    /// Whether the GC has welcomed us, set once the matchmaking hello is received
+    gc_connected: bool,
+
+    /// Matchmaking details sent along with the matchmaking hello
+    matchmaking: Option<MatchmakingState>,
+}
+
+/// The player's matchmaking state, as the GC reported it in its matchmaking hello
+#[derive(Debug, Clone, Default)]
+pub struct MatchmakingState
+{
+    /// CS:GO Matchmaking Account ID
+    pub account_id: u32,
+
+    /// Competitive skill group, if the player has a ranking
+    pub rank_id: Option<u32>,
+
+    /// Competitive wins, if the player has a ranking
+    pub wins: Option<u32>,
+
+    /// Profile rank level
+    pub player_level: i32,
+
+    /// Experience towards the next profile rank
+    pub player_cur_xp: i32,
+
+    /// Seconds remaining on a matchmaking cooldown, 0 if there is none
+    pub penalty_seconds: u32,
+
+    /// Why the matchmaking cooldown was given
+    pub penalty_reason: u32,
+
+    /// Whether the account is VAC banned
+    pub vac_banned: bool,
+
+    /// Commendations received from other players
+    pub commends_friendly: u32,
+    pub commends_teaching: u32,
+    pub commends_leader: u32,
+}
+
+impl MatchmakingState
+{
+    /// Pull the matchmaking state out of the GC's matchmaking hello
+    fn from_hello(hello: &CMsgGCCStrike15_v2_MatchmakingGC2ClientHello) -> MatchmakingState
+    {
+        // ranking is only present once the player has played competitive
+        let ranking = if hello.has_ranking() { Some(hello.get_ranking()) } else { None };
+        let commendation = hello.get_commendation();
+
+        return MatchmakingState {
+            account_id: hello.get_account_id(),
+            rank_id: ranking.map(|ranking| ranking.get_rank_id()),
+            wins: ranking.map(|ranking| ranking.get_wins()),
+            player_level: hello.get_player_level(),
+            player_cur_xp: hello.get_player_cur_xp(),
+            penalty_seconds: hello.get_penalty_seconds(),
+            penalty_reason: hello.get_penalty_reason(),
+            vac_banned: hello.get_vac_banned() != 0,
+            commends_friendly: commendation.get_cmd_friendly(),
+            commends_teaching: commendation.get_cmd_teaching(),
+            commends_leader: commendation.get_cmd_leader(),
+        }
+    }
+}
+
+/// Why a message could not be sent to the game coordinator
+#[derive(Debug)]
+pub enum GcSendError
+{
+    /// The GC has not welcomed us yet, waiting for it to (re)connect may help
+    NotConnected,
+
+    /// Steam refused the message while connected to the GC, usually because its queue is full
+    QueueFull,
+
+    /// The message could not be encoded, retrying will not help
+    Serialization(String),
+}
+
+impl fmt::Display for GcSendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self
+        {
+            GcSendError::NotConnected => write!(f, "Not connected to the game coordinator"),
+            GcSendError::QueueFull => write!(f, "Game coordinator message queue is full"),
+            GcSendError::Serialization(reason) => write!(f, "Failed to serialize GC message: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for GcSendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
 }
 
-/// The result of a call to `request_join_server`
+/// The result of a call to `request_join_server` or `request_join_lobby`
 #[derive(Debug)]
 pub struct JoinServerReservation
 {
@@ -49,6 +153,28 @@ pub struct JoinServerReservation
     pub reservationid: u64,
 }
 
+impl JoinServerReservation
+{
+    /// Interpret the reservation the GC sent in response to a join request
+    ///
+    /// The GC leaves out the reservation if it won't let us join (server full, wrong version, etc.)
+    fn from_reserve(res: Option<&CMsgGCCStrike15_v2_MatchmakingGC2ClientReserve>, errormsg: &str) -> anyhow::Result<JoinServerReservation>
+    {
+        let reservation = match res {
+            Some(reservation) => reservation,
+            None if errormsg.is_empty() => return Err(anyhow::anyhow!("Game coordinator did not grant a server reservation")),
+            None => return Err(anyhow::anyhow!("Game coordinator did not grant a server reservation: {}", errormsg)),
+        };
+
+        return Ok(JoinServerReservation{
+            reservationid: reservation.get_reservationid(),
+            direct_udp_ip: Ipv4Addr::from(reservation.get_direct_udp_ip()),
+            direct_udp_port: reservation.get_direct_udp_port(),
+            serverid: reservation.get_serverid()
+        })
+    }
+}
+
 /// Helper to transform an enum into a proto id
 fn proto_id(msg_type: u32) -> u32
 {
@@ -59,6 +185,17 @@ impl SteamClient {
     /// Connect to Steam and the Game Coordinator
     /// Returns an active client
     pub fn connect() -> anyhow::Result<SteamClient>
+    {
+        Self::connect_with_logon_timeout(None)
+    }
+
+    /// Connect to Steam and the Game Coordinator, first waiting up to `logon_timeout` for the
+    /// Steam client itself to finish logging on to Steam.
+    ///
+    /// Useful when launching alongside Steam, where the GC hello would otherwise go unanswered
+    /// until Steam has connected and only fail with a GC timeout. With `None` the GC hello is
+    /// sent straight away, the same as `connect`.
+    pub fn connect_with_logon_timeout(logon_timeout: Option<Duration>) -> anyhow::Result<SteamClient>
     {
         // create a steam client interface... the user must be logged in already on Steam
         let res = Client::init();
@@ -72,21 +209,30 @@ impl SteamClient {
         let gc_queue = GCMessageQueue::new(client.clone());
 
         // create a thread to constantly call steam callbacks
-        let main_thread = SteamClient::spawn_main_thread(single, Duration::from_millis(10));
+        let stop_main_thread = Arc::new(AtomicBool::new(false));
+        let main_thread = SteamClient::spawn_main_thread(single, Duration::from_millis(10), stop_main_thread.clone());
 
         // internal state keeping that is updated when callbacks fire for certain packets
         let state = Arc::new(Mutex::new(SteamClientState{
             accountid: 0xFFFFFFFF,
+            gc_connected: false,
+            matchmaking: None,
         }));
 
         // create steam client object
         let steam = SteamClient {
             _client: client,
             gc_queue,
-            _main_thread: main_thread,
+            main_thread: Some(main_thread),
+            stop_main_thread,
             state
         };
 
+        // the GC can't be reached until Steam itself is logged on
+        if let Some(logon_timeout) = logon_timeout {
+            steam.wait_for_logon(logon_timeout)?;
+        }
+
         // perform a handshake to login to the GC
         steam.do_hello_handshake()?;
 
@@ -121,9 +267,12 @@ impl SteamClient {
         self.gc_queue.install_callback(
             proto_id(enum_val),
             move |_pkt| {
-                // decode protobuf packet
-                let res = protoutil::deserialize::<ProtoMsgType>(&_pkt.body).unwrap();
-                callback(res);
+                // decode protobuf packet, a bad packet must not take down the callback thread
+                // so just log it and skip it
+                match protoutil::deserialize::<ProtoMsgType>(&_pkt.body) {
+                    Ok(res) => callback(res),
+                    Err(e) => warn!("Failed to decode GC message {:#x}: {}", enum_val, e),
+                }
             }
         )
     }
@@ -176,11 +325,10 @@ impl SteamClient {
         );
 
         // send request
-        if !self.gc_queue.send_message(
-            proto_id(to_send_type),
-            &protoutil::serialize(to_send)?) {
-            return Err(anyhow::anyhow!("Could not send message {}", to_send_type))
-        }
+        let body = protoutil::serialize(to_send)
+            .map_err(|e| GcSendError::Serialization(e.to_string()))?;
+        self.send_gc_message(to_send_type, &body)
+            .context(format!("Could not send message {}", to_send_type))?;
 
         // wait a bit for the response
         receiver
@@ -190,6 +338,71 @@ impl SteamClient {
         return Ok(())
     }
 
+    /// Send a protobuf request to the game coordinator and wait on the decoded response
+    ///
+    /// This is the blocking form of `do_request`, for GC endpoints the client doesn't wrap itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `send_type` - The packet enum value for the request being sent
+    /// * `send_msg`  - The `protobuf::Message` structure for the packet being sent
+    /// * `recv_type` - The packet enum value for the response packet
+    /// * `timeout`   - How long to wait on the response before returning Err
+    pub fn gc_request<SendMsgType, RecvMsgType>(
+        &self,
+        send_type: u32,
+        send_msg: SendMsgType,
+        recv_type: u32,
+        timeout: Duration
+    ) -> anyhow::Result<RecvMsgType>
+        where SendMsgType: Send + protobuf::Message,
+              RecvMsgType: Send + protobuf::Message
+    {
+        // channel to hand the response back from the callback thread
+        let (send, recv) = mpsc::sync_channel::<RecvMsgType>(1);
+
+        self.do_request::<RecvMsgType, _, _>(
+            send_type,
+            send_msg,
+            recv_type,
+            timeout,
+            move |pkt| {
+                // a late duplicate response has nowhere to go, which is fine
+                let _ = send.try_send(pkt);
+            }
+        )?;
+
+        // do_request only returns once the response has been received
+        return Ok(recv.recv()?);
+    }
+
+    /// Send a raw message to the game coordinator, explaining why if it could not be sent
+    fn send_gc_message(&self, msg_type: u32, body: &[u8]) -> Result<(), GcSendError>
+    {
+        if self.gc_queue.send_message(proto_id(msg_type), body) {
+            return Ok(())
+        }
+
+        // steam only tells us that it failed, so use what we know about the connection to say why
+        if self.is_gc_connected() {
+            Err(GcSendError::QueueFull)
+        } else {
+            Err(GcSendError::NotConnected)
+        }
+    }
+
+    /// Whether the game coordinator has welcomed us
+    pub fn is_gc_connected(&self) -> bool
+    {
+        return self.state.lock().unwrap().gc_connected;
+    }
+
+    /// The matchmaking state the GC sent when it welcomed us, None until it has
+    pub fn matchmaking_state(&self) -> Option<MatchmakingState>
+    {
+        return self.state.lock().unwrap().matchmaking.clone();
+    }
+
     /// Get an authentication ticket to authenticate with a server.
     ///
     /// This ticket must be sent to the server to verify that your user's identity
@@ -204,15 +417,23 @@ impl SteamClient {
         return Ok(ticket)
     }
 
+    /// Get the SteamID and a new authentication ticket for the currently logged in user,
+    /// ready to be sent in a `C2sConnect` packet.
+    pub fn get_auth_info(&self) -> anyhow::Result<SteamAuthInfo>
+    {
+        let auth_ticket = self.get_auth_ticket()?;
+
+        return Ok(SteamAuthInfo::new(self.get_steam_id().raw(), auth_ticket))
+    }
+
     /// Get the SteamID of the currently logged in user.
     pub fn get_steam_id(&self) -> steamworks::SteamId
     {
         return self._client.user().steam_id();
     }
 
-    /// Send a request to join a server and wait on the result
-    /// Returns a `JoinServerReservation` struct which represents the server reservation
-    pub fn request_join_server(&self, version: u32, serverid: u64, server_ip: u32, server_port: u32) -> anyhow::Result<JoinServerReservation>
+    /// Build the request packet sent to the GC to join a server
+    fn join_server_request(&self, version: u32, serverid: u64, server_ip: u32, server_port: u32) -> CMsgGCCStrike15_v2_ClientRequestJoinServerData
     {
         let mut msg = CMsgGCCStrike15_v2_ClientRequestJoinServerData::new();
 
@@ -227,8 +448,36 @@ impl SteamClient {
         // server's port (as we know it)
         msg.set_server_port(server_port);
 
+        return msg;
+    }
+
+    /// Send a request to join a server and wait on the full response from the GC
+    ///
+    /// Unlike `request_join_server`, this returns the response exactly as the GC sent it, which is
+    /// useful for finding out why a reservation was not granted (e.g. the `res` field is absent)
+    pub fn request_join_server_raw(&self, version: u32, serverid: u64, server_ip: u32, server_port: u32) -> anyhow::Result<CMsgGCCStrike15_v2_ClientRequestJoinServerData>
+    {
+        let msg = self.join_server_request(version, serverid, server_ip, server_port);
+
+        // pass the whole response back untouched
+        self.gc_request(
+            ECsgoGCMsg::k_EMsgGCCStrike15_v2_ClientRequestJoinServerData as u32,
+            msg,
+            ECsgoGCMsg::k_EMsgGCCStrike15_v2_ClientRequestJoinServerData as u32,
+            Duration::from_millis(10000)
+        )
+    }
+
+    /// Send a request to join a server and wait on the result
+    /// Returns a `JoinServerReservation` struct which represents the server reservation, or an
+    /// error if the GC declined to grant one
+    pub fn request_join_server(&self, version: u32, serverid: u64, server_ip: u32, server_port: u32) -> anyhow::Result<JoinServerReservation>
+    {
+        let msg = self.join_server_request(version, serverid, server_ip, server_port);
+
         // channel to wait on reservation when it comes in
-        let (send, recv) = mpsc::sync_channel(1);
+        // carries an error if the gc declines to give us a reservation
+        let (send, recv) = mpsc::sync_channel::<anyhow::Result<JoinServerReservation>>(1);
 
         // perform the request to join a server
         self.do_request::<CMsgGCCStrike15_v2_ClientRequestJoinServerData, _, _>(
@@ -237,24 +486,75 @@ impl SteamClient {
             ECsgoGCMsg::k_EMsgGCCStrike15_v2_ClientRequestJoinServerData as u32,
             Duration::from_millis(10000),
             move |pkt| {
-               // we got a reservation from the server
-               let reservation = pkt.res.unwrap();
-
                // interpret the protobuf packet into a structure we actually want to return
-               let reservation = JoinServerReservation{
-                   reservationid: reservation.get_reservationid(),
-                   direct_udp_ip: Ipv4Addr::from(reservation.get_direct_udp_ip()),
-                   direct_udp_port: reservation.get_direct_udp_port(),
-                   serverid: reservation.get_serverid()
-               };
+               // this runs on the shared callback thread so don't panic here
+               let reservation = JoinServerReservation::from_reserve(pkt.res.as_ref(), pkt.get_errormsg());
 
                // send that over the channel, which will hit the recv.recv() and unblock it
-               send.send(reservation).unwrap();
+               // the receiver is gone if we already timed out waiting, so just drop the reply
+               let _ = send.send(reservation);
             }
         )?;
 
         // wait until the request finishes or times out
-        return Ok(recv.recv()?);
+        return recv.recv()?;
+    }
+
+    /// Send a request to follow a friend into their lobby's server and wait on the result
+    ///
+    /// Rather than naming the server, this asks the GC for a reservation on whichever server the
+    /// friend is playing on. `account_id` is the friend's account id (the low 32 bits of their
+    /// steamid), and `join_token`/`join_ipp` are the values the friend advertises in their rich
+    /// presence for others to join them with.
+    pub fn request_join_lobby(&self, version: u32, account_id: u32, join_token: u32, join_ipp: u32) -> anyhow::Result<JoinServerReservation>
+    {
+        let mut msg = CMsgGCCStrike15_v2_ClientRequestJoinFriendData::new();
+
+        // version of the client connecting
+        msg.set_version(version);
+        // account id of the friend we're following
+        msg.set_account_id(account_id);
+        // join details from the friend's rich presence
+        msg.set_join_token(join_token);
+        msg.set_join_ipp(join_ipp);
+
+        // channel to wait on reservation when it comes in
+        // carries an error if the gc declines to give us a reservation
+        let (send, recv) = mpsc::sync_channel::<anyhow::Result<JoinServerReservation>>(1);
+
+        self.do_request::<CMsgGCCStrike15_v2_ClientRequestJoinFriendData, _, _>(
+            ECsgoGCMsg::k_EMsgGCCStrike15_v2_ClientRequestJoinFriendData as u32,
+            msg,
+            ECsgoGCMsg::k_EMsgGCCStrike15_v2_ClientRequestJoinFriendData as u32,
+            Duration::from_millis(10000),
+            move |pkt| {
+               let reservation = JoinServerReservation::from_reserve(pkt.res.as_ref(), pkt.get_errormsg());
+
+               // the receiver is gone if we already timed out waiting, so just drop the reply
+               let _ = send.send(reservation);
+            }
+        )?;
+
+        // wait until the request finishes or times out
+        return recv.recv()?;
+    }
+
+    /// Wait for the Steam client to report that it is logged on to Steam. The callback thread
+    /// is already running, so the logged on state updates while this polls
+    fn wait_for_logon(&self, timeout: Duration) -> anyhow::Result<()>
+    {
+        let deadline = std::time::Instant::now() + timeout;
+
+        while !self._client.user().logged_on()
+        {
+            if std::time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!("Steam is not logged on after waiting {:?}, is Steam still connecting?", timeout));
+            }
+
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(())
     }
 
     /// Send a client hello and block waiting for the response
@@ -279,7 +579,11 @@ impl SteamClient {
                 println!("Logged into CS:GO Matchmaking accountid='{}'", account_id);
 
                 // remember our account id in the steam state
-                state_cl.lock().unwrap().accountid = account_id;
+                let mut state = state_cl.lock().unwrap();
+                state.accountid = account_id;
+                state.gc_connected = true;
+                state.matchmaking = Some(MatchmakingState::from_hello(&pkt));
+                drop(state);
 
                 // alert that we've successfully logged in
                 sender_cl.send(true).unwrap();
@@ -290,11 +594,8 @@ impl SteamClient {
         for _i in 0..10
         {
             // send a login request to the GC
-            if !self.gc_queue.send_message(
-                proto_id(EGCBaseClientMsg::k_EMsgGCClientHello as u32),
-                &[]) {
-                return Err(anyhow::anyhow!("Could not send GC hello"))
-            }
+            self.send_gc_message(EGCBaseClientMsg::k_EMsgGCClientHello as u32, &[])
+                .context("Could not send GC hello")?;
 
             // wait a bit for the response
             result = receiver
@@ -311,14 +612,29 @@ impl SteamClient {
         Err(result.unwrap_err())
     }
 
-    /// Spawn the main callback handling thread
-    fn spawn_main_thread(single: SingleClient<ClientManager>, callback_interval: Duration) -> JoinHandle<()> {
+    /// Spawn the main callback handling thread, which runs until `stop` is set
+    fn spawn_main_thread(single: SingleClient<ClientManager>, callback_interval: Duration, stop: Arc<AtomicBool>) -> JoinHandle<()> {
         std::thread::spawn(move || {
             // loop constantly calling steam callbacks every 'frame'
-            loop {
+            while !stop.load(Ordering::Relaxed) {
                 single.run_callbacks();
                 ::std::thread::sleep(callback_interval);
             }
         })
     }
 }
+
+impl Drop for SteamClient {
+    /// Stop the callback thread and wait for it to exit, so it never runs callbacks against
+    /// a client that has gone away
+    fn drop(&mut self)
+    {
+        self.stop_main_thread.store(true, Ordering::Relaxed);
+
+        if let Some(main_thread) = self.main_thread.take() {
+            if main_thread.join().is_err() {
+                warn!("Steam callback thread panicked");
+            }
+        }
+    }
+}
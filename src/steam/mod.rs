@@ -1,2 +1,4 @@
 pub mod client;
-pub use client::*;
\ No newline at end of file
+pub mod steamid;
+pub use client::*;
+pub use steamid::*;
@@ -0,0 +1,49 @@
+/// The components packed into a 64-bit Steam3 ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SteamIdParts
+{
+    /// Steam universe, 1 is the public universe
+    pub universe: u8,
+
+    /// Account type, 1 is an individual user and 3 a game server
+    pub account_type: u8,
+
+    /// Account instance, 1 for a user's desktop instance
+    pub instance: u32,
+
+    /// Account id, this is also the matchmaking account id the GC uses for a player
+    pub account_id: u32,
+}
+
+/// Split a raw steamid into its components
+///
+/// The layout from the top bit down is 8 bits of universe, 4 bits of account type,
+/// 20 bits of instance and 32 bits of account id.
+pub fn decode_steamid(raw: u64) -> SteamIdParts
+{
+    return SteamIdParts {
+        universe: (raw >> 56) as u8,
+        account_type: ((raw >> 52) & 0xF) as u8,
+        instance: ((raw >> 32) & 0xFFFFF) as u32,
+        account_id: raw as u32,
+    }
+}
+
+/// Pack components back into a raw steamid, fields wider than their slot are truncated
+pub fn encode_steamid(parts: &SteamIdParts) -> u64
+{
+    return ((parts.universe as u64) << 56)
+        | (((parts.account_type & 0xF) as u64) << 52)
+        | (((parts.instance & 0xFFFFF) as u64) << 32)
+        | (parts.account_id as u64);
+}
+
+#[test]
+fn test_steamid_roundtrip() {
+    // a public universe individual desktop account
+    let raw: u64 = 76561197960287930;
+    let parts = decode_steamid(raw);
+
+    assert_eq!(parts, SteamIdParts { universe: 1, account_type: 1, instance: 1, account_id: 22202 });
+    assert_eq!(encode_steamid(&parts), raw);
+}